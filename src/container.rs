@@ -0,0 +1,120 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::rc::Rc;
+
+use flow::{layout_flow, LayoutFlowDisplay, LayoutFlowItem};
+use grid::{LayoutGrid, LayoutGridPlacement};
+use text_layout::{LayoutTextFragment, TextMetrics};
+use types::{LayoutClientSizeF, LayoutPlacedArea};
+
+/// A layout mode layout engines hand the children of a node over to,
+/// instead of laying them out as flex items.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum LayoutContainer {
+    Grid(LayoutGrid),
+    /// Block and inline flow, like a paragraph.
+    Flow
+}
+
+/// How a node is placed when its parent is a grid or flow container.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct LayoutContainerItem {
+    pub grid_placement: LayoutGridPlacement,
+    pub flow_display: LayoutFlowDisplay
+}
+
+/// A visible child of a grid or flow container, as seen by the layout
+/// engine laying out the container.
+pub trait TLayoutContainerChild {
+    fn container_item(&self) -> LayoutContainerItem;
+
+    /// Line breaking metrics of this child's text, if it's measured as text.
+    fn text_metrics(&self) -> Option<Rc<TextMetrics>>;
+
+    /// Size of this child's margin box when its whole subtree is laid out
+    /// inside the given size, or sized to its content along axes without one.
+    fn measure(&self, width: Option<f32>, height: Option<f32>) -> LayoutClientSizeF;
+}
+
+/// Where a grid or flow container places each of its visible children, and
+/// the size of the container's content box.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutContainerLayout {
+    pub areas: Vec<LayoutPlacedArea>,
+    /// Pieces of text on each line of children laid out inline, relative to
+    /// their border box.
+    pub text_fragments: Vec<Vec<LayoutTextFragment>>,
+    pub size: LayoutClientSizeF
+}
+
+impl LayoutContainer {
+    /// Lays out children inside a content box of the given size, or sized to
+    /// fit them along axes without one.
+    pub fn layout<C>(&self, children: &[C], width: Option<f32>, height: Option<f32>) -> LayoutContainerLayout
+    where
+        C: TLayoutContainerChild
+    {
+        match *self {
+            LayoutContainer::Grid(ref grid) => layout_grid(grid, children, width, height),
+            LayoutContainer::Flow => layout_flow_children(children, width)
+        }
+    }
+}
+
+fn layout_grid<C>(grid: &LayoutGrid, children: &[C], width: Option<f32>, height: Option<f32>) -> LayoutContainerLayout
+where
+    C: TLayoutContainerChild
+{
+    let placements = children.iter().map(|v| v.container_item().grid_placement).collect::<Vec<_>>();
+    let layout = grid.layout(&placements, width, height, |index, width| children[index].measure(width, None));
+
+    LayoutContainerLayout {
+        areas: layout
+            .areas
+            .iter()
+            .map(|v| LayoutPlacedArea::new(v.position, v.size.width, Some(v.size.height)))
+            .collect(),
+        text_fragments: vec![vec![]; children.len()],
+        size: layout.size
+    }
+}
+
+fn layout_flow_children<C>(children: &[C], width: Option<f32>) -> LayoutContainerLayout
+where
+    C: TLayoutContainerChild
+{
+    let items = children
+        .iter()
+        .map(|v| match (v.container_item().flow_display, v.text_metrics()) {
+            // Block children take up the whole width of the container.
+            (LayoutFlowDisplay::Block, _) => LayoutFlowItem::Block(v.measure(width, None)),
+            (LayoutFlowDisplay::Inline, Some(metrics)) => LayoutFlowItem::Text(metrics),
+            (LayoutFlowDisplay::Inline, None) => LayoutFlowItem::Atomic(v.measure(None, None))
+        })
+        .collect::<Vec<_>>();
+    let layout = layout_flow(&items, width);
+
+    let areas = items
+        .iter()
+        .zip(layout.fragments.iter())
+        .map(|(item, fragment)| match *item {
+            LayoutFlowItem::Block(_) => LayoutPlacedArea::new(fragment.rect.position, fragment.rect.size.width, None),
+            _ => LayoutPlacedArea::new(fragment.rect.position, fragment.rect.size.width, Some(fragment.rect.size.height))
+        })
+        .collect();
+
+    LayoutContainerLayout {
+        areas,
+        text_fragments: layout.fragments.into_iter().map(|v| v.lines).collect(),
+        size: layout.size
+    }
+}
@@ -0,0 +1,61 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use types::LayoutBoundingClientRect;
+
+/// Areas of the page that need to be repainted because of layout changes.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct LayoutDamage {
+    rects: Vec<LayoutBoundingClientRect>
+}
+
+impl LayoutDamage {
+    pub fn new() -> Self {
+        LayoutDamage::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn add(&mut self, rect: LayoutBoundingClientRect) {
+        if !rect.is_empty() {
+            self.rects.push(rect);
+        }
+    }
+
+    /// Damages both where a node was and where it is now. Nodes that
+    /// just appeared don't have a previous rect.
+    pub fn add_change(&mut self, previous: Option<LayoutBoundingClientRect>, current: LayoutBoundingClientRect) {
+        if let Some(previous) = previous {
+            self.add(previous);
+        }
+        self.add(current);
+    }
+
+    /// Invalidation rects covering all damaged areas, where overlapping
+    /// rects are merged into their union so that no area is repainted twice.
+    pub fn rects(&self) -> Vec<LayoutBoundingClientRect> {
+        let mut merged: Vec<LayoutBoundingClientRect> = vec![];
+        for rect in &self.rects {
+            let mut rect = *rect;
+            loop {
+                let index = match merged.iter().position(|v| v.intersects(&rect)) {
+                    Some(index) => index,
+                    None => break
+                };
+                rect = rect.union(&merged.swap_remove(index));
+            }
+            merged.push(rect);
+        }
+        merged
+    }
+}
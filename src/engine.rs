@@ -0,0 +1,148 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::fmt;
+use std::rc::Rc;
+
+use container::{LayoutContainer, LayoutContainerItem};
+#[cfg(not(feature = "yoga"))]
+use flexbox_engine::FlexboxLayoutEngine;
+use measure::{LayoutMeasureFunc, LayoutMeasureMode};
+use styles::{LayoutReflowDirection, LayoutStyle};
+use text_layout::{LayoutTextFragment, TextMetrics};
+use types::{LayoutBoundingClientRectF, LayoutBoxEdgesF, LayoutClientSizeF};
+#[cfg(feature = "yoga")]
+use yoga_engine::YogaLayoutEngine;
+
+/// The engine layout nodes use unless told otherwise, which is Yoga when
+/// it's built in, or the pure Rust flexbox engine otherwise.
+#[cfg(feature = "yoga")]
+pub type DefaultLayoutEngine = YogaLayoutEngine;
+#[cfg(not(feature = "yoga"))]
+pub type DefaultLayoutEngine = FlexboxLayoutEngine;
+
+/// Everything a layout engine needs to know in order to measure a leaf node
+/// with intrinsic dimensions, regardless of what kind of content it holds.
+#[derive(Clone)]
+pub enum LayoutMeasureContext {
+    Image { width: u32, height: u32 },
+    Text { metrics: Rc<TextMetrics> },
+    Custom { measure: LayoutMeasureFunc }
+}
+
+impl fmt::Debug for LayoutMeasureContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            LayoutMeasureContext::Image { width, height } => write!(f, "Image {{ width: {}, height: {} }}", width, height),
+            LayoutMeasureContext::Text { ref metrics } => write!(f, "Text {{ metrics: {:?} }}", metrics),
+            LayoutMeasureContext::Custom { .. } => write!(f, "Custom")
+        }
+    }
+}
+
+impl LayoutMeasureContext {
+    pub fn measure(
+        &self,
+        suggested_width: f32,
+        width_mode: LayoutMeasureMode,
+        suggested_height: f32,
+        height_mode: LayoutMeasureMode
+    ) -> LayoutClientSizeF {
+        let (measured_width, measured_height) = match *self {
+            LayoutMeasureContext::Image { width, height } => (width as f32, height as f32),
+            LayoutMeasureContext::Text { ref metrics } => metrics.measure(match width_mode {
+                LayoutMeasureMode::Undefined => None,
+                _ => Some(suggested_width)
+            }),
+            LayoutMeasureContext::Custom { ref measure } => {
+                // Custom measure functions are trusted to honor the measure
+                // modes themselves.
+                return measure(suggested_width, width_mode, suggested_height, height_mode);
+            }
+        };
+
+        LayoutClientSizeF {
+            width: constrain(measured_width, suggested_width, width_mode),
+            height: constrain(measured_height, suggested_height, height_mode)
+        }
+    }
+
+    /// Distance from the top of the measured node's border box to its first
+    /// baseline, given the size of the border box and the thickness of its
+    /// border and padding. Custom elements don't have one, so layout engines
+    /// align them like any other box without a baseline of its own.
+    pub fn baseline(&self, width: f32, height: f32, frame: &LayoutBoxEdgesF) -> Option<f32> {
+        match *self {
+            LayoutMeasureContext::Text { ref metrics } => {
+                // Text starts at the top of the content box, and is broken
+                // for its width.
+                let content_width = width - frame.left - frame.right;
+                Some(frame.top + metrics.first_baseline(Some(content_width)))
+            }
+            // Images sit on the baseline with their bottom edge, like inline
+            // images do.
+            LayoutMeasureContext::Image { .. } => Some(height),
+            LayoutMeasureContext::Custom { .. } => None
+        }
+    }
+}
+
+pub(crate) fn constrain(measured: f32, suggested: f32, mode: LayoutMeasureMode) -> f32 {
+    match mode {
+        LayoutMeasureMode::Exactly => suggested,
+        LayoutMeasureMode::AtMost => f32::min(measured, suggested),
+        LayoutMeasureMode::Undefined => measured
+    }
+}
+
+/// A box layout algorithm backing a `LayoutNode`. Each engine node mirrors
+/// one layout node, and the tree structure is kept in sync by the layout
+/// node itself, so engines only ever see style, measure and child updates.
+pub trait TLayoutEngine: fmt::Debug + Sized {
+    fn new() -> Self;
+
+    fn child_count(&self) -> usize;
+
+    fn insert_child(&mut self, child: &mut Self, index: usize);
+
+    fn remove_child(&mut self, child: &mut Self);
+
+    fn apply_style(&mut self, style: &LayoutStyle);
+
+    fn set_measure_context(&mut self, context: Option<LayoutMeasureContext>);
+
+    fn mark_dirty(&mut self);
+
+    /// Makes this node lay out its children in a grid or in flow instead of
+    /// as flex items, or goes back to flexbox. Containers are laid out as
+    /// part of `calculate_layout`, like any other node.
+    fn set_container(&mut self, container: Option<LayoutContainer>);
+
+    /// How this node is placed when its parent is a grid or flow container.
+    fn set_container_item(&mut self, item: LayoutContainerItem);
+
+    /// Lays out this node and its subtree as the root of a tree inside the
+    /// given size. NaN dimensions are sized to fit the content instead.
+    fn calculate_layout(&mut self, width: f32, height: f32, direction: LayoutReflowDirection);
+
+    /// Border box relative to the parent's border box.
+    fn get_layout_rect(&self) -> LayoutBoundingClientRectF;
+
+    fn get_layout_margin(&self) -> LayoutBoxEdgesF;
+
+    fn get_layout_border(&self) -> LayoutBoxEdgesF;
+
+    fn get_layout_padding(&self) -> LayoutBoxEdgesF;
+
+    /// Pieces of this node's text on each line, relative to its border box,
+    /// when its parent laid it out inline. Empty otherwise.
+    fn get_text_fragments(&self) -> Vec<LayoutTextFragment>;
+}
@@ -0,0 +1,1272 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::cell::RefCell;
+use std::f32;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use container::{LayoutContainer, LayoutContainerItem, TLayoutContainerChild};
+use engine::{LayoutMeasureContext, TLayoutEngine};
+use measure::LayoutMeasureMode;
+use styles::{
+    LayoutAlign,
+    LayoutDisplay,
+    LayoutFlexDirection,
+    LayoutFlexWrap,
+    LayoutJustify,
+    LayoutPositionType,
+    LayoutReflowDirection,
+    LayoutStyle,
+    LayoutStyleUnit
+};
+use text_layout::{LayoutTextFragment, TextMetrics};
+use types::{LayoutBoundingClientRectF, LayoutBoxEdgesF, LayoutClientPositionF, LayoutClientSizeF};
+
+const EDGE_LEFT: usize = 0;
+const EDGE_TOP: usize = 1;
+const EDGE_RIGHT: usize = 2;
+const EDGE_BOTTOM: usize = 3;
+const EDGE_START: usize = 4;
+const EDGE_END: usize = 5;
+const EDGE_HORIZONTAL: usize = 6;
+const EDGE_VERTICAL: usize = 7;
+const EDGE_ALL: usize = 8;
+
+const MAX_CACHED_MEASUREMENTS: usize = 16;
+
+type FlexboxEdges = [LayoutStyleUnit; 9];
+
+/// The subset of layout styles understood by the flexbox engine, with
+/// the same defaults as Yoga.
+#[derive(Debug, Copy, Clone)]
+struct FlexboxStyle {
+    display: LayoutDisplay,
+    position_type: LayoutPositionType,
+    flex_direction: LayoutFlexDirection,
+    flex_wrap: LayoutFlexWrap,
+    justify_content: LayoutJustify,
+    align_content: LayoutAlign,
+    align_items: LayoutAlign,
+    align_self: LayoutAlign,
+    flex: Option<f32>,
+    flex_grow: Option<f32>,
+    flex_shrink: Option<f32>,
+    flex_basis: LayoutStyleUnit,
+    aspect_ratio: Option<f32>,
+    width: LayoutStyleUnit,
+    height: LayoutStyleUnit,
+    min_width: LayoutStyleUnit,
+    min_height: LayoutStyleUnit,
+    max_width: LayoutStyleUnit,
+    max_height: LayoutStyleUnit,
+    margin: FlexboxEdges,
+    padding: FlexboxEdges,
+    border: FlexboxEdges,
+    position: FlexboxEdges
+}
+
+impl Default for FlexboxStyle {
+    fn default() -> Self {
+        FlexboxStyle {
+            display: LayoutDisplay::Flex,
+            position_type: LayoutPositionType::Relative,
+            flex_direction: LayoutFlexDirection::Column,
+            flex_wrap: LayoutFlexWrap::NoWrap,
+            justify_content: LayoutJustify::FlexStart,
+            align_content: LayoutAlign::FlexStart,
+            align_items: LayoutAlign::Stretch,
+            align_self: LayoutAlign::Auto,
+            flex: None,
+            flex_grow: None,
+            flex_shrink: None,
+            flex_basis: LayoutStyleUnit::Auto,
+            aspect_ratio: None,
+            width: LayoutStyleUnit::Auto,
+            height: LayoutStyleUnit::Auto,
+            min_width: LayoutStyleUnit::Undefined,
+            min_height: LayoutStyleUnit::Undefined,
+            max_width: LayoutStyleUnit::Undefined,
+            max_height: LayoutStyleUnit::Undefined,
+            margin: [LayoutStyleUnit::Undefined; 9],
+            padding: [LayoutStyleUnit::Undefined; 9],
+            border: [LayoutStyleUnit::Undefined; 9],
+            position: [LayoutStyleUnit::Undefined; 9]
+        }
+    }
+}
+
+impl FlexboxStyle {
+    fn apply(&mut self, style: &LayoutStyle) {
+        match *style {
+            LayoutStyle::AlignContent(v) => self.align_content = v,
+            LayoutStyle::AlignItems(v) => self.align_items = v,
+            LayoutStyle::AlignSelf(v) => self.align_self = v,
+            LayoutStyle::AspectRatio(v) => self.aspect_ratio = v,
+            LayoutStyle::Border(v) => self.border[EDGE_ALL] = point(v),
+            LayoutStyle::BorderBottom(v) => self.border[EDGE_BOTTOM] = point(v),
+            LayoutStyle::BorderEnd(v) => self.border[EDGE_END] = point(v),
+            LayoutStyle::BorderLeft(v) => self.border[EDGE_LEFT] = point(v),
+            LayoutStyle::BorderRight(v) => self.border[EDGE_RIGHT] = point(v),
+            LayoutStyle::BorderStart(v) => self.border[EDGE_START] = point(v),
+            LayoutStyle::BorderTop(v) => self.border[EDGE_TOP] = point(v),
+            LayoutStyle::Bottom(v) => self.position[EDGE_BOTTOM] = v,
+            LayoutStyle::Display(v) => self.display = v,
+            LayoutStyle::End(v) => self.position[EDGE_END] = v,
+            LayoutStyle::Flex(v) => self.flex = v,
+            LayoutStyle::FlexBasis(v) => self.flex_basis = v,
+            LayoutStyle::FlexDirection(v) => self.flex_direction = v,
+            LayoutStyle::FlexGrow(v) => self.flex_grow = v,
+            LayoutStyle::FlexShrink(v) => self.flex_shrink = v,
+            LayoutStyle::FlexWrap(v) => self.flex_wrap = v,
+            LayoutStyle::Height(v) => self.height = v,
+            LayoutStyle::JustifyContent(v) => self.justify_content = v,
+            LayoutStyle::Left(v) => self.position[EDGE_LEFT] = v,
+            LayoutStyle::Margin(v) => self.margin[EDGE_ALL] = v,
+            LayoutStyle::MarginBottom(v) => self.margin[EDGE_BOTTOM] = v,
+            LayoutStyle::MarginEnd(v) => self.margin[EDGE_END] = v,
+            LayoutStyle::MarginHorizontal(v) => self.margin[EDGE_HORIZONTAL] = v,
+            LayoutStyle::MarginLeft(v) => self.margin[EDGE_LEFT] = v,
+            LayoutStyle::MarginRight(v) => self.margin[EDGE_RIGHT] = v,
+            LayoutStyle::MarginStart(v) => self.margin[EDGE_START] = v,
+            LayoutStyle::MarginTop(v) => self.margin[EDGE_TOP] = v,
+            LayoutStyle::MarginVertical(v) => self.margin[EDGE_VERTICAL] = v,
+            LayoutStyle::MaxHeight(v) => self.max_height = v,
+            LayoutStyle::MaxWidth(v) => self.max_width = v,
+            LayoutStyle::MinHeight(v) => self.min_height = v,
+            LayoutStyle::MinWidth(v) => self.min_width = v,
+            // Overflow only affects scrolling and clipping, not layout.
+            LayoutStyle::Overflow(_) => {}
+            LayoutStyle::Padding(v) => self.padding[EDGE_ALL] = v,
+            LayoutStyle::PaddingBottom(v) => self.padding[EDGE_BOTTOM] = v,
+            LayoutStyle::PaddingEnd(v) => self.padding[EDGE_END] = v,
+            LayoutStyle::PaddingHorizontal(v) => self.padding[EDGE_HORIZONTAL] = v,
+            LayoutStyle::PaddingLeft(v) => self.padding[EDGE_LEFT] = v,
+            LayoutStyle::PaddingRight(v) => self.padding[EDGE_RIGHT] = v,
+            LayoutStyle::PaddingStart(v) => self.padding[EDGE_START] = v,
+            LayoutStyle::PaddingTop(v) => self.padding[EDGE_TOP] = v,
+            LayoutStyle::PaddingVertical(v) => self.padding[EDGE_VERTICAL] = v,
+            LayoutStyle::Position(v) => self.position_type = v,
+            LayoutStyle::Right(v) => self.position[EDGE_RIGHT] = v,
+            LayoutStyle::Start(v) => self.position[EDGE_START] = v,
+            LayoutStyle::Top(v) => self.position[EDGE_TOP] = v,
+            LayoutStyle::Width(v) => self.width = v
+        }
+    }
+
+    fn flex_grow(&self) -> f32 {
+        match (self.flex_grow, self.flex) {
+            (Some(grow), _) => grow,
+            (None, Some(flex)) if flex > 0.0 => flex,
+            _ => 0.0
+        }
+    }
+
+    fn flex_shrink(&self) -> f32 {
+        match (self.flex_shrink, self.flex) {
+            (Some(shrink), _) => shrink,
+            (None, Some(flex)) if flex < 0.0 => -flex,
+            _ => 0.0
+        }
+    }
+
+    fn flex_basis(&self) -> LayoutStyleUnit {
+        match (self.flex_basis, self.flex) {
+            (LayoutStyleUnit::Auto, Some(flex)) | (LayoutStyleUnit::Undefined, Some(flex)) if flex > 0.0 => LayoutStyleUnit::Point(0.0),
+            (basis, _) => basis
+        }
+    }
+
+    fn is_hidden(&self) -> bool {
+        match self.display {
+            LayoutDisplay::None => true,
+            _ => false
+        }
+    }
+
+    fn is_in_flow(&self) -> bool {
+        match (self.display, self.position_type) {
+            (LayoutDisplay::None, _) | (_, LayoutPositionType::Absolute) => false,
+            _ => true
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct FlexboxLayout {
+    rect: LayoutBoundingClientRectF,
+    rounded_rect: LayoutBoundingClientRectF,
+    margin: LayoutBoxEdgesF,
+    border: LayoutBoxEdgesF,
+    padding: LayoutBoxEdgesF
+}
+
+#[derive(Default)]
+struct FlexboxNode {
+    style: FlexboxStyle,
+    measure: Option<LayoutMeasureContext>,
+    container: Option<LayoutContainer>,
+    item: LayoutContainerItem,
+    parent: Option<Weak<RefCell<FlexboxNode>>>,
+    children: Vec<Rc<RefCell<FlexboxNode>>>,
+    layout: FlexboxLayout,
+    text_fragments: Vec<LayoutTextFragment>,
+    /// Border box sizes this node was measured at, for the constraints it
+    /// was measured with, kept until the node or its subtree changes.
+    measurements: Vec<(FlexboxConstraints, (f32, f32))>,
+    /// The constraints `layout` was computed with, as long as nothing in
+    /// this subtree was laid out differently since.
+    performed: Option<FlexboxConstraints>
+}
+
+/// Layout engine implementing flexbox in pure Rust, following Yoga's
+/// defaults and rounding so that both engines agree on the same styles.
+/// Like in Yoga, measurements are cached per node until it's marked dirty.
+/// Children of grid and flow containers are laid out as roots of their own
+/// subtrees, inside the areas the container gives them.
+pub struct FlexboxLayoutEngine(Rc<RefCell<FlexboxNode>>);
+
+impl fmt::Debug for FlexboxLayoutEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "FlexboxLayoutEngine {{ layout: {:?} }}", self.0.borrow().layout)
+    }
+}
+
+impl TLayoutEngine for FlexboxLayoutEngine {
+    fn new() -> Self {
+        FlexboxLayoutEngine(Rc::new(RefCell::new(FlexboxNode::default())))
+    }
+
+    fn child_count(&self) -> usize {
+        self.0.borrow().children.len()
+    }
+
+    fn insert_child(&mut self, child: &mut Self, index: usize) {
+        child.0.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+        self.0.borrow_mut().children.insert(index, Rc::clone(&child.0));
+        invalidate(&self.0);
+    }
+
+    fn remove_child(&mut self, child: &mut Self) {
+        let index = self.0.borrow().children.iter().position(|v| Rc::ptr_eq(v, &child.0));
+        if let Some(index) = index {
+            self.0.borrow_mut().children.remove(index);
+            let mut child = child.0.borrow_mut();
+            child.parent = None;
+            child.text_fragments.clear();
+            invalidate(&self.0);
+        }
+    }
+
+    fn apply_style(&mut self, style: &LayoutStyle) {
+        self.0.borrow_mut().style.apply(style);
+        invalidate(&self.0);
+    }
+
+    fn set_measure_context(&mut self, context: Option<LayoutMeasureContext>) {
+        self.0.borrow_mut().measure = context;
+        invalidate(&self.0);
+    }
+
+    fn mark_dirty(&mut self) {
+        invalidate(&self.0);
+    }
+
+    fn set_container(&mut self, container: Option<LayoutContainer>) {
+        {
+            let mut node = self.0.borrow_mut();
+            node.container = container;
+            for child in &node.children {
+                child.borrow_mut().text_fragments.clear();
+            }
+        }
+        invalidate(&self.0);
+    }
+
+    fn set_container_item(&mut self, item: LayoutContainerItem) {
+        self.0.borrow_mut().item = item;
+        invalidate(&self.0);
+    }
+
+    fn calculate_layout(&mut self, width: f32, height: f32, direction: LayoutReflowDirection) {
+        let direction = match direction {
+            LayoutReflowDirection::Inherit => LayoutReflowDirection::LTR,
+            direction => direction
+        };
+
+        layout_root(&self.0, float(width), float(height), direction, true);
+        round_layout(&self.0, 0.0, 0.0);
+    }
+
+    fn get_layout_rect(&self) -> LayoutBoundingClientRectF {
+        self.0.borrow().layout.rounded_rect
+    }
+
+    fn get_layout_margin(&self) -> LayoutBoxEdgesF {
+        self.0.borrow().layout.margin
+    }
+
+    fn get_layout_border(&self) -> LayoutBoxEdgesF {
+        self.0.borrow().layout.border
+    }
+
+    fn get_layout_padding(&self) -> LayoutBoxEdgesF {
+        self.0.borrow().layout.padding
+    }
+
+    fn get_text_fragments(&self) -> Vec<LayoutTextFragment> {
+        self.0.borrow().text_fragments.clone()
+    }
+}
+
+/// Lays out a node as the root of a tree. Like in Yoga, roots without a
+/// size or maximum size of their own fill the given dimensions, and are
+/// sized to fit their content along dimensions that aren't given. Returns
+/// the size of the node's margin box.
+fn layout_root(
+    node: &Rc<RefCell<FlexboxNode>>,
+    width: Option<f32>,
+    height: Option<f32>,
+    direction: LayoutReflowDirection,
+    perform: bool
+) -> (f32, f32) {
+    let style = node.borrow().style;
+    let margin = resolve_edges(&style.margin, direction, width);
+
+    let (width_constraint, available_width) = root_constraint(style.width, style.max_width, width, margin.left + margin.right);
+    let (height_constraint, available_height) = root_constraint(style.height, style.max_height, height, margin.top + margin.bottom);
+
+    let constraints = FlexboxConstraints {
+        width: width_constraint,
+        height: height_constraint,
+        available_width,
+        available_height,
+        owner_width: width,
+        owner_height: height,
+        direction
+    };
+
+    let (border_width, border_height) = layout_node(node, constraints, perform);
+
+    if perform {
+        let (x, y) = relative_offset(&style, direction, width, height);
+        let mut node = node.borrow_mut();
+        node.layout.rect.position = LayoutClientPositionF {
+            left: margin.left + x,
+            top: margin.top + y
+        };
+        node.layout.margin = margin;
+    }
+
+    (border_width + margin.left + margin.right, border_height + margin.top + margin.bottom)
+}
+
+/// What a parent knows about the border box of a child it lays out.
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct FlexboxConstraints {
+    /// Sizes imposed by the parent, for example when flexing or stretching,
+    /// overriding the child's own styles.
+    width: Option<f32>,
+    height: Option<f32>,
+    /// Sizes the child shouldn't exceed when sizing itself to its content.
+    available_width: Option<f32>,
+    available_height: Option<f32>,
+    /// Sizes percentages are resolved against.
+    owner_width: Option<f32>,
+    owner_height: Option<f32>,
+    direction: LayoutReflowDirection
+}
+
+/// The same constraints as above, for a node's content box.
+#[derive(Debug, Copy, Clone)]
+struct FlexboxContentSizes {
+    width: Option<f32>,
+    height: Option<f32>,
+    available_width: Option<f32>,
+    available_height: Option<f32>,
+    min_width: Option<f32>,
+    min_height: Option<f32>,
+    max_width: Option<f32>,
+    max_height: Option<f32>
+}
+
+/// Computes the border box size of a node. Only when `perform` is set are
+/// the results stored on the node and its descendants, otherwise the node
+/// is just measured. Either is skipped if it was already done with the same
+/// constraints since the node was last invalidated.
+fn layout_node(node: &Rc<RefCell<FlexboxNode>>, constraints: FlexboxConstraints, perform: bool) -> (f32, f32) {
+    let (style, measure, container, children) = {
+        let node = node.borrow();
+        if perform && node.performed == Some(constraints) {
+            return (node.layout.rect.size.width, node.layout.rect.size.height);
+        }
+        if !perform {
+            if let Some(&(_, size)) = node.measurements.iter().find(|v| v.0 == constraints) {
+                return size;
+            }
+        }
+        (node.style, node.measure.clone(), node.container.clone(), node.children.clone())
+    };
+
+    let direction = constraints.direction;
+    let padding = resolve_edges(&style.padding, direction, constraints.owner_width);
+    let border = resolve_edges(&style.border, direction, constraints.owner_width);
+    let frame = padding + border;
+    let frame_width = frame.left + frame.right;
+    let frame_height = frame.top + frame.bottom;
+
+    let min_width = resolve(style.min_width, constraints.owner_width);
+    let max_width = resolve(style.max_width, constraints.owner_width);
+    let min_height = resolve(style.min_height, constraints.owner_height);
+    let max_height = resolve(style.max_height, constraints.owner_height);
+
+    let mut width = constraints
+        .width
+        .or_else(|| resolve(style.width, constraints.owner_width).map(|v| bound(v, min_width, max_width)));
+    let mut height = constraints
+        .height
+        .or_else(|| resolve(style.height, constraints.owner_height).map(|v| bound(v, min_height, max_height)));
+
+    if let Some(ratio) = style.aspect_ratio {
+        match (width, height) {
+            (Some(w), None) => height = Some(bound(w / ratio, min_height, max_height)),
+            (None, Some(h)) => width = Some(bound(h * ratio, min_width, max_width)),
+            _ => {}
+        }
+    }
+
+    let width = width.map(|v| f32::max(v, frame_width));
+    let height = height.map(|v| f32::max(v, frame_height));
+
+    let sizes = FlexboxContentSizes {
+        width: inner_size(width, frame_width),
+        height: inner_size(height, frame_height),
+        available_width: inner_size(constraints.available_width, frame_width),
+        available_height: inner_size(constraints.available_height, frame_height),
+        min_width: inner_size(min_width, frame_width),
+        min_height: inner_size(min_height, frame_height),
+        max_width: inner_size(max_width, frame_width),
+        max_height: inner_size(max_height, frame_height)
+    };
+
+    let (content_width, content_height) = match (&measure, &container) {
+        (&Some(ref measure), _) => measure_leaf(measure, &sizes),
+        (&None, _) if children.is_empty() => (0.0, 0.0),
+        (&None, &Some(ref container)) => measure_container(container, &children, &sizes, direction),
+        (&None, &None) => layout_children(&style, &children, &sizes, &border, &padding, direction, perform)
+    };
+
+    let width = width.unwrap_or_else(|| f32::max(bound(content_width + frame_width, min_width, max_width), frame_width));
+    let height = height.unwrap_or_else(|| f32::max(bound(content_height + frame_height, min_height, max_height), frame_height));
+
+    if perform {
+        if let (&None, &Some(ref container)) = (&measure, &container) {
+            let content_size = (width - frame_width, height - frame_height);
+            place_container_children(container, &children, content_size, &frame, direction);
+        }
+
+        // Whatever contains this node was laid out with its previous layout.
+        if let Some(parent) = node.borrow().parent.as_ref().and_then(Weak::upgrade) {
+            invalidate_performed(&parent);
+        }
+    }
+
+    let mut node = node.borrow_mut();
+    if perform {
+        node.layout.rect.size = LayoutClientSizeF { width, height };
+        node.layout.border = border;
+        node.layout.padding = padding;
+        node.performed = Some(constraints);
+    }
+    if !node.measurements.iter().any(|v| v.0 == constraints) {
+        if node.measurements.len() == MAX_CACHED_MEASUREMENTS {
+            node.measurements.remove(0);
+        }
+        node.measurements.push((constraints, (width, height)));
+    }
+
+    (width, height)
+}
+
+/// Drops everything cached for a node and the nodes containing it, after
+/// something changed that could affect their layout.
+fn invalidate(node: &Rc<RefCell<FlexboxNode>>) {
+    let parent = {
+        let mut node = node.borrow_mut();
+        node.measurements.clear();
+        node.performed = None;
+        node.parent.as_ref().and_then(Weak::upgrade)
+    };
+    if let Some(parent) = parent {
+        invalidate(&parent);
+    }
+}
+
+/// Forgets that a node and the nodes containing it were laid out, without
+/// dropping their measurements, which only depend on styles.
+fn invalidate_performed(node: &Rc<RefCell<FlexboxNode>>) {
+    let parent = {
+        let mut node = node.borrow_mut();
+        node.performed = None;
+        node.parent.as_ref().and_then(Weak::upgrade)
+    };
+    if let Some(parent) = parent {
+        invalidate_performed(&parent);
+    }
+}
+
+/// A child of a grid or flow container, laid out as the root of a tree.
+struct FlexboxContainerChild {
+    node: Rc<RefCell<FlexboxNode>>,
+    direction: LayoutReflowDirection
+}
+
+impl TLayoutContainerChild for FlexboxContainerChild {
+    fn container_item(&self) -> LayoutContainerItem {
+        self.node.borrow().item.clone()
+    }
+
+    fn text_metrics(&self) -> Option<Rc<TextMetrics>> {
+        match self.node.borrow().measure {
+            Some(LayoutMeasureContext::Text { ref metrics }) => Some(Rc::clone(metrics)),
+            _ => None
+        }
+    }
+
+    fn measure(&self, width: Option<f32>, height: Option<f32>) -> LayoutClientSizeF {
+        let (width, height) = layout_root(&self.node, width, height, self.direction, false);
+        LayoutClientSizeF { width, height }
+    }
+}
+
+fn container_children(children: &[Rc<RefCell<FlexboxNode>>], direction: LayoutReflowDirection) -> Vec<FlexboxContainerChild> {
+    children
+        .iter()
+        .filter(|v| !v.borrow().style.is_hidden())
+        .map(|v| FlexboxContainerChild {
+            node: Rc::clone(v),
+            direction
+        })
+        .collect()
+}
+
+/// Lays out the children of a grid or flow container to find the size of
+/// its content box. Like blocks, containers fill the width available to
+/// them.
+fn measure_container(
+    container: &LayoutContainer,
+    children: &[Rc<RefCell<FlexboxNode>>],
+    sizes: &FlexboxContentSizes,
+    direction: LayoutReflowDirection
+) -> (f32, f32) {
+    let children = container_children(children, direction);
+    let layout = container.layout(&children, sizes.width.or(sizes.available_width), sizes.height);
+    (layout.size.width, layout.size.height)
+}
+
+/// Lays out the children of a grid or flow container inside the areas it
+/// gives them, once the size of its content box is known.
+fn place_container_children(
+    container: &LayoutContainer,
+    children: &[Rc<RefCell<FlexboxNode>>],
+    (content_width, content_height): (f32, f32),
+    frame: &LayoutBoxEdgesF,
+    direction: LayoutReflowDirection
+) {
+    for child in children.iter().filter(|v| v.borrow().style.is_hidden()) {
+        hide_layout(child);
+    }
+
+    let children = container_children(children, direction);
+    let layout = container.layout(&children, Some(content_width), Some(content_height));
+
+    for ((child, area), fragments) in children.iter().zip(layout.areas).zip(layout.text_fragments) {
+        layout_root(&child.node, Some(area.width), area.height, direction, true);
+        let mut node = child.node.borrow_mut();
+        node.layout.rect.position += LayoutClientPositionF {
+            left: frame.left + area.position.left,
+            top: frame.top + area.position.top
+        };
+        node.text_fragments = fragments;
+    }
+}
+
+fn measure_leaf(measure: &LayoutMeasureContext, sizes: &FlexboxContentSizes) -> (f32, f32) {
+    let (width_mode, suggested_width) = measure_constraint(sizes.width, sizes.available_width);
+    let (height_mode, suggested_height) = measure_constraint(sizes.height, sizes.available_height);
+    let size = measure.measure(suggested_width, width_mode, suggested_height, height_mode);
+    // Custom measure functions may return anything, but the rest of the
+    // layout relies on sizes being finite.
+    (sizes.width.unwrap_or(finite(size.width)), sizes.height.unwrap_or(finite(size.height)))
+}
+
+fn measure_constraint(size: Option<f32>, available: Option<f32>) -> (LayoutMeasureMode, f32) {
+    match (size, available) {
+        (Some(size), _) => (LayoutMeasureMode::Exactly, size),
+        (None, Some(available)) => (LayoutMeasureMode::AtMost, available),
+        (None, None) => (LayoutMeasureMode::Undefined, f32::NAN)
+    }
+}
+
+/// Axis bookkeeping for laying out the children of a flex container.
+struct FlexContainer {
+    is_row: bool,
+    reverse_main: bool,
+    reverse_cross: bool,
+    owner_width: Option<f32>,
+    owner_height: Option<f32>,
+    direction: LayoutReflowDirection
+}
+
+impl FlexContainer {
+    fn main<T>(&self, horizontal: T, vertical: T) -> T {
+        if self.is_row {
+            horizontal
+        } else {
+            vertical
+        }
+    }
+
+    fn cross<T>(&self, horizontal: T, vertical: T) -> T {
+        self.main(vertical, horizontal)
+    }
+
+    /// Lays out an item with the given main and cross sizes, if known.
+    /// Returns the resulting main and cross sizes.
+    fn layout_item(&self, item: &FlexItem, main: Option<f32>, cross: Option<f32>, perform: bool) -> (f32, f32) {
+        let constraints = FlexboxConstraints {
+            width: self.main(main, cross),
+            height: self.cross(main, cross),
+            available_width: self.owner_width.map(|v| v - item.margin.left - item.margin.right),
+            available_height: self.owner_height.map(|v| v - item.margin.top - item.margin.bottom),
+            owner_width: self.owner_width,
+            owner_height: self.owner_height,
+            direction: self.direction
+        };
+        let (width, height) = layout_node(&item.node, constraints, perform);
+        (self.main(width, height), self.cross(width, height))
+    }
+}
+
+struct FlexItem {
+    node: Rc<RefCell<FlexboxNode>>,
+    style: FlexboxStyle,
+    align: LayoutAlign,
+    margin: LayoutBoxEdgesF,
+    margin_main: (f32, f32),
+    margin_cross: (f32, f32),
+    grow: f32,
+    shrink: f32,
+    basis: f32,
+    hypothetical_main: f32,
+    min_main: Option<f32>,
+    max_main: Option<f32>,
+    min_cross: Option<f32>,
+    max_cross: Option<f32>,
+    stretch: bool,
+    main: f32,
+    cross: f32,
+    baseline: f32,
+    frozen: bool
+}
+
+impl FlexItem {
+    fn outer_main(&self) -> f32 {
+        self.main + self.margin_main.0 + self.margin_main.1
+    }
+
+    fn outer_cross(&self) -> f32 {
+        self.cross + self.margin_cross.0 + self.margin_cross.1
+    }
+
+    fn stretched_cross(&self, line_cross: f32) -> f32 {
+        f32::max(bound(line_cross - self.margin_cross.0 - self.margin_cross.1, self.min_cross, self.max_cross), 0.0)
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct FlexLine {
+    start: usize,
+    end: usize,
+    main: f32,
+    cross: f32,
+    ascent: f32,
+    offset: f32
+}
+
+/// Runs the flexbox algorithm over a container's children, returning the
+/// size of its content box.
+fn layout_children(
+    style: &FlexboxStyle,
+    children: &[Rc<RefCell<FlexboxNode>>],
+    sizes: &FlexboxContentSizes,
+    border: &LayoutBoxEdgesF,
+    padding: &LayoutBoxEdgesF,
+    direction: LayoutReflowDirection,
+    perform: bool
+) -> (f32, f32) {
+    let frame = *border + *padding;
+    let is_row = match style.flex_direction {
+        LayoutFlexDirection::Row | LayoutFlexDirection::RowReverse => true,
+        LayoutFlexDirection::Column | LayoutFlexDirection::ColumnReverse => false
+    };
+    let is_rtl = match direction {
+        LayoutReflowDirection::RTL => true,
+        _ => false
+    };
+    let is_reversed = match style.flex_direction {
+        LayoutFlexDirection::RowReverse | LayoutFlexDirection::ColumnReverse => true,
+        LayoutFlexDirection::Row | LayoutFlexDirection::Column => false
+    };
+    let is_wrap_reversed = match style.flex_wrap {
+        LayoutFlexWrap::WrapReverse => true,
+        _ => false
+    };
+    let is_wrapping = match style.flex_wrap {
+        LayoutFlexWrap::NoWrap => false,
+        _ => true
+    };
+
+    let container = FlexContainer {
+        is_row,
+        reverse_main: is_reversed != (is_row && is_rtl),
+        reverse_cross: is_wrap_reversed != (!is_row && is_rtl),
+        owner_width: sizes.width.or(sizes.available_width),
+        owner_height: sizes.height.or(sizes.available_height),
+        direction
+    };
+
+    let main_size = container.main(sizes.width, sizes.height);
+    let cross_size = container.cross(sizes.width, sizes.height);
+    let main_available = container.main(sizes.available_width, sizes.available_height);
+    let cross_available = container.cross(sizes.available_width, sizes.available_height);
+    let (min_main, max_main) = container.main((sizes.min_width, sizes.max_width), (sizes.min_height, sizes.max_height));
+    let (min_cross, max_cross) = container.cross((sizes.min_width, sizes.max_width), (sizes.min_height, sizes.max_height));
+    let owner_main = container.main(container.owner_width, container.owner_height);
+    let owner_cross = container.cross(container.owner_width, container.owner_height);
+
+    let mut items = vec![];
+    let mut absolutes = vec![];
+
+    for child in children {
+        let child_style = child.borrow().style;
+        match (child_style.display, child_style.position_type) {
+            (LayoutDisplay::None, _) => {
+                if perform {
+                    hide_layout(child);
+                }
+                continue;
+            }
+            (_, LayoutPositionType::Absolute) => {
+                absolutes.push(Rc::clone(child));
+                continue;
+            }
+            _ => {}
+        }
+
+        let margin = resolve_edges(&child_style.margin, direction, container.owner_width);
+        let margin_main = container.main((margin.left, margin.right), (margin.top, margin.bottom));
+        let margin_cross = container.cross((margin.left, margin.right), (margin.top, margin.bottom));
+        let child_frame = resolve_edges(&child_style.padding, direction, container.owner_width)
+            + resolve_edges(&child_style.border, direction, container.owner_width);
+        let frame_main = container.main(child_frame.left + child_frame.right, child_frame.top + child_frame.bottom);
+
+        let align = match child_style.align_self {
+            LayoutAlign::Auto => style.align_items,
+            align => align
+        };
+        let cross_style = resolve(container.cross(child_style.width, child_style.height), owner_cross);
+        let stretch = match align {
+            LayoutAlign::Stretch => cross_style.is_none(),
+            _ => false
+        };
+
+        let mut item = FlexItem {
+            node: Rc::clone(child),
+            style: child_style,
+            align,
+            margin,
+            margin_main: if container.reverse_main { (margin_main.1, margin_main.0) } else { margin_main },
+            margin_cross: if container.reverse_cross { (margin_cross.1, margin_cross.0) } else { margin_cross },
+            grow: child_style.flex_grow(),
+            shrink: child_style.flex_shrink(),
+            basis: 0.0,
+            hypothetical_main: 0.0,
+            min_main: resolve(container.main(child_style.min_width, child_style.min_height), owner_main),
+            max_main: resolve(container.main(child_style.max_width, child_style.max_height), owner_main),
+            min_cross: resolve(container.cross(child_style.min_width, child_style.min_height), owner_cross),
+            max_cross: resolve(container.cross(child_style.max_width, child_style.max_height), owner_cross),
+            stretch,
+            main: 0.0,
+            cross: 0.0,
+            baseline: 0.0,
+            frozen: false
+        };
+
+        let basis = resolve(child_style.flex_basis(), owner_main)
+            .or_else(|| resolve(container.main(child_style.width, child_style.height), owner_main));
+
+        item.basis = f32::max(
+            match basis {
+                Some(basis) => basis,
+                None => {
+                    let stretched_cross = match (stretch, cross_size) {
+                        (true, Some(cross)) => Some(item.stretched_cross(cross)),
+                        _ => None
+                    };
+                    container.layout_item(&item, None, stretched_cross, false).0
+                }
+            },
+            frame_main
+        );
+        item.hypothetical_main = bound(item.basis, item.min_main, item.max_main);
+        items.push(item);
+    }
+
+    // Break items into lines.
+
+    let mut lines: Vec<FlexLine> = vec![];
+    let main_limit = main_size.or(main_available);
+    let mut start = 0;
+    let mut used = 0.0;
+
+    for (index, item) in items.iter().enumerate() {
+        let outer = item.hypothetical_main + item.margin_main.0 + item.margin_main.1;
+        let exceeds = match main_limit {
+            Some(limit) => is_wrapping && index > start && used + outer > limit,
+            None => false
+        };
+        if exceeds {
+            lines.push(FlexLine { start, end: index, ..FlexLine::default() });
+            start = index;
+            used = 0.0;
+        }
+        used += outer;
+    }
+    if !items.is_empty() {
+        lines.push(FlexLine { start, end: items.len(), ..FlexLine::default() });
+    }
+
+    // Resolve flexible lengths and cross sizes, line by line.
+
+    for line in &mut lines {
+        let used = items[line.start..line.end]
+            .iter()
+            .map(|v| v.hypothetical_main + v.margin_main.0 + v.margin_main.1)
+            .sum::<f32>();
+        let total_grow = items[line.start..line.end].iter().map(|v| v.grow).sum::<f32>();
+
+        let target = match main_size {
+            Some(size) => size,
+            None => match (min_main, max_main) {
+                (Some(min), _) if used < min => min,
+                (_, Some(max)) if used > max => max,
+                _ if total_grow == 0.0 || style.flex_grow() == 0.0 => used,
+                _ => main_available.unwrap_or(used)
+            }
+        };
+
+        resolve_flexible_lengths(&mut items[line.start..line.end], target);
+
+        for item in &mut items[line.start..line.end] {
+            let stretched_cross = match (item.stretch, cross_size, is_wrapping) {
+                (true, Some(cross), false) => Some(item.stretched_cross(cross)),
+                _ => None
+            };
+            item.cross = container.layout_item(item, Some(item.main), stretched_cross, false).1;
+
+            if is_row && is_baseline(item.align) {
+                container.layout_item(item, Some(item.main), Some(item.cross), true);
+                item.baseline = item.margin.top + layout_baseline(&item.node);
+                line.ascent = f32::max(line.ascent, item.baseline);
+            }
+        }
+
+        let items = &items[line.start..line.end];
+        line.main = items.iter().map(|v| v.outer_main()).sum();
+        line.cross = items.iter().map(|v| v.outer_cross()).fold(0.0, f32::max);
+
+        if is_row {
+            let descent = items
+                .iter()
+                .filter(|v| is_baseline(v.align))
+                .map(|v| v.outer_cross() - v.baseline)
+                .fold(0.0, f32::max);
+            line.cross = f32::max(line.cross, line.ascent + descent);
+        }
+
+        if let (Some(cross), false) = (cross_size, is_wrapping) {
+            line.cross = cross;
+        }
+    }
+
+    // Size the container itself.
+
+    let lines_main = lines.iter().map(|v| v.main).fold(0.0, f32::max);
+    let lines_cross = lines.iter().map(|v| v.cross).sum::<f32>();
+
+    let final_main = main_size.unwrap_or_else(|| {
+        let main = bound(lines_main, min_main, max_main);
+        main_available.map_or(main, |available| f32::min(main, available))
+    });
+    let final_cross = cross_size.unwrap_or_else(|| {
+        let cross = bound(lines_cross, min_cross, max_cross);
+        cross_available.map_or(cross, |available| f32::min(cross, available))
+    });
+
+    let (content_width, content_height) = (container.main(final_main, final_cross), container.cross(final_main, final_cross));
+
+    if !perform {
+        return (content_width, content_height);
+    }
+
+    // Distribute the lines along the cross axis.
+
+    let remaining_cross = if lines.len() > 1 { final_cross - lines_cross } else { 0.0 };
+    let count = lines.len() as f32;
+    let (mut offset, between_lines, extra_cross) = match style.align_content {
+        LayoutAlign::FlexEnd => (remaining_cross, 0.0, 0.0),
+        LayoutAlign::Center => (remaining_cross / 2.0, 0.0, 0.0),
+        LayoutAlign::Stretch if remaining_cross > 0.0 => (0.0, 0.0, remaining_cross / count),
+        LayoutAlign::SpaceBetween if remaining_cross > 0.0 && count > 1.0 => (0.0, remaining_cross / (count - 1.0), 0.0),
+        LayoutAlign::SpaceAround if remaining_cross > 0.0 => (remaining_cross / count / 2.0, remaining_cross / count, 0.0),
+        _ => (0.0, 0.0, 0.0)
+    };
+    for line in &mut lines {
+        line.cross += extra_cross;
+        line.offset = offset;
+        offset += line.cross + between_lines;
+    }
+
+    // Position the items inside each line.
+
+    for line in &lines {
+        let count = line.end - line.start;
+        let remaining_main = final_main - line.main;
+        let (mut position, between_items) = match style.justify_content {
+            LayoutJustify::Center => (remaining_main / 2.0, 0.0),
+            LayoutJustify::FlexEnd => (remaining_main, 0.0),
+            LayoutJustify::SpaceBetween if count > 1 => (0.0, f32::max(remaining_main, 0.0) / (count - 1) as f32),
+            LayoutJustify::SpaceAround => (remaining_main / count as f32 / 2.0, remaining_main / count as f32),
+            _ => (0.0, 0.0)
+        };
+
+        for item in &items[line.start..line.end] {
+            let cross = if item.stretch {
+                item.stretched_cross(line.cross)
+            } else {
+                item.cross
+            };
+            container.layout_item(item, Some(item.main), Some(cross), true);
+
+            let main_position = position + item.margin_main.0;
+            position += item.outer_main() + between_items;
+
+            let cross_position = match item.align {
+                LayoutAlign::FlexEnd => line.cross - cross - item.margin_cross.1,
+                LayoutAlign::Center => (line.cross - cross - item.margin_cross.0 - item.margin_cross.1) / 2.0 + item.margin_cross.0,
+                LayoutAlign::Baseline if is_row => line.ascent - item.baseline + item.margin.top,
+                _ => item.margin_cross.0
+            } + line.offset;
+
+            let main_position = if container.reverse_main { final_main - main_position - item.main } else { main_position };
+            let cross_position = if container.reverse_cross { final_cross - cross_position - cross } else { cross_position };
+
+            let (x, y) = relative_offset(&item.style, direction, container.owner_width, container.owner_height);
+            let mut node = item.node.borrow_mut();
+            node.layout.rect.position = LayoutClientPositionF {
+                left: frame.left + container.main(main_position, cross_position) + x,
+                top: frame.top + container.cross(main_position, cross_position) + y
+            };
+            node.layout.margin = item.margin;
+        }
+    }
+
+    // Absolutely positioned children don't take part in flex layout, and are
+    // laid out relative to the padding box once its size is known.
+
+    for child in &absolutes {
+        layout_absolute(style, child, &container, (content_width, content_height), border, padding, direction);
+    }
+
+    (content_width, content_height)
+}
+
+/// Distributes free space between the items of a line according to their
+/// flex factors, freezing items as they hit their minimum or maximum size.
+fn resolve_flexible_lengths(items: &mut [FlexItem], target: f32) {
+    let used = items.iter().map(|v| v.hypothetical_main + v.margin_main.0 + v.margin_main.1).sum::<f32>();
+    let growing = target > used;
+
+    for item in items.iter_mut() {
+        let factor = if growing { item.grow } else { item.shrink };
+        item.main = item.hypothetical_main;
+        item.frozen = factor == 0.0 || (growing && item.basis > item.hypothetical_main)
+            || (!growing && item.basis < item.hypothetical_main);
+    }
+
+    while items.iter().any(|v| !v.frozen) {
+        let consumed = items
+            .iter()
+            .map(|v| v.margin_main.0 + v.margin_main.1 + if v.frozen { v.main } else { v.basis })
+            .sum::<f32>();
+        let free = target - consumed;
+
+        let total = items
+            .iter()
+            .filter(|v| !v.frozen)
+            .map(|v| if growing { v.grow } else { v.shrink * v.basis })
+            .sum::<f32>();
+        // Like Yoga, fractional factors only distribute part of the space.
+        let total = if total > 0.0 && total < 1.0 { 1.0 } else { total };
+
+        let mut violation = 0.0;
+        let mut violations = vec![0.0; items.len()];
+
+        for (index, item) in items.iter_mut().enumerate() {
+            if item.frozen {
+                continue;
+            }
+            let factor = if growing { item.grow } else { item.shrink * item.basis };
+            let size = if total > 0.0 { item.basis + free * factor / total } else { item.basis };
+            let clamped = f32::max(bound(size, item.min_main, item.max_main), 0.0);
+            violations[index] = clamped - size;
+            violation += clamped - size;
+            item.main = clamped;
+        }
+
+        // Sizes that aren't finite never settle, so give up on them.
+        let settled = violation == 0.0 || !f32::is_finite(violation);
+        for (index, item) in items.iter_mut().enumerate() {
+            if item.frozen {
+                continue;
+            }
+            item.frozen = settled || (violation > 0.0 && violations[index] > 0.0) || (violation < 0.0 && violations[index] < 0.0);
+        }
+    }
+}
+
+fn layout_absolute(
+    style: &FlexboxStyle,
+    child: &Rc<RefCell<FlexboxNode>>,
+    container: &FlexContainer,
+    (content_width, content_height): (f32, f32),
+    border: &LayoutBoxEdgesF,
+    padding: &LayoutBoxEdgesF,
+    direction: LayoutReflowDirection
+) {
+    let child_style = child.borrow().style;
+    let owner_width = Some(content_width);
+    let owner_height = Some(content_height);
+    let margin = resolve_edges(&child_style.margin, direction, owner_width);
+
+    let left = resolve(resolve_edge(&child_style.position, EDGE_LEFT, direction), owner_width);
+    let right = resolve(resolve_edge(&child_style.position, EDGE_RIGHT, direction), owner_width);
+    let top = resolve(resolve_edge(&child_style.position, EDGE_TOP, direction), owner_height);
+    let bottom = resolve(resolve_edge(&child_style.position, EDGE_BOTTOM, direction), owner_height);
+
+    let padding_width = padding.left + content_width + padding.right;
+    let padding_height = padding.top + content_height + padding.bottom;
+
+    let constraints = FlexboxConstraints {
+        width: inset_size(child_style.width, owner_width, (left, right), padding_width - margin.left - margin.right),
+        height: inset_size(child_style.height, owner_height, (top, bottom), padding_height - margin.top - margin.bottom),
+        available_width: None,
+        available_height: None,
+        owner_width,
+        owner_height,
+        direction
+    };
+
+    let (width, height) = layout_node(child, constraints, true);
+
+    // Without insets, children are placed like a single flex item would be.
+    let align = match child_style.align_self {
+        LayoutAlign::Auto => style.align_items,
+        align => align
+    };
+    let justify = match style.justify_content {
+        LayoutJustify::Center => LayoutAlign::Center,
+        LayoutJustify::FlexEnd => LayoutAlign::FlexEnd,
+        _ => LayoutAlign::FlexStart
+    };
+    let horizontal = container.main(justify, align);
+    let vertical = container.cross(justify, align);
+
+    let x = match (left, right) {
+        (Some(left), _) => border.left + left + margin.left,
+        (None, Some(right)) => border.left + padding_width - right - margin.right - width,
+        _ => place_in_content(horizontal, border.left + padding.left, content_width, width, (margin.left, margin.right))
+    };
+    let y = match (top, bottom) {
+        (Some(top), _) => border.top + top + margin.top,
+        (None, Some(bottom)) => border.top + padding_height - bottom - margin.bottom - height,
+        _ => place_in_content(vertical, border.top + padding.top, content_height, height, (margin.top, margin.bottom))
+    };
+
+    let mut node = child.borrow_mut();
+    node.layout.rect.position = LayoutClientPositionF { left: x, top: y };
+    node.layout.margin = margin;
+}
+
+fn inset_size(size: LayoutStyleUnit, owner: Option<f32>, insets: (Option<f32>, Option<f32>), available: f32) -> Option<f32> {
+    match (resolve(size, owner), insets) {
+        (None, (Some(start), Some(end))) => Some(f32::max(available - start - end, 0.0)),
+        _ => None
+    }
+}
+
+fn place_in_content(align: LayoutAlign, start: f32, content: f32, size: f32, (margin_start, margin_end): (f32, f32)) -> f32 {
+    match align {
+        LayoutAlign::Center => start + margin_start + (content - size - margin_start - margin_end) / 2.0,
+        LayoutAlign::FlexEnd => start + content - size - margin_end,
+        _ => start + margin_start
+    }
+}
+
+fn is_baseline(align: LayoutAlign) -> bool {
+    match align {
+        LayoutAlign::Baseline => true,
+        _ => false
+    }
+}
+
+/// Distance from the top of a laid out node to its first baseline.
+fn layout_baseline(node: &Rc<RefCell<FlexboxNode>>) -> f32 {
+    let node = node.borrow();
+    let size = node.layout.rect.size;
+    let frame = node.layout.border + node.layout.padding;
+    if let Some(baseline) = node.measure.as_ref().and_then(|v| v.baseline(size.width, size.height, &frame)) {
+        return baseline;
+    }
+    match node.children.iter().find(|v| v.borrow().style.is_in_flow()) {
+        Some(child) => child.borrow().layout.rect.position.top + layout_baseline(child),
+        None => size.height
+    }
+}
+
+fn hide_layout(node: &Rc<RefCell<FlexboxNode>>) {
+    let mut node = node.borrow_mut();
+    node.layout = FlexboxLayout::default();
+    node.performed = None;
+    node.text_fragments.clear();
+    for child in &node.children {
+        hide_layout(child);
+    }
+}
+
+/// Rounds positions and sizes to whole pixels like Yoga does, snapping the
+/// absolute edges of each node so that adjacent nodes don't overlap. The
+/// unrounded layout is kept for the next pass.
+fn round_layout(node: &Rc<RefCell<FlexboxNode>>, parent_left: f32, parent_top: f32) {
+    let mut node = node.borrow_mut();
+    let rect = node.layout.rect;
+    let left = parent_left + rect.position.left;
+    let top = parent_top + rect.position.top;
+
+    node.layout.rounded_rect = LayoutBoundingClientRectF::new(
+        rect.position.left.round(),
+        rect.position.top.round(),
+        (left + rect.size.width).round() - left.round(),
+        (top + rect.size.height).round() - top.round()
+    );
+
+    for child in &node.children {
+        round_layout(child, left, top);
+    }
+}
+
+fn root_constraint(size: LayoutStyleUnit, max: LayoutStyleUnit, owner: Option<f32>, margins: f32) -> (Option<f32>, Option<f32>) {
+    match (resolve(size, owner), resolve(max, owner), owner) {
+        (Some(_), _, _) | (None, None, None) => (None, None),
+        (None, Some(max), _) => (None, Some(max)),
+        (None, None, Some(owner)) => (Some(owner - margins), None)
+    }
+}
+
+fn relative_offset(
+    style: &FlexboxStyle,
+    direction: LayoutReflowDirection,
+    owner_width: Option<f32>,
+    owner_height: Option<f32>
+) -> (f32, f32) {
+    let offset = |start: usize, end: usize, owner: Option<f32>| {
+        let start = resolve(resolve_edge(&style.position, start, direction), owner);
+        let end = resolve(resolve_edge(&style.position, end, direction), owner);
+        start.or_else(|| end.map(|v| -v)).unwrap_or(0.0)
+    };
+    (offset(EDGE_LEFT, EDGE_RIGHT, owner_width), offset(EDGE_TOP, EDGE_BOTTOM, owner_height))
+}
+
+/// Picks the value set for a physical edge, with logical edges taking
+/// precedence over physical ones, and those over the axis and all edges.
+fn resolve_edge(edges: &FlexboxEdges, edge: usize, direction: LayoutReflowDirection) -> LayoutStyleUnit {
+    let (start, end) = match direction {
+        LayoutReflowDirection::RTL => (EDGE_RIGHT, EDGE_LEFT),
+        _ => (EDGE_LEFT, EDGE_RIGHT)
+    };
+    let logical = if edge == start {
+        EDGE_START
+    } else if edge == end {
+        EDGE_END
+    } else {
+        edge
+    };
+    let axis = if edge == EDGE_LEFT || edge == EDGE_RIGHT { EDGE_HORIZONTAL } else { EDGE_VERTICAL };
+    [logical, edge, axis, EDGE_ALL]
+        .iter()
+        .map(|&v| edges[v])
+        .find(|v| *v != LayoutStyleUnit::Undefined)
+        .unwrap_or(LayoutStyleUnit::Undefined)
+}
+
+fn resolve_edges(edges: &FlexboxEdges, direction: LayoutReflowDirection, owner_width: Option<f32>) -> LayoutBoxEdgesF {
+    // Percentages on every edge resolve against the width, as in CSS.
+    let value = |edge: usize| resolve(resolve_edge(edges, edge, direction), owner_width).unwrap_or(0.0);
+    LayoutBoxEdgesF::new(value(EDGE_TOP), value(EDGE_RIGHT), value(EDGE_BOTTOM), value(EDGE_LEFT))
+}
+
+fn resolve(unit: LayoutStyleUnit, owner: Option<f32>) -> Option<f32> {
+    match unit {
+        LayoutStyleUnit::Point(value) => float(value),
+        LayoutStyleUnit::Percent(value) => owner.map(|owner| value * owner / 100.0),
+        LayoutStyleUnit::Undefined | LayoutStyleUnit::Auto => None
+    }
+}
+
+fn float(value: f32) -> Option<f32> {
+    if value.is_nan() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn finite(value: f32) -> f32 {
+    if value.is_finite() {
+        f32::max(value, 0.0)
+    } else {
+        0.0
+    }
+}
+
+fn point(value: Option<f32>) -> LayoutStyleUnit {
+    value.map_or(LayoutStyleUnit::Undefined, LayoutStyleUnit::Point)
+}
+
+fn bound(value: f32, min: Option<f32>, max: Option<f32>) -> f32 {
+    let value = max.map_or(value, |max| f32::min(value, max));
+    min.map_or(value, |min| f32::max(value, min))
+}
+
+fn inner_size(size: Option<f32>, frame: f32) -> Option<f32> {
+    size.map(|v| f32::max(v - frame, 0.0))
+}
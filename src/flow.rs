@@ -0,0 +1,277 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::f32;
+use std::rc::Rc;
+
+use text_layout::{break_segments, has_break_opportunity, LayoutTextFragment, TextMetrics, TextSegmentMetrics};
+use types::{LayoutBoundingClientRectF, LayoutClientSizeF};
+
+/// How a child of a flow container takes part in its layout.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutFlowDisplay {
+    /// Takes up the whole width of the container, on its own.
+    Block,
+    /// Shares line boxes with adjacent inline siblings.
+    Inline
+}
+
+impl Default for LayoutFlowDisplay {
+    fn default() -> Self {
+        LayoutFlowDisplay::Block
+    }
+}
+
+/// A child of a flow container, as far as flow layout is concerned.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LayoutFlowItem {
+    /// A block level child, and the size of its margin box when laid out at
+    /// the width of the container.
+    Block(LayoutClientSizeF),
+    /// Inline text, which can break across lines.
+    Text(Rc<TextMetrics>),
+    /// An inline box that can't be broken, like an image, and the size of
+    /// its margin box. It sits on the baseline with its bottom edge.
+    Atomic(LayoutClientSizeF)
+}
+
+/// Where a flow item ended up, as a margin box relative to the container's
+/// content box. Text items also get the pieces of text on each of the lines
+/// they span, relative to their own rect.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutFlowFragment {
+    pub rect: LayoutBoundingClientRectF,
+    pub lines: Vec<LayoutTextFragment>
+}
+
+/// Where flow items ended up, and the size of the container's content box.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutFlowLayout {
+    pub fragments: Vec<LayoutFlowFragment>,
+    pub size: LayoutClientSizeF
+}
+
+/// Stacks block items on top of each other, and packs runs of adjacent
+/// inline items into line boxes no wider than `width`. Without a width,
+/// lines are only broken at hard breaks, and the container is as wide as
+/// its widest line or block item.
+pub fn layout_flow(items: &[LayoutFlowItem], width: Option<f32>) -> LayoutFlowLayout {
+    let mut fragments = vec![];
+    let mut top = 0.0;
+    let mut widest = 0.0;
+    let mut index = 0;
+
+    while index < items.len() {
+        if let LayoutFlowItem::Block(size) = items[index] {
+            fragments.push(LayoutFlowFragment {
+                rect: LayoutBoundingClientRectF::new(0.0, top, width.unwrap_or(size.width), size.height),
+                lines: vec![]
+            });
+            top += size.height;
+            widest = f32::max(widest, size.width);
+            index += 1;
+            continue;
+        }
+
+        let end = items[index..]
+            .iter()
+            .position(|v| match *v {
+                LayoutFlowItem::Block(_) => true,
+                _ => false
+            })
+            .map_or(items.len(), |v| index + v);
+
+        let (bottom, line_width) = layout_inline_run(&items[index..end], width, top, &mut fragments);
+        top = bottom;
+        widest = f32::max(widest, line_width);
+        index = end;
+    }
+
+    LayoutFlowLayout {
+        fragments,
+        size: LayoutClientSizeF {
+            width: width.unwrap_or(widest),
+            height: top
+        }
+    }
+}
+
+/// The smallest piece of inline content that can go on a line: a text
+/// segment between two break opportunities, or a whole atomic item.
+#[derive(Debug, Copy, Clone)]
+struct InlinePiece {
+    item: usize,
+    segment: usize,
+    metrics: TextSegmentMetrics,
+    ascent: f32,
+    descent: f32
+}
+
+/// Where a piece was placed, relative to the container's content box.
+#[derive(Debug, Copy, Clone)]
+struct PlacedPiece {
+    line: usize,
+    left: f32,
+    top: f32,
+    baseline: f32
+}
+
+/// Places a run of inline items into line boxes starting at `top`. Returns
+/// the bottom of the last line box and the width of the widest one.
+fn layout_inline_run(items: &[LayoutFlowItem], width: Option<f32>, top: f32, fragments: &mut Vec<LayoutFlowFragment>) -> (f32, f32) {
+    let mut pieces: Vec<InlinePiece> = vec![];
+    let mut last_char = None;
+    for (item, value) in items.iter().enumerate() {
+        match *value {
+            LayoutFlowItem::Text(ref metrics) => {
+                // Adjacent pieces of text only break where UAX #14 allows it,
+                // so a word split across items stays on one line.
+                if let (Some(before), Some(after)) = (last_char, metrics.first_char) {
+                    if !has_break_opportunity(before, after) {
+                        if let Some(piece) = pieces.last_mut() {
+                            piece.metrics.joins_next = true;
+                        }
+                    }
+                }
+                last_char = metrics.last_char;
+
+                for (segment, v) in metrics.segments.iter().enumerate() {
+                    let (line_height, ascent) = metrics.segment_line_metrics(segment);
+                    pieces.push(InlinePiece {
+                        item,
+                        segment,
+                        metrics: *v,
+                        ascent,
+                        descent: line_height - ascent
+                    });
+                }
+            }
+            LayoutFlowItem::Atomic(size) => {
+                last_char = None;
+                pieces.push(InlinePiece {
+                    item,
+                    segment: 0,
+                    metrics: TextSegmentMetrics {
+                        width_f: size.width,
+                        ..TextSegmentMetrics::default()
+                    },
+                    ascent: size.height,
+                    descent: 0.0
+                });
+            }
+            LayoutFlowItem::Block(_) => unreachable!()
+        }
+    }
+
+    // Breaking happens the same way as for a single paragraph, with both
+    // sides of atomic items as extra break opportunities.
+    let segments = pieces.iter().map(|v| v.metrics).collect::<Vec<_>>();
+    let lines = break_segments(&segments, width);
+
+    let mut placed = Vec::with_capacity(pieces.len());
+    let mut line_top = top;
+    let mut widest = 0.0;
+    for (line, range) in lines.iter().enumerate() {
+        let line_pieces = &pieces[range.segments.start..range.segments.end];
+        let ascent = line_pieces.iter().fold(0.0, |acc, v| f32::max(acc, v.ascent));
+        let descent = line_pieces.iter().fold(0.0, |acc, v| f32::max(acc, v.descent));
+        let baseline = line_top + ascent;
+
+        let mut pen = 0.0;
+        for piece in line_pieces {
+            placed.push(PlacedPiece {
+                line,
+                left: pen,
+                top: baseline - piece.ascent,
+                baseline
+            });
+            pen += piece.metrics.width_f + piece.metrics.trailing_width_f;
+        }
+        let trailing = line_pieces.last().map_or(0.0, |v| v.metrics.trailing_width_f);
+        widest = f32::max(widest, pen - trailing);
+        line_top = baseline + descent;
+    }
+
+    for (item, value) in items.iter().enumerate() {
+        let indices = (0..pieces.len()).filter(|&i| pieces[i].item == item).collect::<Vec<_>>();
+        let fragment = match *value {
+            LayoutFlowItem::Text(_) => text_fragment(&pieces, &placed, &indices, line_top),
+            _ => {
+                let (piece, place) = (pieces[indices[0]], placed[indices[0]]);
+                LayoutFlowFragment {
+                    rect: LayoutBoundingClientRectF::new(place.left, place.top, piece.metrics.width_f, piece.ascent + piece.descent),
+                    lines: vec![]
+                }
+            }
+        };
+        fragments.push(fragment);
+    }
+
+    (line_top, widest)
+}
+
+fn text_fragment(pieces: &[InlinePiece], placed: &[PlacedPiece], indices: &[usize], bottom: f32) -> LayoutFlowFragment {
+    if indices.is_empty() {
+        return LayoutFlowFragment {
+            rect: LayoutBoundingClientRectF::new(0.0, bottom, 0.0, 0.0),
+            lines: vec![]
+        };
+    }
+
+    // Group the pieces of this item by line. They're consecutive, and so
+    // are the lines they're on. Each line is as tall as the largest font
+    // of this item on it.
+    let mut lines: Vec<(LayoutTextFragment, f32, f32)> = vec![];
+    let mut current_line = None;
+    for &i in indices {
+        let (piece, place) = (pieces[i], placed[i]);
+        if current_line == Some(place.line) {
+            let &mut (ref mut line, ref mut ascent, ref mut descent) = lines.last_mut().unwrap();
+            line.segments.end = piece.segment + 1;
+            line.rect.size.width = place.left + piece.metrics.width_f - line.rect.position.left;
+            *ascent = f32::max(*ascent, piece.ascent);
+            *descent = f32::max(*descent, piece.descent);
+        } else {
+            let line = LayoutTextFragment {
+                segments: piece.segment..piece.segment + 1,
+                rect: LayoutBoundingClientRectF::new(place.left, 0.0, piece.metrics.width_f, 0.0),
+                baseline_f: place.baseline
+            };
+            lines.push((line, piece.ascent, piece.descent));
+            current_line = Some(place.line);
+        }
+    }
+
+    let mut lines = lines
+        .into_iter()
+        .map(|(mut line, ascent, descent)| {
+            line.rect.position.top = line.baseline_f - ascent;
+            line.rect.size.height = ascent + descent;
+            line
+        })
+        .collect::<Vec<_>>();
+
+    let left = lines.iter().fold(f32::INFINITY, |acc, v| f32::min(acc, v.rect.position.left));
+    let right = lines.iter().fold(0.0, |acc, v| f32::max(acc, v.rect.position.left + v.rect.size.width));
+    let top = lines.iter().fold(f32::INFINITY, |acc, v| f32::min(acc, v.rect.position.top));
+    let bottom = lines.iter().fold(top, |acc, v| f32::max(acc, v.rect.position.top + v.rect.size.height));
+
+    for line in &mut lines {
+        line.rect.position.left -= left;
+        line.rect.position.top -= top;
+        line.baseline_f -= top;
+    }
+
+    LayoutFlowFragment {
+        rect: LayoutBoundingClientRectF::new(left, top, right - left, bottom - top),
+        lines
+    }
+}
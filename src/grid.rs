@@ -0,0 +1,584 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::f32;
+
+use types::{LayoutBoundingClientRectF, LayoutClientSizeF};
+
+/// One side of a track sizing function.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutGridBreadth {
+    Point(f32),
+    /// A share of the space left over once other tracks are sized. Only
+    /// meaningful as a maximum, it's treated like `Auto` as a minimum.
+    Fraction(f32),
+    /// Sized to fit the items in the track.
+    Auto
+}
+
+/// Sizing function of a row or column, like `minmax(min, max)` in CSS.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutGridTrack {
+    pub min: LayoutGridBreadth,
+    pub max: LayoutGridBreadth
+}
+
+impl Default for LayoutGridTrack {
+    fn default() -> Self {
+        LayoutGridTrack::auto()
+    }
+}
+
+impl LayoutGridTrack {
+    pub fn point(value: f32) -> Self {
+        LayoutGridTrack::minmax(LayoutGridBreadth::Point(value), LayoutGridBreadth::Point(value))
+    }
+
+    pub fn fraction(value: f32) -> Self {
+        LayoutGridTrack::minmax(LayoutGridBreadth::Auto, LayoutGridBreadth::Fraction(value))
+    }
+
+    pub fn auto() -> Self {
+        LayoutGridTrack::minmax(LayoutGridBreadth::Auto, LayoutGridBreadth::Auto)
+    }
+
+    pub fn minmax(min: LayoutGridBreadth, max: LayoutGridBreadth) -> Self {
+        LayoutGridTrack { min, max }
+    }
+
+    fn flex_factor(&self) -> Option<f32> {
+        match self.max {
+            LayoutGridBreadth::Fraction(value) => Some(value),
+            _ => None
+        }
+    }
+
+    fn has_auto_min(&self) -> bool {
+        match self.min {
+            LayoutGridBreadth::Point(_) => false,
+            LayoutGridBreadth::Fraction(_) | LayoutGridBreadth::Auto => true
+        }
+    }
+
+    fn has_auto_max(&self) -> bool {
+        match self.max {
+            LayoutGridBreadth::Auto => true,
+            _ => false
+        }
+    }
+}
+
+/// The explicit tracks along one axis, along with the names of the lines
+/// between them.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct LayoutGridTemplate {
+    pub tracks: Vec<LayoutGridTrack>,
+    /// Names of each line, from the one before the first track to the one
+    /// after the last. Lines past the end of this list have no names.
+    pub line_names: Vec<Vec<String>>
+}
+
+impl LayoutGridTemplate {
+    pub fn new(tracks: Vec<LayoutGridTrack>) -> Self {
+        LayoutGridTemplate {
+            tracks,
+            line_names: vec![]
+        }
+    }
+
+    /// Adds a name to a line, numbered from 1 like in CSS. There's no line
+    /// 0, so naming it does nothing.
+    pub fn name_line(&mut self, line: usize, name: &str) {
+        if line == 0 {
+            return;
+        }
+        if self.line_names.len() < line {
+            self.line_names.resize(line, vec![]);
+        }
+        self.line_names[line - 1].push(name.to_string());
+    }
+
+    fn find_line(&self, name: &str) -> Option<usize> {
+        self.line_names.iter().position(|v| v.iter().any(|v| v == name))
+    }
+}
+
+/// One edge of a grid item's placement along an axis.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum LayoutGridLine {
+    Auto,
+    /// A line number, counted from 1. Negative numbers count backwards from
+    /// the end of the explicit grid.
+    Line(i32),
+    Named(String),
+    /// Spans this many tracks from the other edge.
+    Span(u32)
+}
+
+impl Default for LayoutGridLine {
+    fn default() -> Self {
+        LayoutGridLine::Auto
+    }
+}
+
+/// Where a grid item wants to be, like `grid-column` and `grid-row` in CSS.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct LayoutGridPlacement {
+    pub column_start: LayoutGridLine,
+    pub column_end: LayoutGridLine,
+    pub row_start: LayoutGridLine,
+    pub row_end: LayoutGridLine
+}
+
+impl LayoutGridPlacement {
+    pub fn new(column_start: LayoutGridLine, column_end: LayoutGridLine, row_start: LayoutGridLine, row_end: LayoutGridLine) -> Self {
+        LayoutGridPlacement {
+            column_start,
+            column_end,
+            row_start,
+            row_end
+        }
+    }
+}
+
+/// Whether auto placed items fill rows or columns first.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutGridAutoFlow {
+    Row,
+    Column
+}
+
+impl Default for LayoutGridAutoFlow {
+    fn default() -> Self {
+        LayoutGridAutoFlow::Row
+    }
+}
+
+/// Tracks covered by a grid item, as half open ranges of track indices.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct LayoutGridArea {
+    pub column_start: usize,
+    pub column_end: usize,
+    pub row_start: usize,
+    pub row_end: usize
+}
+
+/// Sizes of the tracks along both axes and where each item ended up, with
+/// rects relative to the grid container's content box.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutGridLayout {
+    pub columns: Vec<f32>,
+    pub rows: Vec<f32>,
+    pub areas: Vec<LayoutBoundingClientRectF>,
+    pub size: LayoutClientSizeF
+}
+
+/// Lays out children in rows and columns, like `display: grid` in CSS.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct LayoutGrid {
+    pub columns: LayoutGridTemplate,
+    pub rows: LayoutGridTemplate,
+    /// Sizing function of tracks added past the explicit grid.
+    pub auto_columns: LayoutGridTrack,
+    pub auto_rows: LayoutGridTrack,
+    pub auto_flow: LayoutGridAutoFlow,
+    pub column_gap: f32,
+    pub row_gap: f32
+}
+
+impl LayoutGrid {
+    pub fn new(columns: LayoutGridTemplate, rows: LayoutGridTemplate) -> Self {
+        LayoutGrid {
+            columns,
+            rows,
+            ..LayoutGrid::default()
+        }
+    }
+
+    /// Resolves which tracks each item covers, auto placing items without a
+    /// definite position. The grid grows implicit tracks to fit every item.
+    pub fn place_items(&self, items: &[LayoutGridPlacement]) -> Vec<LayoutGridArea> {
+        let resolved = items
+            .iter()
+            .map(|v| {
+                let columns = resolve_span(&self.columns, &v.column_start, &v.column_end);
+                let rows = resolve_span(&self.rows, &v.row_start, &v.row_end);
+                match self.auto_flow {
+                    LayoutGridAutoFlow::Row => (rows, columns),
+                    LayoutGridAutoFlow::Column => (columns, rows)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Auto placement walks along the minor axis, and wraps onto a new
+        // track along the major axis whenever it runs out of space.
+        let explicit_minor = match self.auto_flow {
+            LayoutGridAutoFlow::Row => self.columns.tracks.len(),
+            LayoutGridAutoFlow::Column => self.rows.tracks.len()
+        };
+        let minor_count = resolved
+            .iter()
+            .map(|&(_, minor)| match minor {
+                GridSpan::Definite(_, end) => end,
+                GridSpan::Auto(span) => span
+            })
+            .fold(usize::max(explicit_minor, 1), usize::max);
+
+        let mut placed: Vec<Option<FlowArea>> = vec![None; items.len()];
+
+        for (index, &(major, minor)) in resolved.iter().enumerate() {
+            if let (GridSpan::Definite(major_start, major_end), GridSpan::Definite(minor_start, minor_end)) = (major, minor) {
+                placed[index] = Some(FlowArea::new(major_start, major_end, minor_start, minor_end));
+            }
+        }
+
+        for (index, &(major, minor)) in resolved.iter().enumerate() {
+            if let (GridSpan::Definite(major_start, major_end), GridSpan::Auto(span)) = (major, minor) {
+                let minor_start = (0..minor_count - span + 1)
+                    .find(|&start| is_free(&placed, &FlowArea::new(major_start, major_end, start, start + span)))
+                    .unwrap_or(0);
+                placed[index] = Some(FlowArea::new(major_start, major_end, minor_start, minor_start + span));
+            }
+        }
+
+        let mut cursor_major = 0;
+        let mut cursor_minor = 0;
+
+        for (index, &(major, minor)) in resolved.iter().enumerate() {
+            let major_span = match major {
+                GridSpan::Definite(..) => continue,
+                GridSpan::Auto(span) => span
+            };
+            let area = match minor {
+                GridSpan::Definite(minor_start, minor_end) => {
+                    if minor_start < cursor_minor {
+                        cursor_major += 1;
+                    }
+                    cursor_minor = minor_start;
+                    while !is_free(&placed, &FlowArea::new(cursor_major, cursor_major + major_span, minor_start, minor_end)) {
+                        cursor_major += 1;
+                    }
+                    FlowArea::new(cursor_major, cursor_major + major_span, minor_start, minor_end)
+                }
+                GridSpan::Auto(minor_span) => loop {
+                    if cursor_minor + minor_span > minor_count {
+                        cursor_major += 1;
+                        cursor_minor = 0;
+                    }
+                    let area = FlowArea::new(cursor_major, cursor_major + major_span, cursor_minor, cursor_minor + minor_span);
+                    if is_free(&placed, &area) {
+                        cursor_minor += minor_span;
+                        break area;
+                    }
+                    cursor_minor += 1;
+                }
+            };
+            placed[index] = Some(area);
+        }
+
+        placed
+            .into_iter()
+            .map(|v| {
+                let v = v.expect("Every grid item should be placed.");
+                match self.auto_flow {
+                    LayoutGridAutoFlow::Row => LayoutGridArea {
+                        column_start: v.minor_start,
+                        column_end: v.minor_end,
+                        row_start: v.major_start,
+                        row_end: v.major_end
+                    },
+                    LayoutGridAutoFlow::Column => LayoutGridArea {
+                        column_start: v.major_start,
+                        column_end: v.major_end,
+                        row_start: v.minor_start,
+                        row_end: v.minor_end
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Places and sizes items inside a content box of the given size. Along
+    /// axes without one, the content box is sized to fit the tracks instead.
+    /// Items are measured with `measure(index, width)`, where `width` is
+    /// the width of their area once the columns are known.
+    pub fn layout<F>(&self, items: &[LayoutGridPlacement], width: Option<f32>, height: Option<f32>, measure: F) -> LayoutGridLayout
+    where
+        F: Fn(usize, Option<f32>) -> LayoutClientSizeF
+    {
+        let areas = self.place_items(items);
+        let column_count = areas.iter().map(|v| v.column_end).fold(self.columns.tracks.len(), usize::max);
+        let row_count = areas.iter().map(|v| v.row_end).fold(self.rows.tracks.len(), usize::max);
+
+        let column_tracks = implicit_tracks(&self.columns, self.auto_columns, column_count);
+        let column_contributions = areas
+            .iter()
+            .enumerate()
+            .map(|(index, v)| (v.column_start, v.column_end, measure(index, None).width))
+            .collect::<Vec<_>>();
+        let columns = size_tracks(&column_tracks, self.column_gap, width, &column_contributions);
+        let column_offsets = track_offsets(&columns, self.column_gap);
+
+        let row_tracks = implicit_tracks(&self.rows, self.auto_rows, row_count);
+        let row_contributions = areas
+            .iter()
+            .enumerate()
+            .map(|(index, v)| {
+                let width = span_size(&column_offsets, &columns, v.column_start, v.column_end);
+                (v.row_start, v.row_end, measure(index, Some(width)).height)
+            })
+            .collect::<Vec<_>>();
+        let rows = size_tracks(&row_tracks, self.row_gap, height, &row_contributions);
+        let row_offsets = track_offsets(&rows, self.row_gap);
+
+        let rects = areas
+            .iter()
+            .map(|v| {
+                LayoutBoundingClientRectF::new(
+                    column_offsets[v.column_start],
+                    row_offsets[v.row_start],
+                    span_size(&column_offsets, &columns, v.column_start, v.column_end),
+                    span_size(&row_offsets, &rows, v.row_start, v.row_end)
+                )
+            })
+            .collect();
+
+        let columns_width = column_offsets.last().map_or(0.0, |v| v + columns[columns.len() - 1]);
+        let rows_height = row_offsets.last().map_or(0.0, |v| v + rows[rows.len() - 1]);
+
+        LayoutGridLayout {
+            columns,
+            rows,
+            areas: rects,
+            size: LayoutClientSizeF {
+                width: width.unwrap_or(columns_width),
+                height: height.unwrap_or(rows_height)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum GridSpan {
+    Definite(usize, usize),
+    Auto(usize)
+}
+
+/// An area in terms of the auto placement axes.
+#[derive(Debug, Copy, Clone)]
+struct FlowArea {
+    major_start: usize,
+    major_end: usize,
+    minor_start: usize,
+    minor_end: usize
+}
+
+impl FlowArea {
+    fn new(major_start: usize, major_end: usize, minor_start: usize, minor_end: usize) -> Self {
+        FlowArea {
+            major_start,
+            major_end,
+            minor_start,
+            minor_end
+        }
+    }
+
+    fn overlaps(&self, other: &FlowArea) -> bool {
+        self.major_start < other.major_end && other.major_start < self.major_end && self.minor_start < other.minor_end
+            && other.minor_start < self.minor_end
+    }
+}
+
+fn is_free(placed: &[Option<FlowArea>], area: &FlowArea) -> bool {
+    placed.iter().filter_map(|v| v.as_ref()).all(|v| !v.overlaps(area))
+}
+
+fn resolve_span(template: &LayoutGridTemplate, start: &LayoutGridLine, end: &LayoutGridLine) -> GridSpan {
+    let line = |line: &LayoutGridLine| match *line {
+        LayoutGridLine::Line(index) if index > 0 => Some(index as usize - 1),
+        LayoutGridLine::Line(index) if index < 0 => Some(i32::max(template.tracks.len() as i32 + 1 + index, 0) as usize),
+        LayoutGridLine::Named(ref name) => template.find_line(name),
+        _ => None
+    };
+    let span = |line: &LayoutGridLine| match *line {
+        LayoutGridLine::Span(span) => Some(u32::max(span, 1) as usize),
+        _ => None
+    };
+
+    match (line(start), line(end)) {
+        (Some(start), Some(end)) if start < end => GridSpan::Definite(start, end),
+        (Some(start), Some(end)) if end < start => GridSpan::Definite(end, start),
+        (Some(start), _) => GridSpan::Definite(start, start + span(end).unwrap_or(1)),
+        (None, Some(end)) => {
+            let span = span(start).unwrap_or(1);
+            if end >= span {
+                GridSpan::Definite(end - span, end)
+            } else {
+                GridSpan::Definite(0, span)
+            }
+        }
+        (None, None) => GridSpan::Auto(span(start).or_else(|| span(end)).unwrap_or(1))
+    }
+}
+
+fn implicit_tracks(template: &LayoutGridTemplate, auto: LayoutGridTrack, count: usize) -> Vec<LayoutGridTrack> {
+    let mut tracks = template.tracks.clone();
+    tracks.resize(usize::max(count, template.tracks.len()), auto);
+    tracks
+}
+
+fn track_offsets(sizes: &[f32], gap: f32) -> Vec<f32> {
+    let mut offset = 0.0;
+    sizes
+        .iter()
+        .map(|size| {
+            let start = offset;
+            offset += size + gap;
+            start
+        })
+        .collect()
+}
+
+fn span_size(offsets: &[f32], sizes: &[f32], start: usize, end: usize) -> f32 {
+    offsets[end - 1] + sizes[end - 1] - offsets[start]
+}
+
+/// A simplified version of the CSS grid track sizing algorithm: content
+/// sized tracks grow to fit the items spanning them, then grow up to their
+/// maximums, then flexible tracks share the leftover space, and finally any
+/// remaining space stretches `auto` tracks.
+fn size_tracks(tracks: &[LayoutGridTrack], gap: f32, available: Option<f32>, contributions: &[(usize, usize, f32)]) -> Vec<f32> {
+    let mut bases = tracks
+        .iter()
+        .map(|v| match v.min {
+            LayoutGridBreadth::Point(value) => value,
+            _ => 0.0
+        })
+        .collect::<Vec<_>>();
+    let mut limits = tracks
+        .iter()
+        .map(|v| match v.max {
+            LayoutGridBreadth::Point(value) => Some(value),
+            _ => None
+        })
+        .collect::<Vec<_>>();
+
+    // Items spanning fewer tracks go first, so that they get to size the
+    // tracks before items spanning several of them.
+    let mut contributions = contributions.to_vec();
+    contributions.sort_by_key(|&(start, end, _)| end - start);
+
+    for &(start, end, size) in &contributions {
+        let span = start..end;
+        if span.len() > 1 && tracks[span.clone()].iter().any(|v| v.flex_factor().is_some()) {
+            continue;
+        }
+        let gaps = gap * (span.len() - 1) as f32;
+
+        let auto_min = span.clone().filter(|&i| tracks[i].has_auto_min()).collect::<Vec<_>>();
+        let extra = size - gaps - bases[span.clone()].iter().sum::<f32>();
+        if extra > 0.0 && !auto_min.is_empty() {
+            for &i in &auto_min {
+                bases[i] += extra / auto_min.len() as f32;
+            }
+        }
+
+        let auto_max = span.clone().filter(|&i| tracks[i].has_auto_max()).collect::<Vec<_>>();
+        let extra = size - gaps - span.clone().map(|i| limits[i].unwrap_or(bases[i])).sum::<f32>();
+        if extra > 0.0 && !auto_max.is_empty() {
+            for &i in &auto_max {
+                limits[i] = Some(limits[i].unwrap_or(bases[i]) + extra / auto_max.len() as f32);
+            }
+        }
+    }
+
+    let limits = limits
+        .iter()
+        .zip(bases.iter())
+        .map(|(limit, base)| f32::max(limit.unwrap_or(*base), *base))
+        .collect::<Vec<_>>();
+
+    let gaps = gap * tracks.len().saturating_sub(1) as f32;
+    let mut sizes = bases.clone();
+    let is_flexible = |i: usize| tracks[i].flex_factor().is_some();
+
+    match available {
+        Some(available) => {
+            let mut free = available - gaps - sizes.iter().sum::<f32>();
+            loop {
+                let growable = (0..tracks.len()).filter(|&i| !is_flexible(i) && limits[i] > sizes[i]).collect::<Vec<_>>();
+                if free <= 0.0 || growable.is_empty() {
+                    break;
+                }
+                let share = free / growable.len() as f32;
+                for &i in &growable {
+                    let growth = f32::min(share, limits[i] - sizes[i]);
+                    sizes[i] += growth;
+                    free -= growth;
+                }
+            }
+        }
+        None => {
+            for i in (0..tracks.len()).filter(|&i| !is_flexible(i)) {
+                sizes[i] = limits[i];
+            }
+        }
+    }
+
+    // Flexible tracks whose base size is larger than their share of the
+    // leftover space keep their base size, and are left out of sharing.
+    let mut inflexible = vec![false; tracks.len()];
+    let fraction = match available {
+        Some(available) => loop {
+            let used = (0..tracks.len()).filter(|&i| !is_flexible(i) || inflexible[i]).map(|i| sizes[i]).sum::<f32>();
+            let factors = (0..tracks.len())
+                .filter(|&i| !inflexible[i])
+                .filter_map(|i| tracks[i].flex_factor())
+                .sum::<f32>();
+            let factors = if factors > 0.0 && factors < 1.0 { 1.0 } else { factors };
+            let fraction = if factors > 0.0 { f32::max(available - gaps - used, 0.0) / factors } else { 0.0 };
+
+            let mut changed = false;
+            for i in 0..tracks.len() {
+                if let (Some(factor), false) = (tracks[i].flex_factor(), inflexible[i]) {
+                    if fraction * factor < bases[i] {
+                        inflexible[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break fraction;
+            }
+        },
+        None => (0..tracks.len())
+            .filter_map(|i| tracks[i].flex_factor().map(|factor| if factor > 0.0 { bases[i] / factor } else { 0.0 }))
+            .fold(0.0, f32::max)
+    };
+
+    for i in 0..tracks.len() {
+        if let (Some(factor), false) = (tracks[i].flex_factor(), inflexible[i]) {
+            sizes[i] = f32::max(bases[i], fraction * factor);
+        }
+    }
+
+    if let Some(available) = available {
+        let free = available - gaps - sizes.iter().sum::<f32>();
+        let auto = (0..tracks.len()).filter(|&i| tracks[i].has_auto_max()).collect::<Vec<_>>();
+        if free > 0.0 && !auto.is_empty() {
+            for &i in &auto {
+                sizes[i] += free / auto.len() as f32;
+            }
+        }
+    }
+
+    sizes
+}
@@ -0,0 +1,83 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use traits::{TLayoutTree, TLayoutTreeNode};
+use types::LayoutBoundingClientRect;
+
+/// Returns the topmost, deepest node under a point in page coordinates,
+/// which are global coordinates with the contents of scroll containers
+/// moved by how far they're scrolled.
+pub fn hit_test<T>(tree: &T, point: (i32, i32)) -> Option<T::NodeId>
+where
+    T: TLayoutTree
+{
+    let mut hits = vec![];
+    collect_hits(tree, tree.root(), None, point, &mut hits);
+    hits.pop()
+}
+
+/// Returns all nodes under a point in page coordinates, ordered front to
+/// back, so that the first one is the same as what `hit_test` returns.
+pub fn hit_test_all<T>(tree: &T, point: (i32, i32)) -> Vec<T::NodeId>
+where
+    T: TLayoutTree
+{
+    let mut hits = vec![];
+    collect_hits(tree, tree.root(), None, point, &mut hits);
+    hits.reverse();
+    hits
+}
+
+/// Looks for hits in a subtree, given a point and a clip in the space the
+/// subtree's global rects are in, which is shifted by the scroll offsets of
+/// all of its ancestors.
+fn collect_hits<T>(tree: &T, id: T::NodeId, clip: Option<LayoutBoundingClientRect>, point: (i32, i32), hits: &mut Vec<T::NodeId>)
+where
+    T: TLayoutTree
+{
+    // Nothing in this subtree can be hit if an ancestor clips the point away.
+    if let Some(ref clip) = clip {
+        if !clip.contains(point) {
+            return;
+        }
+    }
+
+    let mut clip = clip;
+    let mut point = point;
+
+    if let Some(node) = tree.get_layout_node(id) {
+        if node.is_hidden() {
+            return;
+        }
+
+        let rect = node.global_rect();
+        if !rect.is_empty() && rect.contains(point) {
+            hits.push(id);
+        }
+
+        if node.clips_overflow() {
+            clip = match clip {
+                Some(clip) => Some(clip.intersection(&rect).unwrap_or_else(|| rect.zero_size())),
+                None => Some(rect)
+            };
+        }
+
+        // Scrolled contents are painted up and to the left of where they're
+        // laid out, so they're hit further down and to the right.
+        let scroll_offset = node.scroll_offset();
+        point = (point.0 + scroll_offset.left, point.1 + scroll_offset.top);
+        clip = clip.map(|v| v + scroll_offset);
+    }
+
+    for child in tree.get_children(id) {
+        collect_hits(tree, child, clip, point, hits);
+    }
+}
@@ -9,13 +9,36 @@ CONDITIONS OF ANY KIND, either express or implied. See the License for the
 specific language governing permissions and limitations under the License.
 */
 
-#![feature(never_type)]
 #![feature(try_from)]
 
 extern crate rsx_shared;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate unicode_bidi;
+extern crate xi_unicode;
+#[cfg(feature = "yoga")]
 extern crate yoga;
 
+#[cfg(not(any(feature = "yoga", feature = "flexbox")))]
+compile_error!("Either the `yoga` or the `flexbox` feature needs to be enabled.");
+
+pub mod container;
+pub mod damage;
+pub mod engine;
+#[cfg(feature = "flexbox")]
+pub mod flexbox_engine;
+pub mod flow;
+pub mod grid;
+pub mod hit_testing;
+pub mod measure;
+pub mod object_fit;
+pub mod reflow;
+pub mod spatial_index;
+pub mod styles;
+pub mod text_layout;
+pub mod traits;
 pub mod types;
+pub mod user_agent;
+#[cfg(feature = "yoga")]
+pub mod yoga_engine;
@@ -0,0 +1,86 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rsx_shared::types::KnownElementName;
+
+use types::LayoutClientSizeF;
+
+/// How a measure function should treat a suggested width or height.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutMeasureMode {
+    /// There's no constraint, the suggested value should be ignored.
+    Undefined,
+    /// The measured value must be the suggested value.
+    Exactly,
+    /// The measured value must not be larger than the suggested value.
+    AtMost
+}
+
+/// Computes the intrinsic size of an element, given a suggested width and
+/// height along with how each of them should be treated.
+pub type LayoutMeasureFunc = Rc<Fn(f32, LayoutMeasureMode, f32, LayoutMeasureMode) -> LayoutClientSizeF>;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum LayoutMeasureKey {
+    Known(KnownElementName),
+    Custom(String)
+}
+
+impl From<KnownElementName> for LayoutMeasureKey {
+    fn from(value: KnownElementName) -> Self {
+        LayoutMeasureKey::Known(value)
+    }
+}
+
+impl<'a> From<&'a str> for LayoutMeasureKey {
+    fn from(value: &'a str) -> Self {
+        LayoutMeasureKey::Custom(value.to_string())
+    }
+}
+
+/// Measure functions for elements, keyed by element name. Layout nodes don't
+/// look measure functions up by themselves, so that unrelated trees can use
+/// different ones: whoever measures a node passes it the function found
+/// here with `measure_self_as_normal`. Layout nodes won't notice if a
+/// measure function starts returning different sizes, so mark them dirty
+/// when that happens.
+#[derive(Default, Clone)]
+pub struct LayoutMeasureRegistry(HashMap<LayoutMeasureKey, LayoutMeasureFunc>);
+
+impl LayoutMeasureRegistry {
+    pub fn new() -> Self {
+        LayoutMeasureRegistry(HashMap::new())
+    }
+
+    pub fn register<K>(&mut self, key: K, measure: LayoutMeasureFunc)
+    where
+        K: Into<LayoutMeasureKey>
+    {
+        self.0.insert(key.into(), measure);
+    }
+
+    pub fn unregister<K>(&mut self, key: K) -> Option<LayoutMeasureFunc>
+    where
+        K: Into<LayoutMeasureKey>
+    {
+        self.0.remove(&key.into())
+    }
+
+    pub fn get<K>(&self, key: K) -> Option<LayoutMeasureFunc>
+    where
+        K: Into<LayoutMeasureKey>
+    {
+        self.0.get(&key.into()).cloned()
+    }
+}
@@ -0,0 +1,130 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use types::{LayoutBoundingClientRectF, LayoutClientSizeF};
+
+/// How an image is resized to fit its content box.
+/// See https://developer.mozilla.org/en-US/docs/Web/CSS/object-fit
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutObjectFit {
+    Fill,
+    Contain,
+    Cover,
+    None,
+    ScaleDown
+}
+
+impl Default for LayoutObjectFit {
+    fn default() -> Self {
+        LayoutObjectFit::Fill
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutObjectPositionValue {
+    Point(f32),
+    Percent(f32)
+}
+
+impl LayoutObjectPositionValue {
+    fn resolve(&self, free_space: f32) -> f32 {
+        match *self {
+            LayoutObjectPositionValue::Point(value) => value,
+            LayoutObjectPositionValue::Percent(value) => free_space * value / 100.0
+        }
+    }
+}
+
+/// Where an image is placed inside its content box, relative to the top
+/// left corner. Percentages refer to the space left over after fitting.
+/// See https://developer.mozilla.org/en-US/docs/Web/CSS/object-position
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutObjectPosition {
+    pub left: LayoutObjectPositionValue,
+    pub top: LayoutObjectPositionValue
+}
+
+impl Default for LayoutObjectPosition {
+    fn default() -> Self {
+        LayoutObjectPosition {
+            left: LayoutObjectPositionValue::Percent(50.0),
+            top: LayoutObjectPositionValue::Percent(50.0)
+        }
+    }
+}
+
+/// The visible part of an image: `source` is the crop in image pixels, and
+/// `destination` is where it's drawn, in the same space as the content box.
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutImagePlacement {
+    pub destination: LayoutBoundingClientRectF,
+    pub source: LayoutBoundingClientRectF
+}
+
+impl LayoutImagePlacement {
+    pub fn new(
+        fit: LayoutObjectFit,
+        position: LayoutObjectPosition,
+        content_box: &LayoutBoundingClientRectF,
+        image_size: LayoutClientSizeF
+    ) -> Self {
+        let box_size = content_box.size;
+
+        if image_size.width <= 0.0 || image_size.height <= 0.0 || box_size.width <= 0.0 || box_size.height <= 0.0 {
+            return LayoutImagePlacement {
+                destination: content_box.zero_size(),
+                source: LayoutBoundingClientRectF::default()
+            };
+        }
+
+        let scale_x = box_size.width / image_size.width;
+        let scale_y = box_size.height / image_size.height;
+
+        let (scale_x, scale_y) = match fit {
+            LayoutObjectFit::Fill => (scale_x, scale_y),
+            LayoutObjectFit::Contain => (f32::min(scale_x, scale_y), f32::min(scale_x, scale_y)),
+            LayoutObjectFit::Cover => (f32::max(scale_x, scale_y), f32::max(scale_x, scale_y)),
+            LayoutObjectFit::None => (1.0, 1.0),
+            LayoutObjectFit::ScaleDown => {
+                let scale = f32::min(f32::min(scale_x, scale_y), 1.0);
+                (scale, scale)
+            }
+        };
+
+        let width = image_size.width * scale_x;
+        let height = image_size.height * scale_y;
+        let left = content_box.position.left + position.left.resolve(box_size.width - width);
+        let top = content_box.position.top + position.top.resolve(box_size.height - height);
+        let fitted = LayoutBoundingClientRectF::new(left, top, width, height);
+
+        let destination = match fitted.intersection(content_box) {
+            Some(destination) => destination,
+            None => {
+                return LayoutImagePlacement {
+                    destination: content_box.zero_size(),
+                    source: LayoutBoundingClientRectF::default()
+                }
+            }
+        };
+
+        let source = LayoutBoundingClientRectF::new(
+            (destination.position.left - left) / scale_x,
+            (destination.position.top - top) / scale_y,
+            destination.size.width / scale_x,
+            destination.size.height / scale_y
+        );
+
+        LayoutImagePlacement {
+            destination,
+            source
+        }
+    }
+}
@@ -0,0 +1,126 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use rsx_shared::traits::TLayoutNode;
+
+use damage::LayoutDamage;
+use traits::{TLayoutTree, TLayoutTreeMut, TLayoutTreeNode};
+use types::{LayoutBoundingClientRect, LayoutClientPosition, LayoutClientPositionF, LayoutReflowDirection};
+
+/// Nodes whose local or global rect changed during a reflow, in tree order,
+/// and the areas that need repainting because of it. Nodes laid out for the
+/// first time are included. Nodes that were tainted going into the reflow,
+/// because their styles, measurements or children changed, are listed
+/// separately, also in tree order.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutChangeSet<K> {
+    pub changed: Vec<K>,
+    pub tainted: Vec<K>,
+    pub damage: LayoutDamage
+}
+
+impl<K> Default for LayoutChangeSet<K> {
+    fn default() -> Self {
+        LayoutChangeSet {
+            changed: vec![],
+            tainted: vec![],
+            damage: LayoutDamage::new()
+        }
+    }
+}
+
+impl<K> LayoutChangeSet<K>
+where
+    K: PartialEq
+{
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.tainted.is_empty()
+    }
+
+    pub fn contains(&self, id: &K) -> bool {
+        self.changed.contains(id)
+    }
+}
+
+/// Calculates layout for a whole tree, positions every node relative to the
+/// root, and collects which nodes changed.
+pub fn reflow_tree<T>(tree: &mut T, width: u32, height: u32, direction: LayoutReflowDirection) -> LayoutChangeSet<T::NodeId>
+where
+    T: TLayoutTreeMut,
+    T::LayoutNode: TLayoutNode<
+        ReflowDirection = LayoutReflowDirection,
+        ClientPosition = LayoutClientPosition,
+        BoundingClientRect = LayoutBoundingClientRect
+    >
+{
+    let root = tree.root();
+    if let Some(node) = tree.get_layout_node_mut(root) {
+        node.reflow_subtree(width, height, direction);
+    }
+
+    position_subtree(tree, root, LayoutClientPosition::default(), LayoutClientPositionF::default());
+    collect_changes(tree)
+}
+
+/// Nodes whose rects changed since changes were last collected, for owners
+/// of a tree that call `reflow_subtree` and set positions themselves. Setting
+/// a node's position after a reflow is what commits it and clears its
+/// tainted flag.
+pub fn collect_changes<T>(tree: &mut T) -> LayoutChangeSet<T::NodeId>
+where
+    T: TLayoutTreeMut
+{
+    let mut changes = LayoutChangeSet::default();
+    let root = tree.root();
+    collect_subtree(tree, root, &mut changes);
+    changes
+}
+
+/// Positions nodes from the unsnapped global position of their parent as
+/// well, so that snapping can round global edges.
+fn position_subtree<T>(tree: &mut T, id: T::NodeId, position: LayoutClientPosition, position_f: LayoutClientPositionF)
+where
+    T: TLayoutTreeMut
+{
+    let (position, position_f) = match tree.get_layout_node_mut(id) {
+        Some(node) => {
+            node.set_computed_client_positions(position, position_f);
+            (node.global_rect().position, node.global_rect_f().position)
+        }
+        None => (position, position_f)
+    };
+
+    for child in tree.get_children(id) {
+        position_subtree(tree, child, position, position_f);
+    }
+}
+
+fn collect_subtree<T>(tree: &mut T, id: T::NodeId, changes: &mut LayoutChangeSet<T::NodeId>)
+where
+    T: TLayoutTreeMut
+{
+    if let Some(node) = tree.get_layout_node_mut(id) {
+        for rect in node.take_removed_rects() {
+            changes.damage.add(rect);
+        }
+        if let Some(change) = node.take_rect_change() {
+            changes.changed.push(id);
+            changes.damage.add_change(change.previous, change.current);
+        }
+        if node.take_tainted_reflow() {
+            changes.tainted.push(id);
+        }
+    }
+
+    for child in tree.get_children(id) {
+        collect_subtree(tree, child, changes);
+    }
+}
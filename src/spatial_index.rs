@@ -0,0 +1,307 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use reflow::LayoutChangeSet;
+use traits::{TLayoutTree, TLayoutTreeNode};
+use types::LayoutBoundingClientRect;
+
+pub const DEFAULT_CELL_SIZE: u32 = 256;
+
+/// A uniform grid over global bounding client rects, answering which nodes
+/// intersect a rect or lie near a point without walking the whole layout tree.
+/// Nodes indexed from a tree are stored with the part of their rect that
+/// isn't clipped away by ancestors.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutSpatialIndex<K>
+where
+    K: Copy + Eq + Hash
+{
+    cell_size: u32,
+    cells: HashMap<(i32, i32), Vec<K>>,
+    rects: HashMap<K, LayoutBoundingClientRect>,
+    parents: HashMap<K, K>,
+    clips: HashMap<K, Option<LayoutBoundingClientRect>>
+}
+
+impl<K> Default for LayoutSpatialIndex<K>
+where
+    K: Copy + Eq + Hash
+{
+    fn default() -> Self {
+        LayoutSpatialIndex::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl<K> LayoutSpatialIndex<K>
+where
+    K: Copy + Eq + Hash
+{
+    /// Creates an index with square cells of the given size. Cells can't be
+    /// empty, so a size of 0 is ignored in favor of `DEFAULT_CELL_SIZE`.
+    pub fn new(cell_size: u32) -> Self {
+        LayoutSpatialIndex {
+            cell_size: if cell_size > 0 { cell_size } else { DEFAULT_CELL_SIZE },
+            cells: HashMap::new(),
+            rects: HashMap::new(),
+            parents: HashMap::new(),
+            clips: HashMap::new()
+        }
+    }
+
+    pub fn from_tree<T>(tree: &T, cell_size: u32) -> Self
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        let mut index = LayoutSpatialIndex::new(cell_size);
+        index.update_subtree(tree, tree.root());
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.rects.clear();
+        self.parents.clear();
+        self.clips.clear();
+    }
+
+    pub fn get_rect(&self, key: K) -> Option<LayoutBoundingClientRect> {
+        self.rects.get(&key).cloned()
+    }
+
+    /// Adds a node to the index, or moves it if it's already there.
+    /// Empty rects can't intersect anything, so they aren't stored.
+    pub fn insert(&mut self, key: K, rect: LayoutBoundingClientRect) {
+        if self.rects.get(&key) == Some(&rect) {
+            return;
+        }
+        self.remove(key);
+        if rect.is_empty() {
+            return;
+        }
+        let ((x0, y0), (x1, y1)) = self.cell_range(&rect);
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                self.cells.entry((x, y)).or_insert_with(Vec::new).push(key);
+            }
+        }
+        self.rects.insert(key, rect);
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<LayoutBoundingClientRect> {
+        let rect = self.rects.remove(&key)?;
+        let ((x0, y0), (x1, y1)) = self.cell_range(&rect);
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                let is_cell_empty = match self.cells.get_mut(&(x, y)) {
+                    Some(keys) => {
+                        keys.retain(|k| *k != key);
+                        keys.is_empty()
+                    }
+                    None => false
+                };
+                if is_cell_empty {
+                    self.cells.remove(&(x, y));
+                }
+            }
+        }
+        Some(rect)
+    }
+
+    /// Re-indexes all nodes in a subtree whose global rect changed since
+    /// they were last indexed. Hidden subtrees are dropped from the index.
+    /// Nodes removed from the tree need to be removed separately.
+    pub fn update_subtree<T>(&mut self, tree: &T, id: K)
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        let clip = self.ancestor_clip(id);
+        self.index_node(tree, id, clip, true);
+    }
+
+    /// Re-indexes only what a reflow could have changed: the nodes whose
+    /// rects changed, tainted nodes and their children, which might have
+    /// just been inserted, and descendants of nodes whose clip changed.
+    /// Nodes removed from the tree need to be removed separately.
+    pub fn update_changed<T>(&mut self, tree: &T, changes: &LayoutChangeSet<K>)
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        for id in &changes.tainted {
+            let clip = self.ancestor_clip(*id);
+            if self.index_node(tree, *id, clip, false) {
+                continue;
+            }
+            let clip = self.clips.get(id).cloned().unwrap_or(None);
+            for child in tree.get_children(*id) {
+                self.parents.insert(child, *id);
+                self.index_node(tree, child, clip, false);
+            }
+        }
+        for id in &changes.changed {
+            let clip = self.ancestor_clip(*id);
+            self.index_node(tree, *id, clip, false);
+        }
+    }
+
+    pub fn remove_subtree<T>(&mut self, tree: &T, id: K)
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        self.remove(id);
+        self.parents.remove(&id);
+        self.clips.remove(&id);
+        for child in tree.get_children(id) {
+            self.remove_subtree(tree, child);
+        }
+    }
+
+    /// The clip a node was last indexed with, from its ancestors clipping
+    /// their overflow.
+    fn ancestor_clip(&self, id: K) -> Option<LayoutBoundingClientRect> {
+        self.parents
+            .get(&id)
+            .and_then(|parent| self.clips.get(parent))
+            .cloned()
+            .unwrap_or(None)
+    }
+
+    /// Indexes the visible part of a node's rect, and its children when
+    /// `recurse` is set or when the clip they get changed. Returns whether
+    /// the children were visited.
+    fn index_node<T>(&mut self, tree: &T, id: K, clip: Option<LayoutBoundingClientRect>, recurse: bool) -> bool
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        let (rect, clips_overflow) = match tree.get_layout_node(id) {
+            Some(node) if !node.is_hidden() => (node.global_rect(), node.clips_overflow()),
+            _ => {
+                self.drop_subtree(tree, id);
+                return true;
+            }
+        };
+
+        let visible = match clip {
+            Some(clip) => clip.intersection(&rect).unwrap_or_else(|| rect.zero_size()),
+            None => rect
+        };
+        self.insert(id, visible);
+
+        let children_clip = if clips_overflow { Some(visible) } else { clip };
+        let is_clip_changed = self.clips.insert(id, children_clip) != Some(children_clip);
+        if !recurse && !is_clip_changed {
+            return false;
+        }
+        for child in tree.get_children(id) {
+            self.parents.insert(child, id);
+            self.index_node(tree, child, children_clip, recurse);
+        }
+        true
+    }
+
+    /// Drops a subtree from the index, but remembers where its nodes are in
+    /// the tree, in case they show up again.
+    fn drop_subtree<T>(&mut self, tree: &T, id: K)
+    where
+        T: TLayoutTree<NodeId = K>
+    {
+        self.remove(id);
+        self.clips.remove(&id);
+        for child in tree.get_children(id) {
+            self.drop_subtree(tree, child);
+        }
+    }
+
+    /// Returns all nodes intersecting a rect in global coordinates, in no
+    /// particular order.
+    pub fn query_rect(&self, rect: &LayoutBoundingClientRect) -> Vec<K> {
+        self.query_candidates(rect)
+            .into_iter()
+            .filter(|key| self.rects[key].intersects(rect))
+            .collect()
+    }
+
+    /// Returns all nodes whose rect is at most `radius` pixels away from a
+    /// point in global coordinates, in no particular order.
+    pub fn query_point(&self, (x, y): (i32, i32), radius: u32) -> Vec<K> {
+        let search = LayoutBoundingClientRect::new(
+            x.saturating_sub(radius as i32),
+            y.saturating_sub(radius as i32),
+            radius.saturating_mul(2).saturating_add(1),
+            radius.saturating_mul(2).saturating_add(1)
+        );
+        let radius = i64::from(radius);
+        self.query_candidates(&search)
+            .into_iter()
+            .filter(|key| {
+                let rect = &self.rects[key];
+                let dx = distance_to_span(i64::from(x), i64::from(rect.position.left), rect.right());
+                let dy = distance_to_span(i64::from(y), i64::from(rect.position.top), rect.bottom());
+                dx * dx + dy * dy <= radius * radius
+            })
+            .collect()
+    }
+
+    fn query_candidates(&self, rect: &LayoutBoundingClientRect) -> HashSet<K> {
+        let mut candidates = HashSet::new();
+        if rect.is_empty() {
+            return candidates;
+        }
+        let ((x0, y0), (x1, y1)) = self.cell_range(rect);
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                if let Some(keys) = self.cells.get(&(x, y)) {
+                    candidates.extend(keys.iter().cloned());
+                }
+            }
+        }
+        candidates
+    }
+
+    fn cell_range(&self, rect: &LayoutBoundingClientRect) -> ((i32, i32), (i32, i32)) {
+        let size = i64::from(self.cell_size);
+        let x0 = cell_coord(i64::from(rect.position.left), size);
+        let y0 = cell_coord(i64::from(rect.position.top), size);
+        let x1 = cell_coord(rect.right() - 1, size);
+        let y1 = cell_coord(rect.bottom() - 1, size);
+        ((x0, y0), (x1, y1))
+    }
+}
+
+#[inline]
+fn cell_coord(value: i64, cell_size: i64) -> i32 {
+    if value >= 0 {
+        (value / cell_size) as i32
+    } else {
+        ((value - cell_size + 1) / cell_size) as i32
+    }
+}
+
+#[inline]
+fn distance_to_span(value: i64, start: i64, end: i64) -> i64 {
+    if value < start {
+        start - value
+    } else if value >= end {
+        value - end + 1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,286 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::mem;
+
+/// A length that's either absolute, relative to the size of the containing
+/// block, or left for the layout engine to figure out.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutStyleUnit {
+    Undefined,
+    Point(f32),
+    Percent(f32),
+    Auto
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutAlign {
+    Auto,
+    FlexStart,
+    Center,
+    FlexEnd,
+    Stretch,
+    Baseline,
+    SpaceBetween,
+    SpaceAround
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutJustify {
+    FlexStart,
+    Center,
+    FlexEnd,
+    SpaceBetween,
+    SpaceAround
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutFlexDirection {
+    Column,
+    ColumnReverse,
+    Row,
+    RowReverse
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutFlexWrap {
+    NoWrap,
+    Wrap,
+    WrapReverse
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutDisplay {
+    Flex,
+    None
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutPositionType {
+    Relative,
+    Absolute
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutOverflow {
+    Visible,
+    Hidden,
+    Scroll
+}
+
+/// A single layout property and its value, as understood by every layout
+/// engine. Numbers without a value are undefined, which is also the
+/// default for most of them.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutStyle {
+    AlignContent(LayoutAlign),
+    AlignItems(LayoutAlign),
+    AlignSelf(LayoutAlign),
+    AspectRatio(Option<f32>),
+    Border(Option<f32>),
+    BorderBottom(Option<f32>),
+    BorderEnd(Option<f32>),
+    BorderLeft(Option<f32>),
+    BorderRight(Option<f32>),
+    BorderStart(Option<f32>),
+    BorderTop(Option<f32>),
+    Bottom(LayoutStyleUnit),
+    Display(LayoutDisplay),
+    End(LayoutStyleUnit),
+    Flex(Option<f32>),
+    FlexBasis(LayoutStyleUnit),
+    FlexDirection(LayoutFlexDirection),
+    FlexGrow(Option<f32>),
+    FlexShrink(Option<f32>),
+    FlexWrap(LayoutFlexWrap),
+    Height(LayoutStyleUnit),
+    JustifyContent(LayoutJustify),
+    Left(LayoutStyleUnit),
+    Margin(LayoutStyleUnit),
+    MarginBottom(LayoutStyleUnit),
+    MarginEnd(LayoutStyleUnit),
+    MarginHorizontal(LayoutStyleUnit),
+    MarginLeft(LayoutStyleUnit),
+    MarginRight(LayoutStyleUnit),
+    MarginStart(LayoutStyleUnit),
+    MarginTop(LayoutStyleUnit),
+    MarginVertical(LayoutStyleUnit),
+    MaxHeight(LayoutStyleUnit),
+    MaxWidth(LayoutStyleUnit),
+    MinHeight(LayoutStyleUnit),
+    MinWidth(LayoutStyleUnit),
+    Overflow(LayoutOverflow),
+    Padding(LayoutStyleUnit),
+    PaddingBottom(LayoutStyleUnit),
+    PaddingEnd(LayoutStyleUnit),
+    PaddingHorizontal(LayoutStyleUnit),
+    PaddingLeft(LayoutStyleUnit),
+    PaddingRight(LayoutStyleUnit),
+    PaddingStart(LayoutStyleUnit),
+    PaddingTop(LayoutStyleUnit),
+    PaddingVertical(LayoutStyleUnit),
+    Position(LayoutPositionType),
+    Right(LayoutStyleUnit),
+    Start(LayoutStyleUnit),
+    Top(LayoutStyleUnit),
+    Width(LayoutStyleUnit)
+}
+
+/// The direction text and inline content flow in, which decides what start
+/// and end edges are.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutReflowDirection {
+    Inherit,
+    LTR,
+    RTL
+}
+
+impl Default for LayoutReflowDirection {
+    fn default() -> Self {
+        LayoutReflowDirection::Inherit
+    }
+}
+
+/// Whether two styles set the same property, regardless of their values.
+#[inline]
+pub fn is_same_property(a: &LayoutStyle, b: &LayoutStyle) -> bool {
+    mem::discriminant(a) == mem::discriminant(b)
+}
+
+/// The default value for the property set by a style, which is the same
+/// for every layout engine.
+pub fn default_layout_style(style: &LayoutStyle) -> LayoutStyle {
+    use self::LayoutStyleUnit::{Auto, Undefined};
+
+    match *style {
+        LayoutStyle::AlignContent(_) => LayoutStyle::AlignContent(LayoutAlign::FlexStart),
+        LayoutStyle::AlignItems(_) => LayoutStyle::AlignItems(LayoutAlign::Stretch),
+        LayoutStyle::AlignSelf(_) => LayoutStyle::AlignSelf(LayoutAlign::Auto),
+        LayoutStyle::AspectRatio(_) => LayoutStyle::AspectRatio(None),
+        LayoutStyle::Border(_) => LayoutStyle::Border(None),
+        LayoutStyle::BorderBottom(_) => LayoutStyle::BorderBottom(None),
+        LayoutStyle::BorderEnd(_) => LayoutStyle::BorderEnd(None),
+        LayoutStyle::BorderLeft(_) => LayoutStyle::BorderLeft(None),
+        LayoutStyle::BorderRight(_) => LayoutStyle::BorderRight(None),
+        LayoutStyle::BorderStart(_) => LayoutStyle::BorderStart(None),
+        LayoutStyle::BorderTop(_) => LayoutStyle::BorderTop(None),
+        LayoutStyle::Bottom(_) => LayoutStyle::Bottom(Undefined),
+        LayoutStyle::Display(_) => LayoutStyle::Display(LayoutDisplay::Flex),
+        LayoutStyle::End(_) => LayoutStyle::End(Undefined),
+        LayoutStyle::Flex(_) => LayoutStyle::Flex(None),
+        LayoutStyle::FlexBasis(_) => LayoutStyle::FlexBasis(Auto),
+        LayoutStyle::FlexDirection(_) => LayoutStyle::FlexDirection(LayoutFlexDirection::Column),
+        LayoutStyle::FlexGrow(_) => LayoutStyle::FlexGrow(None),
+        LayoutStyle::FlexShrink(_) => LayoutStyle::FlexShrink(None),
+        LayoutStyle::FlexWrap(_) => LayoutStyle::FlexWrap(LayoutFlexWrap::NoWrap),
+        LayoutStyle::Height(_) => LayoutStyle::Height(Auto),
+        LayoutStyle::JustifyContent(_) => LayoutStyle::JustifyContent(LayoutJustify::FlexStart),
+        LayoutStyle::Left(_) => LayoutStyle::Left(Undefined),
+        LayoutStyle::Margin(_) => LayoutStyle::Margin(Undefined),
+        LayoutStyle::MarginBottom(_) => LayoutStyle::MarginBottom(Undefined),
+        LayoutStyle::MarginEnd(_) => LayoutStyle::MarginEnd(Undefined),
+        LayoutStyle::MarginHorizontal(_) => LayoutStyle::MarginHorizontal(Undefined),
+        LayoutStyle::MarginLeft(_) => LayoutStyle::MarginLeft(Undefined),
+        LayoutStyle::MarginRight(_) => LayoutStyle::MarginRight(Undefined),
+        LayoutStyle::MarginStart(_) => LayoutStyle::MarginStart(Undefined),
+        LayoutStyle::MarginTop(_) => LayoutStyle::MarginTop(Undefined),
+        LayoutStyle::MarginVertical(_) => LayoutStyle::MarginVertical(Undefined),
+        LayoutStyle::MaxHeight(_) => LayoutStyle::MaxHeight(Undefined),
+        LayoutStyle::MaxWidth(_) => LayoutStyle::MaxWidth(Undefined),
+        LayoutStyle::MinHeight(_) => LayoutStyle::MinHeight(Undefined),
+        LayoutStyle::MinWidth(_) => LayoutStyle::MinWidth(Undefined),
+        LayoutStyle::Overflow(_) => LayoutStyle::Overflow(LayoutOverflow::Visible),
+        LayoutStyle::Padding(_) => LayoutStyle::Padding(Undefined),
+        LayoutStyle::PaddingBottom(_) => LayoutStyle::PaddingBottom(Undefined),
+        LayoutStyle::PaddingEnd(_) => LayoutStyle::PaddingEnd(Undefined),
+        LayoutStyle::PaddingHorizontal(_) => LayoutStyle::PaddingHorizontal(Undefined),
+        LayoutStyle::PaddingLeft(_) => LayoutStyle::PaddingLeft(Undefined),
+        LayoutStyle::PaddingRight(_) => LayoutStyle::PaddingRight(Undefined),
+        LayoutStyle::PaddingStart(_) => LayoutStyle::PaddingStart(Undefined),
+        LayoutStyle::PaddingTop(_) => LayoutStyle::PaddingTop(Undefined),
+        LayoutStyle::PaddingVertical(_) => LayoutStyle::PaddingVertical(Undefined),
+        LayoutStyle::Position(_) => LayoutStyle::Position(LayoutPositionType::Relative),
+        LayoutStyle::Right(_) => LayoutStyle::Right(Undefined),
+        LayoutStyle::Start(_) => LayoutStyle::Start(Undefined),
+        LayoutStyle::Top(_) => LayoutStyle::Top(Undefined),
+        LayoutStyle::Width(_) => LayoutStyle::Width(Auto)
+    }
+}
+
+/// The layout styles that were pushed to a layout node, at most one value
+/// per property. Properties that were never set are at their defaults.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct LayoutStyleSnapshot(Vec<LayoutStyle>);
+
+impl LayoutStyleSnapshot {
+    pub fn new() -> Self {
+        LayoutStyleSnapshot(vec![])
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<LayoutStyle> {
+        self.0.iter()
+    }
+
+    /// Returns the value of the property set by a style, if it was set.
+    pub fn get(&self, property: &LayoutStyle) -> Option<&LayoutStyle> {
+        self.0.iter().find(|v| is_same_property(v, property))
+    }
+
+    /// Sets a property, replacing its previous value. Returns whether the
+    /// value of the property changed.
+    pub fn set(&mut self, style: LayoutStyle) -> bool {
+        if let Some(existing) = self.0.iter_mut().find(|v| is_same_property(v, &style)) {
+            let changed = *existing != style;
+            *existing = style;
+            return changed;
+        }
+        self.0.push(style);
+        true
+    }
+
+    pub fn remove(&mut self, property: &LayoutStyle) -> Option<LayoutStyle> {
+        let index = self.0.iter().position(|v| is_same_property(v, property))?;
+        Some(self.0.swap_remove(index))
+    }
+
+    /// Styles that need to be applied to go from this snapshot to another
+    /// one: changed and added properties take their new values, while
+    /// properties missing from the other snapshot go back to their defaults.
+    pub fn diff(&self, other: &LayoutStyleSnapshot) -> Vec<LayoutStyle> {
+        let mut changes = vec![];
+        for style in &self.0 {
+            if other.get(style).is_none() {
+                let default = default_layout_style(style);
+                if default != *style {
+                    changes.push(default);
+                }
+            }
+        }
+        for style in &other.0 {
+            let unchanged = match self.get(style) {
+                Some(existing) => existing == style,
+                None => *style == default_layout_style(style)
+            };
+            if !unchanged {
+                changes.push(*style);
+            }
+        }
+        changes
+    }
+}
@@ -0,0 +1,1118 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::f32;
+use std::iter;
+use std::ops::Range;
+use std::rc::Rc;
+
+use rsx_shared::traits::{TGlyphStore, TShapedText};
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+use xi_unicode::LineBreakIterator;
+
+use types::{LayoutBoundingClientRectF, LayoutClientPositionF, LayoutReflowDirection, ShapedText};
+
+/// Glyph stores only report the height of a line, not the font's ascent, so
+/// baselines are placed at this fraction of the line height, which is close
+/// to what most fonts use.
+pub const DEFAULT_ASCENT_RATIO: f32 = 0.8;
+
+pub const LAYOUT_FONT_WEIGHT_NORMAL: u16 = 400;
+
+pub const LAYOUT_TEXT_ELLIPSIS: &str = "\u{2026}";
+
+/// Preserved tabs are expanded to spaces up to the next multiple of this
+/// many columns.
+pub const LAYOUT_TAB_SIZE: usize = 8;
+
+/// How whitespace in text is processed before shaping, and whether lines
+/// wrap, like the CSS `white-space` property.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutWhiteSpace {
+    Normal,
+    Pre,
+    PreWrap,
+    PreLine,
+    NoWrap
+}
+
+impl Default for LayoutWhiteSpace {
+    fn default() -> Self {
+        LayoutWhiteSpace::Normal
+    }
+}
+
+impl LayoutWhiteSpace {
+    /// Whether sequences of spaces and tabs collapse into a single space.
+    pub fn collapses_spaces(self) -> bool {
+        match self {
+            LayoutWhiteSpace::Normal | LayoutWhiteSpace::NoWrap | LayoutWhiteSpace::PreLine => true,
+            LayoutWhiteSpace::Pre | LayoutWhiteSpace::PreWrap => false
+        }
+    }
+
+    /// Whether newlines are kept as hard line breaks instead of collapsing
+    /// like spaces.
+    pub fn preserves_newlines(self) -> bool {
+        match self {
+            LayoutWhiteSpace::Pre | LayoutWhiteSpace::PreWrap | LayoutWhiteSpace::PreLine => true,
+            LayoutWhiteSpace::Normal | LayoutWhiteSpace::NoWrap => false
+        }
+    }
+
+    /// Whether lines wrap at soft break opportunities.
+    pub fn wraps(self) -> bool {
+        match self {
+            LayoutWhiteSpace::Normal | LayoutWhiteSpace::PreWrap | LayoutWhiteSpace::PreLine => true,
+            LayoutWhiteSpace::Pre | LayoutWhiteSpace::NoWrap => false
+        }
+    }
+}
+
+/// Collapses or preserves whitespace in a paragraph made of several spans,
+/// as if they were a single piece of text. `TextLayout` does this before
+/// shaping, so text is passed to it as it appears in the source. Collapsible
+/// spaces at the start of the paragraph, or of a line after a preserved
+/// newline, are removed.
+pub fn process_white_space(spans: &[&str], white_space: LayoutWhiteSpace) -> Vec<String> {
+    let collapses_spaces = white_space.collapses_spaces();
+    let preserves_newlines = white_space.preserves_newlines();
+    let mut after_space = collapses_spaces;
+    let mut column = 0;
+
+    spans
+        .iter()
+        .map(|text| {
+            let mut result = String::with_capacity(text.len());
+            for c in text.replace("\r\n", "\n").chars() {
+                if preserves_newlines && is_newline(c) {
+                    // Spaces around preserved newlines are removed when
+                    // other spaces collapse.
+                    if collapses_spaces {
+                        let trimmed = result.trim_right_matches(' ').len();
+                        result.truncate(trimmed);
+                    }
+                    result.push(c);
+                    after_space = collapses_spaces;
+                    column = 0;
+                } else if collapses_spaces && (c == ' ' || c == '\t' || is_newline(c)) {
+                    if !after_space {
+                        result.push(' ');
+                        column += 1;
+                    }
+                    after_space = true;
+                } else if c == '\t' {
+                    let width = LAYOUT_TAB_SIZE - column % LAYOUT_TAB_SIZE;
+                    result.extend(iter::repeat(' ').take(width));
+                    column += width;
+                } else {
+                    result.push(c);
+                    column += 1;
+                    after_space = false;
+                }
+            }
+            result
+        })
+        .collect()
+}
+
+/// What happens to text that doesn't fit: it's either cut off, or its last
+/// visible line ends with an ellipsis.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutTextOverflow {
+    Clip,
+    Ellipsis
+}
+
+impl Default for LayoutTextOverflow {
+    fn default() -> Self {
+        LayoutTextOverflow::Clip
+    }
+}
+
+/// A run of text with its own font and colour, laid out in one paragraph
+/// together with other spans.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LayoutTextSpan {
+    pub text: String,
+    /// Font families to try, in order, before falling back to the default.
+    pub font_families: Vec<String>,
+    pub font_size: f32,
+    /// Passed through to renderers. Fonts are picked by family and size, so
+    /// this doesn't affect layout.
+    pub font_weight: u16,
+    /// Opaque key the renderer resolves into an actual colour.
+    pub color_key: Option<String>
+}
+
+impl LayoutTextSpan {
+    pub fn new(text: &str, font_size: f32) -> Self {
+        LayoutTextSpan {
+            text: text.to_string(),
+            font_families: vec![],
+            font_size,
+            font_weight: LAYOUT_FONT_WEIGHT_NORMAL,
+            color_key: None
+        }
+    }
+}
+
+/// Widths of a piece of text between two line break opportunities. Trailing
+/// whitespace is measured separately, since it hangs at the end of a line.
+/// When a span boundary splits a word, each part gets its own segment, but
+/// there's no break opportunity between them.
+#[derive(Debug, PartialEq, Default, Copy, Clone)]
+pub struct TextSegmentMetrics {
+    pub width_f: f32,
+    pub trailing_width_f: f32,
+    pub hard_break: bool,
+    pub joins_next: bool
+}
+
+/// Vertical metrics of the font a span of text is shaped with, and which
+/// segments belong to that span.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TextSpanMetrics {
+    pub segments: Range<usize>,
+    pub line_height_f: f32,
+    pub ascent_f: f32
+}
+
+/// Everything needed to break a paragraph into lines for an arbitrary
+/// width, without having to shape text again. This is what Yoga's measure
+/// function gets to look at. Segments outside of any span use the
+/// paragraph's line height and ascent. Lines past `max_lines` are dropped,
+/// and when there's an ellipsis, lines that were cut short end with it. The
+/// first and last characters tell whether the paragraph can break from the
+/// content next to it when laid out inline.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TextMetrics {
+    pub segments: Vec<TextSegmentMetrics>,
+    pub spans: Vec<TextSpanMetrics>,
+    pub line_height_f: f32,
+    pub ascent_f: f32,
+    pub max_lines: Option<usize>,
+    pub ellipsis_width_f: Option<f32>,
+    pub first_char: Option<char>,
+    pub last_char: Option<char>
+}
+
+/// Segments that ended up on one line. When the line ends with an ellipsis,
+/// its width includes it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextLine {
+    pub segments: Range<usize>,
+    pub width_f: f32,
+    pub ellipsis: bool
+}
+
+/// The segments of a paragraph that ended up on one line when laid out
+/// inline with other content, and where they were placed. The baseline is
+/// in the same coordinate space as the rect.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutTextFragment {
+    pub segments: Range<usize>,
+    pub rect: LayoutBoundingClientRectF,
+    pub baseline_f: f32
+}
+
+impl TextMetrics {
+    /// Greedily packs segments into lines no wider than `max_width`, breaking
+    /// only at hard breaks when there's no width constraint, then clamps and
+    /// truncates them.
+    pub fn break_lines(&self, max_width: Option<f32>) -> Vec<TextLine> {
+        let mut lines = break_segments(&self.segments, max_width);
+
+        let is_clamped = match self.max_lines {
+            Some(max_lines) if lines.len() > max_lines => {
+                lines.truncate(usize::max(max_lines, 1));
+                true
+            }
+            _ => false
+        };
+
+        if let Some(ellipsis_width_f) = self.ellipsis_width_f {
+            let last = lines.len() - 1;
+            for (index, line) in lines.iter_mut().enumerate() {
+                let overflows = max_width.map_or(false, |v| line.width_f > v + LINE_FIT_EPSILON);
+                if overflows || (is_clamped && index == last) {
+                    *line = self.truncate_line(line, max_width, ellipsis_width_f);
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Keeps as many segments of a line as fit next to an ellipsis.
+    fn truncate_line(&self, line: &TextLine, max_width: Option<f32>, ellipsis_width_f: f32) -> TextLine {
+        let available = max_width.map_or(f32::INFINITY, |v| v - ellipsis_width_f + LINE_FIT_EPSILON);
+        let mut end = line.segments.start;
+        let mut width = 0.0;
+        let mut pending = 0.0;
+
+        for segment in line.segments.clone() {
+            let metrics = &self.segments[segment];
+            if pending + metrics.width_f > available {
+                break;
+            }
+            width = pending + metrics.width_f;
+            pending = width + metrics.trailing_width_f;
+            end = segment + 1;
+        }
+
+        TextLine {
+            segments: line.segments.start..end,
+            width_f: width + ellipsis_width_f,
+            ellipsis: true
+        }
+    }
+
+    /// Line height and ascent of the font a segment is shaped with.
+    pub fn segment_line_metrics(&self, segment: usize) -> (f32, f32) {
+        self.spans
+            .iter()
+            .find(|v| v.segments.start <= segment && segment < v.segments.end)
+            .map_or((self.line_height_f, self.ascent_f), |v| (v.line_height_f, v.ascent_f))
+    }
+
+    /// Height and ascent of a line holding the given segments, tall enough
+    /// for the largest fonts on it, with all of them sharing a baseline.
+    pub fn line_metrics(&self, segments: &Range<usize>) -> (f32, f32) {
+        if segments.start >= segments.end {
+            return (self.line_height_f, self.ascent_f);
+        }
+        let (ascent, descent) = segments.clone().fold((0.0, 0.0), |(ascent, descent), segment| {
+            let (line_height, segment_ascent) = self.segment_line_metrics(segment);
+            (f32::max(ascent, segment_ascent), f32::max(descent, line_height - segment_ascent))
+        });
+        (ascent + descent, ascent)
+    }
+
+    /// Distance from the top of the paragraph to the baseline of its first
+    /// line, when broken for the given width.
+    pub fn first_baseline(&self, max_width: Option<f32>) -> f32 {
+        let lines = self.break_lines(max_width);
+        self.line_metrics(&lines[0].segments).1
+    }
+
+    /// Returns the width of the widest line and the height of all lines.
+    pub fn measure(&self, max_width: Option<f32>) -> (f32, f32) {
+        let lines = self.break_lines(max_width);
+        let width = lines.iter().fold(0.0, |acc, line| f32::max(acc, line.width_f));
+        let height = if self.segments.is_empty() {
+            0.0
+        } else {
+            lines.iter().fold(0.0, |acc, line| acc + self.line_metrics(&line.segments).0)
+        };
+        (width, height)
+    }
+}
+
+/// Greedily packs segments into lines no wider than `max_width`, breaking
+/// only at hard breaks when there's no width constraint. Segments joined to
+/// the next one always end up on the same line.
+pub(crate) fn break_segments(segments: &[TextSegmentMetrics], max_width: Option<f32>) -> Vec<TextLine> {
+    let mut lines = vec![];
+    let mut start = 0;
+    let mut width = 0.0;
+    let mut pending = 0.0;
+    let mut index = 0;
+
+    while index < segments.len() {
+        let end = segments[index..]
+            .iter()
+            .position(|v| !v.joins_next)
+            .map_or(segments.len(), |v| index + v + 1);
+        let word = &segments[index..end];
+        let last = word[word.len() - 1];
+        let word_width = word[..word.len() - 1]
+            .iter()
+            .fold(last.width_f, |acc, v| acc + v.width_f + v.trailing_width_f);
+
+        if let Some(max_width) = max_width {
+            if index > start && pending + word_width > max_width + LINE_FIT_EPSILON {
+                lines.push(TextLine {
+                    segments: start..index,
+                    width_f: width,
+                    ellipsis: false
+                });
+                start = index;
+                pending = 0.0;
+            }
+        }
+
+        width = pending + word_width;
+        pending = width + last.trailing_width_f;
+
+        if last.hard_break {
+            lines.push(TextLine {
+                segments: start..end,
+                width_f: width,
+                ellipsis: false
+            });
+            start = end;
+            width = 0.0;
+            pending = 0.0;
+        }
+
+        index = end;
+    }
+
+    if start < segments.len() || lines.is_empty() {
+        lines.push(TextLine {
+            segments: start..segments.len(),
+            width_f: width,
+            ellipsis: false
+        });
+    }
+
+    lines
+}
+
+// Shaped widths are fractional, so allow for some rounding error when
+// re-breaking text for the width it was measured at.
+pub(crate) const LINE_FIT_EPSILON: f32 = 0.01;
+
+/// A piece of text between two line break opportunities, shaped on its own
+/// so that it can be placed on any line. Segments are also split wherever
+/// the bidi embedding level changes, so each one has a single direction.
+/// Right-to-left segments are shaped in visual order, since glyph stores
+/// only lay glyphs out from left to right. See `visual_text` for how their
+/// text is prepared for that.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextSegment<G> {
+    pub range: Range<usize>,
+    pub span: usize,
+    pub level: u8,
+    pub shaped_text: ShapedText<G>
+}
+
+impl<G> TextSegment<G> {
+    pub fn is_rtl(&self) -> bool {
+        is_rtl_level(self.level)
+    }
+}
+
+/// A paragraph split at UAX #14 line break opportunities, with embedding
+/// levels resolved by the UAX #9 bidirectional algorithm.
+/// See http://www.unicode.org/reports/tr14/
+/// and http://www.unicode.org/reports/tr9/
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextLayout<G> {
+    text: String,
+    segments: Vec<TextSegment<G>>,
+    paragraphs: Vec<(Range<usize>, u8)>,
+    ellipsis: ShapedText<G>,
+    metrics: Rc<TextMetrics>
+}
+
+impl<G> Default for TextLayout<G> {
+    fn default() -> Self {
+        TextLayout {
+            text: String::new(),
+            segments: vec![],
+            paragraphs: vec![],
+            ellipsis: ShapedText::default(),
+            metrics: Rc::new(TextMetrics::default())
+        }
+    }
+}
+
+impl<G> TextLayout<G>
+where
+    G: TGlyphStore
+{
+    pub fn new<F>(text: &str, direction: LayoutReflowDirection, white_space: LayoutWhiteSpace, shape: F) -> Self
+    where
+        F: Fn(&str) -> Option<G>
+    {
+        TextLayout::with_spans(&[text], direction, white_space, |_, text| shape(text))
+    }
+
+    /// Lays out a paragraph made of several spans of text, each shaped with
+    /// its own font by `shape(span, text)`, which only needs to lay glyphs
+    /// out from left to right. Each piece of text is shaped once, and a
+    /// span's line height is the one its font reports for a space. The base
+    /// direction of each paragraph is taken from its first strong character
+    /// when `direction` is `Inherit`. Whitespace is processed according to
+    /// `white_space` before anything is shaped.
+    ///
+    /// Bidi levels, and therefore the order of runs within lines, only ever
+    /// depend on `direction`. The direction boxes are laid out in isn't known
+    /// yet when text is measured, so it can't affect them.
+    pub fn with_spans<F>(spans: &[&str], direction: LayoutReflowDirection, white_space: LayoutWhiteSpace, shape: F) -> Self
+    where
+        F: Fn(usize, &str) -> Option<G>
+    {
+        if spans.is_empty() {
+            return TextLayout::default();
+        }
+
+        let texts = process_white_space(spans, white_space);
+        let text = texts.concat();
+        let span_ends = texts
+            .iter()
+            .scan(0, |end, v| {
+                *end += v.len();
+                Some(*end)
+            })
+            .collect::<Vec<_>>();
+        let span_at = |offset: usize| span_ends.iter().position(|end| offset < *end).unwrap_or(spans.len() - 1);
+        let spaces = (0..spans.len()).map(|span| ShapedText(shape(span, " "))).collect::<Vec<_>>();
+        let space_widths = spaces.iter().map(|v| v.width_f().unwrap_or(0.0)).collect::<Vec<_>>();
+        let line_heights = spaces.iter().map(|v| v.height_f().unwrap_or(0.0)).collect::<Vec<_>>();
+
+        let base_level = match direction {
+            LayoutReflowDirection::LTR => Some(Level::ltr()),
+            LayoutReflowDirection::RTL => Some(Level::rtl()),
+            LayoutReflowDirection::Inherit => None
+        };
+        let (levels, paragraphs) = {
+            let bidi_info = BidiInfo::new(&text, base_level);
+            let levels = bidi_info.levels.iter().map(|v| v.number()).collect::<Vec<_>>();
+            let paragraphs = bidi_info
+                .paragraphs
+                .iter()
+                .map(|v| (v.range.clone(), v.level.number()))
+                .collect::<Vec<_>>();
+            (levels, paragraphs)
+        };
+
+        let mut segments = vec![];
+        let mut metrics = vec![];
+        let mut start = 0;
+
+        for (end, hard_break) in LineBreakIterator::new(&text) {
+            if end <= start {
+                continue;
+            }
+
+            let content_end = start + text[start..end].trim_right().len();
+            let trailing_width_f = text[content_end..end]
+                .char_indices()
+                .filter(|&(_, c)| !is_newline(c))
+                .map(|(offset, _)| space_widths[span_at(content_end + offset)])
+                .sum::<f32>();
+
+            // Words split by span boundaries or direction changes get one
+            // segment for each part. Without wrapping, only hard breaks
+            // separate segments that can go on different lines.
+            let mut part_start = start;
+            loop {
+                let span = span_at(part_start);
+                let level = if part_start < content_end {
+                    levels[part_start]
+                } else {
+                    paragraph_level(&paragraphs, part_start)
+                };
+                let level_end = (part_start..content_end)
+                    .find(|v| levels[*v] != level)
+                    .unwrap_or(content_end);
+                let part_end = usize::min(usize::min(span_ends[span], content_end), level_end);
+                let is_last = part_end >= content_end;
+
+                let shaped_text = if part_start == part_end {
+                    ShapedText::default()
+                } else if is_rtl_level(level) {
+                    ShapedText(shape(span, &visual_text(&text, part_start..part_end)))
+                } else {
+                    ShapedText(shape(span, &text[part_start..part_end]))
+                };
+
+                metrics.push(TextSegmentMetrics {
+                    width_f: shaped_text.width_f().unwrap_or(0.0),
+                    trailing_width_f: if is_last { trailing_width_f } else { 0.0 },
+                    hard_break: is_last && hard_break && end < text.len(),
+                    joins_next: !is_last || !(hard_break || white_space.wraps())
+                });
+
+                segments.push(TextSegment {
+                    range: part_start..part_end,
+                    span,
+                    level,
+                    shaped_text
+                });
+
+                if is_last {
+                    break;
+                }
+                part_start = part_end;
+            }
+
+            start = end;
+        }
+
+        let span_metrics = line_heights
+            .iter()
+            .enumerate()
+            .map(|(span, &line_height_f)| {
+                // Segments are in span order, so each span's segments are contiguous.
+                let first = segments.iter().position(|v| v.span >= span).unwrap_or(segments.len());
+                let last = segments.iter().position(|v| v.span > span).unwrap_or(segments.len());
+                TextSpanMetrics {
+                    segments: first..last,
+                    line_height_f,
+                    ascent_f: line_height_f * DEFAULT_ASCENT_RATIO
+                }
+            })
+            .collect();
+
+        let first_char = text.chars().next();
+        let last_char = text.chars().next_back();
+
+        TextLayout {
+            text,
+            segments,
+            paragraphs,
+            ellipsis: ShapedText::default(),
+            metrics: Rc::new(TextMetrics {
+                segments: metrics,
+                spans: span_metrics,
+                line_height_f: line_heights[0],
+                ascent_f: line_heights[0] * DEFAULT_ASCENT_RATIO,
+                max_lines: None,
+                ellipsis_width_f: None,
+                first_char,
+                last_char
+            })
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn segments(&self) -> &[TextSegment<G>] {
+        &self.segments
+    }
+
+    pub fn metrics(&self) -> &Rc<TextMetrics> {
+        &self.metrics
+    }
+
+    /// The shaped ellipsis that lines cut short end with, if any.
+    pub fn ellipsis(&self) -> &ShapedText<G> {
+        &self.ellipsis
+    }
+
+    /// Limits this paragraph to `max_lines` lines, ending lines that don't
+    /// fit with the given shaped ellipsis, or cutting them off if there's
+    /// none.
+    pub fn set_overflow(&mut self, max_lines: Option<usize>, ellipsis: ShapedText<G>) {
+        {
+            let metrics = Rc::make_mut(&mut self.metrics);
+            metrics.max_lines = max_lines;
+            metrics.ellipsis_width_f = ellipsis.width_f();
+        }
+        self.ellipsis = ellipsis;
+    }
+
+    pub fn has_different_layout(&self, other: &TextLayout<G>) -> bool {
+        self.metrics != other.metrics || self.segments.iter().map(|v| v.level).ne(other.segments.iter().map(|v| v.level))
+    }
+
+    /// Whether the paragraph containing the given byte offset of the text
+    /// has a right-to-left base direction.
+    pub fn is_rtl_at(&self, offset: usize) -> bool {
+        is_rtl_level(paragraph_level(&self.paragraphs, offset))
+    }
+
+    /// Places lines this paragraph was broken into for the given content
+    /// box, in the same coordinate space as the content box. Lines of
+    /// right-to-left paragraphs start at the right edge of the content box.
+    pub fn line_boxes(&self, content_box: &LayoutBoundingClientRectF, lines: &[TextLine]) -> Vec<LayoutLineBox> {
+        let metrics = &self.metrics;
+        let origin = content_box.position;
+        let glyph_offsets = self.glyph_offsets();
+
+        let mut top = origin.top;
+        lines
+            .iter()
+            .map(|line| {
+                let (line_height_f, ascent_f) = metrics.line_metrics(&line.segments);
+                let offset = self.segments.get(line.segments.start).map_or(0, |v| v.range.start);
+                let left = if self.is_rtl_at(offset) {
+                    origin.left + content_box.size.width - line.width_f
+                } else {
+                    origin.left
+                };
+                let rect = LayoutBoundingClientRectF::new(left, top, line.width_f, line_height_f);
+                let line_box = self.line_box(&glyph_offsets, line.segments.clone(), line.ellipsis, rect, top + ascent_f);
+                top += line_height_f;
+                line_box
+            })
+            .collect()
+    }
+
+    /// Lines of a paragraph that was laid out inline, one for each fragment
+    /// up to `max_lines`. When fragments were dropped, the last line keeps
+    /// as many segments as fit in its fragment next to the ellipsis.
+    pub fn fragment_lines(&self, fragments: &[LayoutTextFragment]) -> Vec<TextLine> {
+        let metrics = &self.metrics;
+        let count = metrics
+            .max_lines
+            .map_or(fragments.len(), |v| usize::min(usize::max(v, 1), fragments.len()));
+        let is_clamped = count < fragments.len();
+
+        fragments[..count]
+            .iter()
+            .enumerate()
+            .map(|(index, fragment)| {
+                let line = TextLine {
+                    segments: fragment.segments.clone(),
+                    width_f: fragment.rect.size.width,
+                    ellipsis: false
+                };
+                match metrics.ellipsis_width_f {
+                    Some(ellipsis_width_f) if is_clamped && index + 1 == count => {
+                        metrics.truncate_line(&line, Some(fragment.rect.size.width), ellipsis_width_f)
+                    }
+                    _ => line
+                }
+            })
+            .collect()
+    }
+
+    /// Line boxes for a paragraph that was laid out inline, for the lines
+    /// `fragment_lines` keeps, with fragments relative to `origin`.
+    pub fn fragment_line_boxes(&self, origin: LayoutClientPositionF, fragments: &[LayoutTextFragment]) -> Vec<LayoutLineBox> {
+        let glyph_offsets = self.glyph_offsets();
+
+        self.fragment_lines(fragments)
+            .into_iter()
+            .zip(fragments)
+            .map(|(line, fragment)| {
+                let mut rect = fragment.rect + origin;
+                rect.size.width = line.width_f;
+                self.line_box(&glyph_offsets, line.segments, line.ellipsis, rect, origin.top + fragment.baseline_f)
+            })
+            .collect()
+    }
+
+    fn glyph_offsets(&self) -> Vec<usize> {
+        let mut glyph_offsets = Vec::with_capacity(self.segments.len() + 1);
+        glyph_offsets.push(0);
+        for segment in &self.segments {
+            let previous = glyph_offsets[glyph_offsets.len() - 1];
+            glyph_offsets.push(previous + segment.shaped_text.glyphs().len());
+        }
+        glyph_offsets
+    }
+
+    fn line_box(
+        &self,
+        glyph_offsets: &[usize],
+        segments: Range<usize>,
+        ellipsis: bool,
+        rect: LayoutBoundingClientRectF,
+        baseline_f: f32
+    ) -> LayoutLineBox {
+        let metrics = &self.metrics;
+        let levels = segments.clone().map(|v| self.segments[v].level).collect::<Vec<_>>();
+
+        // The ellipsis goes at the end of the line, which is on the left for
+        // right-to-left paragraphs.
+        let ellipsis_width_f = if ellipsis { self.ellipsis.width_f().unwrap_or(0.0) } else { 0.0 };
+        let offset = self.segments.get(segments.start).map_or(0, |v| v.range.start);
+        let is_rtl_paragraph = self.is_rtl_at(offset);
+        let ellipsis_ascent_f = metrics.spans.last().map_or(metrics.ascent_f, |v| v.ascent_f);
+
+        // Runs are placed in visual order. Runs in smaller fonts are moved
+        // down so that baselines line up. Whitespace trailing a right-to-left
+        // run goes on its left, and the whitespace at the end of the line
+        // hangs outside of it.
+        let mut pen = rect.position.left;
+        if is_rtl_paragraph {
+            pen += ellipsis_width_f;
+        }
+        let runs = visual_order(&levels)
+            .into_iter()
+            .map(|index| {
+                let segment = segments.start + index;
+                let (_, ascent_f) = metrics.segment_line_metrics(segment);
+                let width_f = metrics.segments[segment].width_f;
+                let trailing_width_f = if segment + 1 == segments.end {
+                    0.0
+                } else {
+                    metrics.segments[segment].trailing_width_f
+                };
+                let is_rtl = is_rtl_level(levels[index]);
+                let left = if is_rtl { pen + trailing_width_f } else { pen };
+                let run = LayoutGlyphRun {
+                    segment,
+                    glyph_range: glyph_offsets[segment]..glyph_offsets[segment + 1],
+                    origin: (left, baseline_f - ascent_f),
+                    is_rtl
+                };
+                pen += width_f + trailing_width_f;
+                run
+            })
+            .collect();
+
+        let ellipsis_origin = if !ellipsis {
+            None
+        } else if is_rtl_paragraph {
+            Some((rect.position.left, baseline_f - ellipsis_ascent_f))
+        } else {
+            Some((pen, baseline_f - ellipsis_ascent_f))
+        };
+
+        LayoutLineBox {
+            rect,
+            baseline_f,
+            glyph_range: glyph_offsets[segments.start]..glyph_offsets[segments.end],
+            segments,
+            runs,
+            ellipsis_origin
+        }
+    }
+}
+
+/// A shaped segment placed on a line. Glyph positions in the segment's
+/// `ShapedText` are relative to `origin`, and already in visual order for
+/// right-to-left runs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutGlyphRun {
+    pub segment: usize,
+    pub glyph_range: Range<usize>,
+    pub origin: (f32, f32),
+    pub is_rtl: bool
+}
+
+impl LayoutGlyphRun {
+    /// Converts a glyph position relative to this run into the coordinate
+    /// space of the line box.
+    pub fn glyph_origin(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.origin.0 + x, self.origin.1 + y)
+    }
+}
+
+/// A single line of a paragraph. Glyph ranges index into the glyphs of all
+/// segments, taken in logical order. Runs are in visual order. Lines cut
+/// short end with the paragraph's ellipsis, drawn at `ellipsis_origin`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutLineBox {
+    pub rect: LayoutBoundingClientRectF,
+    pub baseline_f: f32,
+    pub glyph_range: Range<usize>,
+    pub segments: Range<usize>,
+    pub runs: Vec<LayoutGlyphRun>,
+    pub ellipsis_origin: Option<(f32, f32)>
+}
+
+/// A node's text as renderers draw it: a paragraph shaped into runs of
+/// glyphs, one for each part of a span between break opportunities, and
+/// the line boxes these runs were placed on when the last reflow was
+/// committed, relative to the node's border box. Renderers only draw the
+/// runs on line boxes, and the ellipsis where a line box has one, so text
+/// cut off by a line clamp or an ellipsis is never drawn.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutShapedText<G> {
+    layout: TextLayout<G>,
+    lines: Vec<TextLine>,
+    line_boxes: Vec<LayoutLineBox>
+}
+
+impl<G> Default for LayoutShapedText<G> {
+    fn default() -> Self {
+        LayoutShapedText {
+            layout: TextLayout::default(),
+            lines: vec![],
+            line_boxes: vec![]
+        }
+    }
+}
+
+impl<G> LayoutShapedText<G>
+where
+    G: TGlyphStore
+{
+    pub fn layout(&self) -> &TextLayout<G> {
+        &self.layout
+    }
+
+    pub fn lines(&self) -> &[TextLine] {
+        &self.lines
+    }
+
+    pub fn line_boxes(&self) -> &[LayoutLineBox] {
+        &self.line_boxes
+    }
+
+    /// The shaped segment a run on one of the line boxes draws, with its
+    /// font and glyphs positioned relative to the run's origin. The span it
+    /// belongs to is the segment's span.
+    pub fn run_text(&self, run: &LayoutGlyphRun) -> &ShapedText<G> {
+        &self.layout.segments[run.segment].shaped_text
+    }
+
+    /// The shaped ellipsis drawn at the `ellipsis_origin` of line boxes that
+    /// were cut short.
+    pub fn ellipsis(&self) -> &ShapedText<G> {
+        &self.layout.ellipsis
+    }
+
+    /// Replaces the paragraph, dropping lines broken for the old one.
+    pub(crate) fn set_layout(&mut self, layout: TextLayout<G>) {
+        self.layout = layout;
+        self.lines.clear();
+        self.line_boxes.clear();
+    }
+
+    pub(crate) fn set_lines(&mut self, lines: Vec<TextLine>, line_boxes: Vec<LayoutLineBox>) {
+        self.lines = lines;
+        self.line_boxes = line_boxes;
+    }
+}
+
+impl<G> TShapedText for LayoutShapedText<G>
+where
+    G: TGlyphStore
+{
+}
+
+/// Whether UAX #14 allows a line break between two adjacent characters.
+/// Only the pair itself is looked at, so rules that depend on the text
+/// further away are approximated.
+pub(crate) fn has_break_opportunity(before: char, after: char) -> bool {
+    let text = format!("{}{}", before, after);
+    LineBreakIterator::new(&text).any(|(offset, _)| offset == before.len_utf8())
+}
+
+#[inline]
+fn is_newline(c: char) -> bool {
+    match c {
+        '\n' | '\r' | '\u{000B}' | '\u{000C}' | '\u{0085}' | '\u{2028}' | '\u{2029}' => true,
+        _ => false
+    }
+}
+
+#[inline]
+fn is_rtl_level(level: u8) -> bool {
+    level % 2 == 1
+}
+
+fn paragraph_level(paragraphs: &[(Range<usize>, u8)], offset: usize) -> u8 {
+    paragraphs
+        .iter()
+        .find(|v| offset < v.0.end)
+        .or_else(|| paragraphs.last())
+        .map_or(0, |v| v.1)
+}
+
+/// Indices of a line's runs in visual order, given their embedding levels
+/// in logical order, following rule L2 of UAX #9: from the highest level
+/// down to the lowest odd one, every sequence of runs at that level or
+/// higher is reversed.
+fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order = (0..levels.len()).collect::<Vec<_>>();
+    let highest = levels.iter().cloned().max().unwrap_or(0);
+    let lowest_odd = levels.iter().cloned().min().unwrap_or(0) | 1;
+
+    let mut level = highest;
+    while level >= lowest_odd {
+        let mut index = 0;
+        while index < order.len() {
+            if levels[order[index]] < level {
+                index += 1;
+                continue;
+            }
+            let end = order[index..]
+                .iter()
+                .position(|v| levels[*v] < level)
+                .map_or(order.len(), |v| index + v);
+            order[index..end].reverse();
+            index = end;
+        }
+        level -= 1;
+    }
+
+    order
+}
+
+/// The right-to-left text in a range of a paragraph, in the order its glyphs
+/// are drawn from left to right. Arabic letters are first replaced by the
+/// presentation forms they take when joined to their neighbours, looking
+/// past the range too, so that words split into several segments still
+/// join. Characters are then reversed one cluster at a time, keeping marks
+/// after their base, and paired punctuation is mirrored as required by rule
+/// L4 of UAX #9.
+fn visual_text(text: &str, range: Range<usize>) -> String {
+    let chars = text[range.clone()].chars().collect::<Vec<_>>();
+    let before = text[..range.start].chars().rev().find(|c| !is_mark(*c));
+    let after = text[range.end..].chars().find(|c| !is_mark(*c));
+
+    let mut clusters: Vec<String> = vec![];
+    for c in arabic_forms(&chars, before, after) {
+        if is_mark(c) && !clusters.is_empty() {
+            let last = clusters.len() - 1;
+            clusters[last].push(c);
+        } else {
+            clusters.push(mirrored_char(c).to_string());
+        }
+    }
+    clusters.into_iter().rev().collect()
+}
+
+const ARABIC_TATWEEL: char = '\u{0640}';
+const ARABIC_LAM: char = '\u{0644}';
+
+// Arabic letters with presentation forms, whether they join on both sides
+// rather than only to the letter before them, and their isolated form. The
+// final form follows, then the initial and medial ones for letters joining
+// on both sides.
+const ARABIC_FORMS: &[(char, bool, u32)] = &[
+    ('\u{0622}', false, 0xFE81),
+    ('\u{0623}', false, 0xFE83),
+    ('\u{0624}', false, 0xFE85),
+    ('\u{0625}', false, 0xFE87),
+    ('\u{0626}', true, 0xFE89),
+    ('\u{0627}', false, 0xFE8D),
+    ('\u{0628}', true, 0xFE8F),
+    ('\u{0629}', false, 0xFE93),
+    ('\u{062A}', true, 0xFE95),
+    ('\u{062B}', true, 0xFE99),
+    ('\u{062C}', true, 0xFE9D),
+    ('\u{062D}', true, 0xFEA1),
+    ('\u{062E}', true, 0xFEA5),
+    ('\u{062F}', false, 0xFEA9),
+    ('\u{0630}', false, 0xFEAB),
+    ('\u{0631}', false, 0xFEAD),
+    ('\u{0632}', false, 0xFEAF),
+    ('\u{0633}', true, 0xFEB1),
+    ('\u{0634}', true, 0xFEB5),
+    ('\u{0635}', true, 0xFEB9),
+    ('\u{0636}', true, 0xFEBD),
+    ('\u{0637}', true, 0xFEC1),
+    ('\u{0638}', true, 0xFEC5),
+    ('\u{0639}', true, 0xFEC9),
+    ('\u{063A}', true, 0xFECD),
+    ('\u{0641}', true, 0xFED1),
+    ('\u{0642}', true, 0xFED5),
+    ('\u{0643}', true, 0xFED9),
+    ('\u{0644}', true, 0xFEDD),
+    ('\u{0645}', true, 0xFEE1),
+    ('\u{0646}', true, 0xFEE5),
+    ('\u{0647}', true, 0xFEE9),
+    ('\u{0648}', false, 0xFEED),
+    ('\u{0649}', false, 0xFEEF),
+    ('\u{064A}', true, 0xFEF1)
+];
+
+/// Replaces Arabic letters in logical order by their contextual forms, given
+/// the letters around them. Lam followed by alef becomes a single ligature.
+/// Marks don't interrupt joining.
+fn arabic_forms(chars: &[char], before: Option<char>, after: Option<char>) -> Vec<char> {
+    let mut forms = Vec::with_capacity(chars.len());
+    let mut previous = before;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        if is_mark(c) {
+            forms.push(c);
+            index += 1;
+            continue;
+        }
+
+        let next_index = chars[index + 1..].iter().position(|v| !is_mark(*v)).map(|v| index + 1 + v);
+        let next = next_index.map_or(after, |v| Some(chars[v]));
+        let joins_previous = previous.map_or(false, joins_next_letter) && joins_previous_letter(c);
+
+        if let Some((alef_index, ligature)) = next_index.and_then(|v| lam_alef_ligature(c, chars[v]).map(|form| (v, form))) {
+            // Marks on the lam stay with the ligature, and the ligature
+            // never joins the next letter.
+            forms.push(char::from_u32(ligature + joins_previous as u32).unwrap_or(c));
+            forms.extend(chars[index + 1..alef_index].iter().cloned());
+            previous = Some(chars[alef_index]);
+            index = alef_index + 1;
+            continue;
+        }
+
+        let form = match arabic_letter(c) {
+            Some((dual, isolated)) => {
+                let joins_next = dual && next.map_or(false, joins_previous_letter);
+                let offset = match (joins_previous, joins_next) {
+                    (false, false) => 0,
+                    (true, false) => 1,
+                    (false, true) => 2,
+                    (true, true) => 3
+                };
+                char::from_u32(isolated + offset).unwrap_or(c)
+            }
+            None => c
+        };
+
+        forms.push(form);
+        previous = Some(c);
+        index += 1;
+    }
+
+    forms
+}
+
+#[inline]
+fn arabic_letter(c: char) -> Option<(bool, u32)> {
+    ARABIC_FORMS.iter().find(|v| v.0 == c).map(|v| (v.1, v.2))
+}
+
+#[inline]
+fn joins_previous_letter(c: char) -> bool {
+    c == ARABIC_TATWEEL || arabic_letter(c).is_some()
+}
+
+#[inline]
+fn joins_next_letter(c: char) -> bool {
+    c == ARABIC_TATWEEL || arabic_letter(c).map_or(false, |v| v.0)
+}
+
+/// The isolated form of the ligature a lam makes with the alef after it, if
+/// any. Its final form follows.
+#[inline]
+fn lam_alef_ligature(lam: char, alef: char) -> Option<u32> {
+    if lam != ARABIC_LAM {
+        return None;
+    }
+    match alef {
+        '\u{0622}' => Some(0xFEF5),
+        '\u{0623}' => Some(0xFEF7),
+        '\u{0625}' => Some(0xFEF9),
+        '\u{0627}' => Some(0xFEFB),
+        _ => None
+    }
+}
+
+/// Nonspacing marks, which are drawn over or under the character before
+/// them.
+#[inline]
+fn is_mark(c: char) -> bool {
+    bidi_class(c) == BidiClass::NSM
+}
+
+#[inline]
+fn mirrored_char(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '\u{00AB}' => '\u{00BB}',
+        '\u{00BB}' => '\u{00AB}',
+        '\u{2039}' => '\u{203A}',
+        '\u{203A}' => '\u{2039}',
+        _ => c
+    }
+}
@@ -0,0 +1,75 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::fmt;
+use std::hash::Hash;
+
+use types::{LayoutBoundingClientRect, LayoutBoundingClientRectF, LayoutClientPosition, LayoutClientPositionF, LayoutRectChange};
+
+/// A tree of layout nodes, usually backed by a DOM tree. Algorithms that need
+/// to look at more than a single node at a time, like hit testing, work
+/// on top of this.
+pub trait TLayoutTree {
+    type NodeId: Copy + Eq + Hash + fmt::Debug;
+    type LayoutNode: TLayoutTreeNode;
+
+    fn root(&self) -> Self::NodeId;
+
+    fn get_layout_node(&self, id: Self::NodeId) -> Option<&Self::LayoutNode>;
+
+    /// Children of a node, in paint order.
+    fn get_children(&self, id: Self::NodeId) -> Vec<Self::NodeId>;
+}
+
+pub trait TLayoutTreeMut: TLayoutTree {
+    fn get_layout_node_mut(&mut self, id: Self::NodeId) -> Option<&mut Self::LayoutNode>;
+}
+
+pub trait TLayoutTreeNode {
+    fn global_rect(&self) -> LayoutBoundingClientRect;
+
+    fn global_rect_f(&self) -> LayoutBoundingClientRectF;
+
+    /// Whether this node and all of its descendants should be skipped.
+    fn is_hidden(&self) -> bool;
+
+    /// Whether descendants are clipped to this node's bounds.
+    fn clips_overflow(&self) -> bool;
+
+    /// How far this node's contents are scrolled. Descendants are painted
+    /// this much up and to the left of their global rects.
+    fn scroll_offset(&self) -> LayoutClientPosition;
+
+    /// Global position of the parent's border box, both snapped and not,
+    /// which is what this node's local rect is relative to. This commits
+    /// the results of a reflow, like `set_computed_client_position` does,
+    /// which only has the snapped position to go by.
+    fn set_computed_client_positions(&mut self, computed: LayoutClientPosition, computed_f: LayoutClientPositionF);
+
+    /// How the local or global rect changed since the last time this was
+    /// called, if it did. Layout results are committed, and the tainted flag
+    /// cleared, when a node's position is set after a reflow.
+    fn take_rect_change(&mut self) -> Option<LayoutRectChange>;
+
+    /// Whether this node was tainted going into any of the reflows committed
+    /// since the last time this was called.
+    fn take_tainted_reflow(&mut self) -> bool;
+
+    /// Global rects of children removed since the last reflow, as they were
+    /// when last committed. Overflowing descendants of removed children
+    /// aren't included.
+    fn take_removed_rects(&mut self) -> Vec<LayoutBoundingClientRect>;
+
+    /// Applies this node's user agent styles again, underneath its custom
+    /// styles, for when the table they come from changed. Returns whether
+    /// any style changed, in which case the node is tainted.
+    fn refresh_user_agent_styles(&mut self) -> bool;
+}
@@ -10,9 +10,12 @@ specific language governing permissions and limitations under the License.
 */
 
 use std::convert::TryInto;
+use std::f32;
 use std::fmt;
 use std::marker::PhantomData;
-use std::ops::{Add, AddAssign, Deref, DerefMut};
+use std::mem;
+use std::ops::{Add, AddAssign, Deref, DerefMut, Sub};
+use std::rc::Rc;
 
 use rsx_shared::consts::DEFAULT_FONT_SIZE;
 use rsx_shared::traits::{
@@ -32,9 +35,29 @@ use rsx_shared::traits::{
     TStyleDeclarations
 };
 use rsx_shared::types::KnownElementName;
-use yoga;
 
-pub use yoga::Direction as LayoutReflowDirection;
+use container::{LayoutContainer, LayoutContainerItem};
+use engine::{DefaultLayoutEngine, LayoutMeasureContext, TLayoutEngine};
+use flow::LayoutFlowDisplay;
+use grid::{LayoutGrid, LayoutGridPlacement};
+use measure::LayoutMeasureFunc;
+use object_fit::{LayoutImagePlacement, LayoutObjectFit, LayoutObjectPosition};
+use styles::{LayoutDisplay, LayoutOverflow, LayoutStyle, LayoutStyleSnapshot};
+use text_layout::{
+    LayoutLineBox,
+    LayoutShapedText,
+    LayoutTextFragment,
+    LayoutTextOverflow,
+    LayoutTextSpan,
+    LayoutWhiteSpace,
+    TextLayout,
+    TextLine,
+    LAYOUT_TEXT_ELLIPSIS
+};
+use traits::TLayoutTreeNode;
+use user_agent::LayoutUserAgentStyles;
+
+pub use styles::LayoutReflowDirection;
 
 #[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct LayoutBoundingClientRect {
@@ -43,7 +66,7 @@ pub struct LayoutBoundingClientRect {
 }
 
 impl LayoutBoundingClientRect {
-    pub fn new(left: u32, top: u32, width: u32, height: u32) -> Self {
+    pub fn new(left: i32, top: i32, width: u32, height: u32) -> Self {
         LayoutBoundingClientRect {
             position: LayoutClientPosition { left, top },
             size: LayoutClientSize { width, height }
@@ -59,6 +82,81 @@ impl LayoutBoundingClientRect {
         self.size = LayoutClientSize::default();
         self
     }
+
+    /// Like `contains_point`, but for signed points. Points on the top and
+    /// left edges are inside the rect, points on the bottom and right edges
+    /// are outside, so that adjacent rects never both contain a point.
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (left, top) = (i64::from(self.position.left), i64::from(self.position.top));
+        let (width, height) = (i64::from(self.size.width), i64::from(self.size.height));
+        left <= x && top <= y && left + width > x && top + height > y
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    pub fn right(&self) -> i64 {
+        i64::from(self.position.left) + i64::from(self.size.width)
+    }
+
+    pub fn bottom(&self) -> i64 {
+        i64::from(self.position.top) + i64::from(self.size.height)
+    }
+
+    pub fn intersects(&self, other: &LayoutBoundingClientRect) -> bool {
+        !self.is_empty() && !other.is_empty() && i64::from(self.position.left) < other.right()
+            && i64::from(other.position.left) < self.right() && i64::from(self.position.top) < other.bottom()
+            && i64::from(other.position.top) < self.bottom()
+    }
+
+    pub fn intersection(&self, other: &LayoutBoundingClientRect) -> Option<LayoutBoundingClientRect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let left = i64::max(i64::from(self.position.left), i64::from(other.position.left));
+        let top = i64::max(i64::from(self.position.top), i64::from(other.position.top));
+        let right = i64::min(self.right(), other.right());
+        let bottom = i64::min(self.bottom(), other.bottom());
+        Some(LayoutBoundingClientRect::new(
+            left as i32,
+            top as i32,
+            (right - left) as u32,
+            (bottom - top) as u32
+        ))
+    }
+
+    pub fn union(&self, other: &LayoutBoundingClientRect) -> LayoutBoundingClientRect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let left = i64::min(i64::from(self.position.left), i64::from(other.position.left));
+        let top = i64::min(i64::from(self.position.top), i64::from(other.position.top));
+        let right = i64::max(self.right(), other.right());
+        let bottom = i64::max(self.bottom(), other.bottom());
+        LayoutBoundingClientRect::new(left as i32, top as i32, (right - left) as u32, (bottom - top) as u32)
+    }
+
+    /// Converts a point from the space this rect is positioned in to the
+    /// space relative to this rect's origin.
+    pub fn local_from_global(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x - self.position.left, y - self.position.top)
+    }
+
+    /// Converts a point relative to this rect's origin to the space this
+    /// rect is positioned in. This is the inverse of `local_from_global`.
+    pub fn global_from_local(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x + self.position.left, y + self.position.top)
+    }
+
+    /// Like `local_from_global`, for unsigned points of any size.
+    fn local_from_page(&self, (x, y): (u32, u32)) -> (i64, i64) {
+        (i64::from(x) - i64::from(self.position.left), i64::from(y) - i64::from(self.position.top))
+    }
 }
 
 impl Add<LayoutClientPosition> for LayoutBoundingClientRect {
@@ -78,6 +176,16 @@ impl AddAssign<LayoutClientPosition> for LayoutBoundingClientRect {
     }
 }
 
+impl Sub<LayoutClientPosition> for LayoutBoundingClientRect {
+    type Output = Self;
+
+    fn sub(self, rhs: LayoutClientPosition) -> Self::Output {
+        let left = self.position.left - rhs.left;
+        let top = self.position.top - rhs.top;
+        LayoutBoundingClientRect::new(left, top, self.size.width, self.size.height)
+    }
+}
+
 impl TClientRect for LayoutBoundingClientRect {
     type Position = LayoutClientPosition;
     type Size = LayoutClientSize;
@@ -90,28 +198,232 @@ impl TClientRect for LayoutBoundingClientRect {
         self.size
     }
 
-    fn offset_from_page(&self, (x, y): (u32, u32)) -> (u32, u32) {
-        // TODO: Properly handle paddings.
-        // See https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/offsetX
-        // See https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/offsetY
-        (self.position.left + x, self.position.top + y)
+    fn offset_from_page(&self, point: (u32, u32)) -> (u32, u32) {
+        // Rects don't know about borders, so this is relative to the border edge.
+        // Use `LayoutNode::offset_from_page` for offsets relative to the padding edge.
+        // Offsets are signed, like a mouse event's: points above or left of
+        // this rect have negative offsets, returned as their two's complement
+        // so that casting them to `i32` gives them back.
+        let (left, top) = self.local_from_page(point);
+        let signed = |v: i64| i64::min(i64::max(v, i64::from(i32::min_value())), i64::from(i32::max_value())) as i32;
+        (signed(left) as u32, signed(top) as u32)
     }
 
-    fn client_from_page(&self, (x, y): (u32, u32)) -> (u32, u32) {
-        (self.position.left + x, self.position.top + y)
+    fn client_from_page(&self, point: (u32, u32)) -> (u32, u32) {
+        // Points above or left of this rect can't be represented unsigned,
+        // so they're clamped. Use `local_from_global` for signed results.
+        let (left, top) = self.local_from_page(point);
+        let unsigned = |v: i64| i64::min(i64::max(v, 0), i64::from(u32::max_value())) as u32;
+        (unsigned(left), unsigned(top))
     }
 
     fn contains_point(&self, (x, y): (u32, u32)) -> bool {
-        let p = self.position;
-        let s = self.size;
-        p.left < x && p.top < y && p.left + s.width > x && p.top + s.height > y
+        if x > i32::max_value() as u32 || y > i32::max_value() as u32 {
+            return false;
+        }
+        self.contains((x as i32, y as i32))
     }
 }
 
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutBoundingClientRectF {
+    pub position: LayoutClientPositionF,
+    pub size: LayoutClientSizeF
+}
+
+impl LayoutBoundingClientRectF {
+    pub fn new(left: f32, top: f32, width: f32, height: f32) -> Self {
+        LayoutBoundingClientRectF {
+            position: LayoutClientPositionF { left, top },
+            size: LayoutClientSizeF { width, height }
+        }
+    }
+
+    pub fn zero_position(mut self) -> Self {
+        self.position = LayoutClientPositionF::default();
+        self
+    }
+
+    pub fn zero_size(mut self) -> Self {
+        self.size = LayoutClientSizeF::default();
+        self
+    }
+
+    pub fn snap(&self, snapping: LayoutPixelSnapping) -> LayoutBoundingClientRect {
+        snapping.snap_rect(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.size.width > 0.0 && self.size.height > 0.0)
+    }
+
+    pub fn intersection(&self, other: &LayoutBoundingClientRectF) -> Option<LayoutBoundingClientRectF> {
+        let left = f32::max(self.position.left, other.position.left);
+        let top = f32::max(self.position.top, other.position.top);
+        let right = f32::min(self.position.left + self.size.width, other.position.left + other.size.width);
+        let bottom = f32::min(self.position.top + self.size.height, other.position.top + other.size.height);
+        let rect = LayoutBoundingClientRectF::new(left, top, right - left, bottom - top);
+        if rect.is_empty() {
+            None
+        } else {
+            Some(rect)
+        }
+    }
+
+    pub fn local_from_global(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (x - self.position.left, y - self.position.top)
+    }
+
+    pub fn global_from_local(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (x + self.position.left, y + self.position.top)
+    }
+
+    pub fn inflate(&self, edges: &LayoutBoxEdgesF) -> Self {
+        LayoutBoundingClientRectF::new(
+            self.position.left - edges.left,
+            self.position.top - edges.top,
+            f32::max(self.size.width + edges.left + edges.right, 0.0),
+            f32::max(self.size.height + edges.top + edges.bottom, 0.0)
+        )
+    }
+
+    pub fn deflate(&self, edges: &LayoutBoxEdgesF) -> Self {
+        LayoutBoundingClientRectF::new(
+            self.position.left + edges.left,
+            self.position.top + edges.top,
+            f32::max(self.size.width - edges.left - edges.right, 0.0),
+            f32::max(self.size.height - edges.top - edges.bottom, 0.0)
+        )
+    }
+}
+
+impl Add<LayoutClientPositionF> for LayoutBoundingClientRectF {
+    type Output = Self;
+
+    fn add(self, rhs: LayoutClientPositionF) -> Self::Output {
+        let left = self.position.left + rhs.left;
+        let top = self.position.top + rhs.top;
+        LayoutBoundingClientRectF::new(left, top, self.size.width, self.size.height)
+    }
+}
+
+impl AddAssign<LayoutClientPositionF> for LayoutBoundingClientRectF {
+    fn add_assign(&mut self, rhs: LayoutClientPositionF) {
+        self.position.left = self.position.left + rhs.left;
+        self.position.top = self.position.top + rhs.top;
+    }
+}
+
+/// Where a grid or flow container places one of its children, as a margin
+/// box relative to the container's content box. Without a height, the
+/// child is sized to fit its content vertically.
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutPlacedArea {
+    pub position: LayoutClientPositionF,
+    pub width: f32,
+    pub height: Option<f32>
+}
+
+impl LayoutPlacedArea {
+    pub fn new(position: LayoutClientPositionF, width: f32, height: Option<f32>) -> Self {
+        LayoutPlacedArea {
+            position,
+            width,
+            height
+        }
+    }
+}
+
+/// Where a node's border box was before the reflows that moved or resized
+/// it since changes were last taken, and where it is now. Nodes laid out
+/// for the first time don't have a previous rect.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayoutRectChange {
+    pub previous: Option<LayoutBoundingClientRect>,
+    pub current: LayoutBoundingClientRect
+}
+
+/// The visible part of the page. Page coordinates are relative to the
+/// document origin, which is also the origin of the layout root, so they
+/// are the same as global coordinates. Client coordinates are relative
+/// to the top left corner of the viewport.
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutViewport {
+    pub scroll_offset: LayoutClientPosition,
+    pub size: LayoutClientSize
+}
+
+impl LayoutViewport {
+    pub fn new(scroll_offset: LayoutClientPosition, size: LayoutClientSize) -> Self {
+        LayoutViewport {
+            scroll_offset,
+            size
+        }
+    }
+
+    pub fn client_from_page(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x - self.scroll_offset.left, y - self.scroll_offset.top)
+    }
+
+    pub fn page_from_client(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x + self.scroll_offset.left, y + self.scroll_offset.top)
+    }
+
+    pub fn get_page_rect(&self) -> LayoutBoundingClientRect {
+        LayoutBoundingClientRect {
+            position: self.scroll_offset,
+            size: self.size
+        }
+    }
+}
+
+/// Computed thickness of each side of a margin, border or padding area.
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutBoxEdgesF {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32
+}
+
+impl LayoutBoxEdgesF {
+    pub fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        LayoutBoxEdgesF {
+            top,
+            right,
+            bottom,
+            left
+        }
+    }
+}
+
+impl Add for LayoutBoxEdgesF {
+    type Output = Self;
+
+    fn add(self, rhs: LayoutBoxEdgesF) -> Self::Output {
+        LayoutBoxEdgesF::new(
+            self.top + rhs.top,
+            self.right + rhs.right,
+            self.bottom + rhs.bottom,
+            self.left + rhs.left
+        )
+    }
+}
+
+/// The CSS box areas of a layout node.
+/// See https://developer.mozilla.org/en-US/docs/Web/CSS/CSS_Box_Model/Introduction_to_the_CSS_box_model
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutBoxKind {
+    Margin,
+    Border,
+    Padding,
+    Content
+}
+
 #[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct LayoutClientPosition {
-    pub left: u32,
-    pub top: u32
+    pub left: i32,
+    pub top: i32
 }
 
 impl Add for LayoutClientPosition {
@@ -133,6 +445,38 @@ impl AddAssign for LayoutClientPosition {
 
 impl TClientPosition for LayoutClientPosition {}
 
+impl From<LayoutClientPosition> for LayoutClientPositionF {
+    fn from(value: LayoutClientPosition) -> Self {
+        LayoutClientPositionF {
+            left: value.left as f32,
+            top: value.top as f32
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutClientPositionF {
+    pub left: f32,
+    pub top: f32
+}
+
+impl Add for LayoutClientPositionF {
+    type Output = Self;
+
+    fn add(self, rhs: LayoutClientPositionF) -> Self::Output {
+        let left = self.left + rhs.left;
+        let top = self.top + rhs.top;
+        LayoutClientPositionF { left, top }
+    }
+}
+
+impl AddAssign for LayoutClientPositionF {
+    fn add_assign(&mut self, rhs: LayoutClientPositionF) {
+        self.left = self.left + rhs.left;
+        self.top = self.top + rhs.top;
+    }
+}
+
 #[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct LayoutClientSize {
     pub width: u32,
@@ -158,16 +502,98 @@ impl AddAssign for LayoutClientSize {
 
 impl TClientSize for LayoutClientSize {}
 
-#[derive(Debug, PartialEq)]
-pub struct ImageNodeContext {
-    width: u32,
-    height: u32
+#[derive(Debug, PartialEq, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct LayoutClientSizeF {
+    pub width: f32,
+    pub height: f32
+}
+
+impl Add for LayoutClientSizeF {
+    type Output = Self;
+
+    fn add(self, rhs: LayoutClientSizeF) -> Self::Output {
+        let width = self.width + rhs.width;
+        let height = self.height + rhs.height;
+        LayoutClientSizeF { width, height }
+    }
+}
+
+impl AddAssign for LayoutClientSizeF {
+    fn add_assign(&mut self, rhs: LayoutClientSizeF) {
+        self.width = self.width + rhs.width;
+        self.height = self.height + rhs.height;
+    }
+}
+
+/// How fractional layout results are turned into whole pixels when producing
+/// `LayoutBoundingClientRect`s from `LayoutBoundingClientRectF`s.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum LayoutPixelSnapping {
+    /// Truncate the origin and size independently.
+    Floor,
+    /// Round the origin and size independently.
+    Round,
+    /// Round the global edges instead of the size, like Yoga does, so that
+    /// the rounding error doesn't add up across adjacent and nested boxes.
+    /// Local rects are derived from the snapped global ones.
+    Cumulative
+}
+
+impl Default for LayoutPixelSnapping {
+    fn default() -> Self {
+        LayoutPixelSnapping::Floor
+    }
+}
+
+impl LayoutPixelSnapping {
+    pub fn snap_rect(&self, rect: &LayoutBoundingClientRectF) -> LayoutBoundingClientRect {
+        let p = rect.position;
+        let s = rect.size;
+        match *self {
+            LayoutPixelSnapping::Floor => LayoutBoundingClientRect::new(
+                snap_to_coordinate(p.left.floor()),
+                snap_to_coordinate(p.top.floor()),
+                snap_to_pixel(s.width.floor()),
+                snap_to_pixel(s.height.floor())
+            ),
+            LayoutPixelSnapping::Round => LayoutBoundingClientRect::new(
+                snap_to_coordinate(p.left.round()),
+                snap_to_coordinate(p.top.round()),
+                snap_to_pixel(s.width.round()),
+                snap_to_pixel(s.height.round())
+            ),
+            LayoutPixelSnapping::Cumulative => {
+                let left = p.left.round();
+                let top = p.top.round();
+                let right = (p.left + s.width).round();
+                let bottom = (p.top + s.height).round();
+                LayoutBoundingClientRect::new(
+                    snap_to_coordinate(left),
+                    snap_to_coordinate(top),
+                    snap_to_pixel(right - left),
+                    snap_to_pixel(bottom - top)
+                )
+            }
+        }
+    }
+}
+
+#[inline]
+fn snap_to_coordinate(value: f32) -> i32 {
+    if value.is_nan() {
+        0
+    } else {
+        f32::min(f32::max(value, i32::min_value() as f32), i32::max_value() as f32) as i32
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct TextNodeContext {
-    width_64: i32,
-    height_64: i32
+#[inline]
+fn snap_to_pixel(value: f32) -> u32 {
+    if value.is_nan() {
+        0
+    } else {
+        f32::max(value, 0.0) as u32
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -235,9 +661,9 @@ where
     }
 
     #[inline]
-    pub fn aspect_ratio_or(&self, value: f32) -> yoga::FlexStyle {
+    pub fn aspect_ratio_or(&self, value: f32) -> LayoutStyle {
         let ratio = self.map(|v| v.width() as f32 / v.height() as f32);
-        yoga::FlexStyle::AspectRatio(ratio.unwrap_or(value).into())
+        LayoutStyle::AspectRatio(Some(ratio.unwrap_or(value)))
     }
 }
 
@@ -280,22 +706,6 @@ impl<G> ShapedText<G>
 where
     G: TGlyphStore
 {
-    #[inline]
-    fn should_relayout(&mut self, other: Option<G>) -> bool {
-        let should_relayout = self.has_different_layout(&other);
-        **self = other;
-        should_relayout
-    }
-
-    #[inline]
-    fn has_different_layout(&self, other: &Option<G>) -> bool {
-        match (&self.0, other) {
-            (&Some(ref a), &Some(ref b)) => a.width_64() != b.width_64() || a.height_64() != b.height_64(),
-            (&None, &None) => false,
-            _ => true
-        }
-    }
-
     #[inline]
     pub fn font_key(&self) -> Option<G::FontKey> {
         self.as_ref().map(G::font_key)
@@ -338,27 +748,73 @@ where
 {
 }
 
-pub struct LayoutNode<S, C, R, T>
+/// Everything text was last measured with, so that measuring the same text
+/// the same way again doesn't shape it again. The font is identified by the
+/// family it was found with, or `Some(None)` for the default font.
+#[derive(Debug, PartialEq, Clone)]
+struct LayoutTextKey {
+    text: String,
+    font_family: Option<Option<String>>,
+    font_size: f32,
+    direction: LayoutReflowDirection,
+    text_overflow: LayoutTextOverflow,
+    line_clamp: Option<usize>,
+    white_space: LayoutWhiteSpace
+}
+
+pub struct LayoutNode<S, C, R, T, E = DefaultLayoutEngine>
 where
-    S: TStyleDeclarations<LayoutStyle = yoga::FlexStyle>,
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
     C: TComputedStyles<Styles = S>,
     R: TResourceGroup,
-    T: TDOMText
+    T: TDOMText,
+    E: TLayoutEngine
 {
     tainted: bool,
-    layout: yoga::Node,
+    element: Option<KnownElementName>,
+    user_agent: LayoutUserAgentStyles,
+    layout: E,
+    styles: LayoutStyleSnapshot,
+    custom_styles: LayoutStyleSnapshot,
+    grid: Option<LayoutGrid>,
+    grid_placement: LayoutGridPlacement,
+    flow_layout: bool,
+    flow_display: LayoutFlowDisplay,
+    object_fit: LayoutObjectFit,
+    object_position: LayoutObjectPosition,
+    pixel_snapping: LayoutPixelSnapping,
     computed_client_position: LayoutClientPosition,
-    shaped_text: ShapedText<<R::Fonts as TFontCache>::Glyphs>,
+    computed_client_position_f: LayoutClientPositionF,
+    committed_local_rect: Option<LayoutBoundingClientRect>,
+    committed_global_rect: Option<LayoutBoundingClientRect>,
+    rect_change: Option<LayoutRectChange>,
+    tainted_reflow: bool,
+    removed_rects: Vec<LayoutBoundingClientRect>,
+    computed_scroll_offset: LayoutClientPosition,
+    scroll_offset: LayoutClientPosition,
+    shaped_text: LayoutShapedText<<R::Fonts as TFontCache>::Glyphs>,
+    text_key: Option<LayoutTextKey>,
+    text_lines_box: Option<LayoutBoundingClientRectF>,
+    text_spans: Vec<LayoutTextSpan>,
+    text_direction: LayoutReflowDirection,
+    text_overflow: LayoutTextOverflow,
+    line_clamp: Option<usize>,
+    white_space: LayoutWhiteSpace,
+    custom_measure: Option<LayoutMeasureFunc>,
     measured_image: MeasuredImage<<R::Images as TImageCache>::Dimensions>,
+    image_aspect_ratio: Option<LayoutStyle>,
     phantom: PhantomData<(S, C, R, T)>
 }
 
-impl<S, C, R, T> PartialEq for LayoutNode<S, C, R, T>
+impl<S, C, R, T, E> PartialEq for LayoutNode<S, C, R, T, E>
 where
-    S: TStyleDeclarations<LayoutStyle = yoga::FlexStyle>,
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
     C: TComputedStyles<Styles = S>,
     R: TResourceGroup,
-    T: TDOMText
+    T: TDOMText,
+    E: TLayoutEngine
 {
     fn eq(&self, _: &Self) -> bool {
         // Layout nodes are opaque to the outside world. For equality checks
@@ -367,58 +823,734 @@ where
     }
 }
 
-impl<S, C, R, T> fmt::Debug for LayoutNode<S, C, R, T>
+impl<S, C, R, T, E> fmt::Debug for LayoutNode<S, C, R, T, E>
 where
-    S: TStyleDeclarations<LayoutStyle = yoga::FlexStyle>,
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
     C: TComputedStyles<Styles = S>,
     R: TResourceGroup,
-    T: TDOMText
+    T: TDOMText,
+    E: TLayoutEngine
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "LayoutNode {{ layout: {:?} }}", self.layout)
     }
 }
 
-impl<S, C, R, T> TLayoutNode for LayoutNode<S, C, R, T>
+impl<S, C, R, T, E> LayoutNode<S, C, R, T, E>
 where
-    S: TStyleDeclarations<LayoutStyle = yoga::FlexStyle>,
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
+    C: TComputedStyles<Styles = S>,
+    R: TResourceGroup,
+    T: TDOMText,
+    E: TLayoutEngine
+{
+    pub fn get_element(&self) -> Option<&KnownElementName> {
+        self.element.as_ref()
+    }
+
+    /// Layout styles currently applied to this node, both from the user
+    /// agent and custom styles.
+    pub fn get_styles(&self) -> &LayoutStyleSnapshot {
+        &self.styles
+    }
+
+    /// Layout styles applied before any custom styles, which are the ones
+    /// the user agent styles of this node give for its element.
+    pub fn get_user_agent_styles(&self) -> Vec<LayoutStyle> {
+        match self.element {
+            Some(ref element) => self.user_agent.get(element),
+            None => vec![]
+        }
+    }
+
+    /// Makes this node take its user agent styles from another table, and
+    /// applies them right away underneath the custom styles already
+    /// applied. Returns whether anything changed.
+    pub fn set_user_agent_styles(&mut self, user_agent: LayoutUserAgentStyles) -> bool {
+        self.user_agent = user_agent;
+        self.refresh_user_agent_styles()
+    }
+
+    /// Creates a layout node taking its user agent styles from the given
+    /// table, which is usually shared by every node of an app.
+    pub fn make_layout_node_with_user_agent_styles<U>(element: U, user_agent: LayoutUserAgentStyles) -> Self
+    where
+        U: TryInto<KnownElementName>
+    {
+        let mut node = LayoutNode {
+            tainted: false,
+            element: element.try_into().ok(),
+            user_agent,
+            layout: E::new(),
+            styles: LayoutStyleSnapshot::new(),
+            custom_styles: LayoutStyleSnapshot::new(),
+            grid: None,
+            grid_placement: LayoutGridPlacement::default(),
+            flow_layout: false,
+            flow_display: LayoutFlowDisplay::default(),
+            object_fit: LayoutObjectFit::default(),
+            object_position: LayoutObjectPosition::default(),
+            pixel_snapping: LayoutPixelSnapping::default(),
+            computed_client_position: LayoutClientPosition::default(),
+            computed_client_position_f: LayoutClientPositionF::default(),
+            committed_local_rect: None,
+            committed_global_rect: None,
+            rect_change: None,
+            tainted_reflow: false,
+            removed_rects: vec![],
+            computed_scroll_offset: LayoutClientPosition::default(),
+            scroll_offset: LayoutClientPosition::default(),
+            shaped_text: LayoutShapedText::default(),
+            text_key: None,
+            text_lines_box: None,
+            text_spans: vec![],
+            text_direction: LayoutReflowDirection::Inherit,
+            text_overflow: LayoutTextOverflow::default(),
+            line_clamp: None,
+            white_space: LayoutWhiteSpace::default(),
+            custom_measure: None,
+            measured_image: MeasuredImage::default(),
+            image_aspect_ratio: None,
+            phantom: PhantomData
+        };
+
+        let styles = node.get_merged_snapshot();
+        node.update_styles(styles);
+        node
+    }
+
+    /// User agent styles with the custom styles applied on top of them.
+    /// Images get their intrinsic aspect ratio in between, so that custom
+    /// styles can still override it.
+    fn get_merged_snapshot(&self) -> LayoutStyleSnapshot {
+        let mut snapshot = LayoutStyleSnapshot::new();
+        for style in self.get_user_agent_styles() {
+            snapshot.set(style);
+        }
+        if let Some(aspect_ratio) = self.image_aspect_ratio {
+            snapshot.set(aspect_ratio);
+        }
+        for style in self.custom_styles.iter() {
+            snapshot.set(*style);
+        }
+        snapshot
+    }
+
+    /// Applies the given custom styles on top of the ones already applied,
+    /// only touching Yoga for properties whose values actually differ from
+    /// what was applied last time. Properties missing from the given styles
+    /// keep their values until `reset_custom_styles`. Returns whether
+    /// anything changed, in which case the node needs to be laid out again.
+    pub fn apply_layout_styles(&mut self, styles: &S) -> bool {
+        {
+            let custom_styles = &mut self.custom_styles;
+            styles.for_each_layout_style(|style| {
+                custom_styles.set((*style).into());
+            });
+        }
+        let snapshot = self.get_merged_snapshot();
+        self.update_styles(snapshot)
+    }
+
+    /// Only pushes the properties that differ between the currently applied
+    /// styles and the given ones to Yoga. Returns whether anything changed.
+    fn update_styles(&mut self, styles: LayoutStyleSnapshot) -> bool {
+        let changes = self.styles.diff(&styles);
+        for style in &changes {
+            self.layout.apply_style(style);
+        }
+        self.styles = styles;
+        !changes.is_empty()
+    }
+
+    pub fn get_grid(&self) -> Option<&LayoutGrid> {
+        self.grid.as_ref()
+    }
+
+    /// Makes this node a grid container, laying out its children in the
+    /// given grid instead of as flex items, or goes back to flex layout.
+    pub fn set_grid(&mut self, grid: Option<LayoutGrid>) {
+        if self.grid != grid {
+            self.grid = grid;
+            self.update_container();
+            self.tainted = true;
+        }
+    }
+
+    pub fn get_grid_placement(&self) -> &LayoutGridPlacement {
+        &self.grid_placement
+    }
+
+    /// Where this node goes when its parent is a grid container.
+    pub fn set_grid_placement(&mut self, placement: LayoutGridPlacement) {
+        if self.grid_placement != placement {
+            self.grid_placement = placement;
+            self.update_container_item();
+            self.tainted = true;
+        }
+    }
+
+    pub fn is_flow_layout(&self) -> bool {
+        self.flow_layout
+    }
+
+    /// Makes this node lay out its children in block and inline flow, like
+    /// a paragraph, instead of as flex items. Grids take precedence over
+    /// this.
+    pub fn set_flow_layout(&mut self, flow_layout: bool) {
+        if self.flow_layout != flow_layout {
+            self.flow_layout = flow_layout;
+            self.update_container();
+            self.tainted = true;
+        }
+    }
+
+    pub fn get_flow_display(&self) -> LayoutFlowDisplay {
+        self.flow_display
+    }
+
+    /// How this node takes part in its parent's flow layout, if any.
+    pub fn set_flow_display(&mut self, flow_display: LayoutFlowDisplay) {
+        if self.flow_display != flow_display {
+            self.flow_display = flow_display;
+            self.update_container_item();
+            self.tainted = true;
+        }
+    }
+
+    fn update_container(&mut self) {
+        let container = match (&self.grid, self.flow_layout) {
+            (&Some(ref grid), _) => Some(LayoutContainer::Grid(grid.clone())),
+            (&None, true) => Some(LayoutContainer::Flow),
+            (&None, false) => None
+        };
+        self.layout.set_container(container);
+    }
+
+    fn update_container_item(&mut self) {
+        self.layout.set_container_item(LayoutContainerItem {
+            grid_placement: self.grid_placement.clone(),
+            flow_display: self.flow_display
+        });
+    }
+
+    /// Pieces of this node's text on each line, relative to its border box,
+    /// when it's laid out inline. Empty otherwise.
+    pub fn get_text_fragments(&self) -> Vec<LayoutTextFragment> {
+        self.layout.get_text_fragments()
+    }
+
+    pub fn get_pixel_snapping(&self) -> LayoutPixelSnapping {
+        self.pixel_snapping
+    }
+
+    pub fn set_pixel_snapping(&mut self, pixel_snapping: LayoutPixelSnapping) {
+        self.pixel_snapping = pixel_snapping;
+    }
+
+    pub fn get_local_bounding_client_rect_f(&self) -> LayoutBoundingClientRectF {
+        self.layout.get_layout_rect()
+    }
+
+    pub fn get_global_bounding_client_rect_f(&self) -> LayoutBoundingClientRectF {
+        self.get_local_bounding_client_rect_f() + self.computed_client_position_f
+    }
+
+    pub fn get_object_fit(&self) -> LayoutObjectFit {
+        self.object_fit
+    }
+
+    pub fn set_object_fit(&mut self, object_fit: LayoutObjectFit) {
+        self.object_fit = object_fit;
+    }
+
+    pub fn get_object_position(&self) -> LayoutObjectPosition {
+        self.object_position
+    }
+
+    pub fn set_object_position(&mut self, object_position: LayoutObjectPosition) {
+        self.object_position = object_position;
+    }
+
+    /// Where the measured image is drawn inside this node's global content
+    /// box, and which part of it is visible, according to object-fit and
+    /// object-position.
+    pub fn get_image_placement(&self) -> Option<LayoutImagePlacement> {
+        let width = self.measured_image.width()?;
+        let height = self.measured_image.height()?;
+        Some(LayoutImagePlacement::new(
+            self.object_fit,
+            self.object_position,
+            &self.get_global_box_rect_f(LayoutBoxKind::Content),
+            LayoutClientSizeF {
+                width: width as f32,
+                height: height as f32
+            }
+        ))
+    }
+
+    pub fn get_text_layout(&self) -> &TextLayout<<R::Fonts as TFontCache>::Glyphs> {
+        self.shaped_text.layout()
+    }
+
+    pub fn get_text_direction(&self) -> LayoutReflowDirection {
+        self.text_direction
+    }
+
+    /// Base direction of the paragraphs in this node's text. With `Inherit`,
+    /// each paragraph's direction is taken from its first strong character.
+    /// Text that was already measured is measured again right away.
+    ///
+    /// The direction given to `reflow_subtree` isn't used for this: it only
+    /// reaches the layout engine after text has been shaped and broken into
+    /// segments, and it decides which edges of boxes are start and end rather
+    /// than how characters are ordered.
+    pub fn set_text_direction(&mut self, resources: &R, direction: LayoutReflowDirection) {
+        if self.text_direction != direction {
+            self.text_direction = direction;
+            self.remeasure_text(resources);
+            self.tainted = true;
+        }
+    }
+
+    pub fn get_text_overflow(&self) -> LayoutTextOverflow {
+        self.text_overflow
+    }
+
+    /// Whether lines of this node's text that don't fit end with an ellipsis.
+    /// Text that was already measured is measured again right away.
+    pub fn set_text_overflow(&mut self, resources: &R, text_overflow: LayoutTextOverflow) {
+        if self.text_overflow != text_overflow {
+            self.text_overflow = text_overflow;
+            self.remeasure_text(resources);
+            self.tainted = true;
+        }
+    }
+
+    pub fn get_line_clamp(&self) -> Option<usize> {
+        self.line_clamp
+    }
+
+    /// The most lines of text this node shows and is measured with. Text
+    /// that was already measured is measured again right away.
+    pub fn set_line_clamp(&mut self, resources: &R, line_clamp: Option<usize>) {
+        if self.line_clamp != line_clamp {
+            self.line_clamp = line_clamp;
+            self.remeasure_text(resources);
+            self.tainted = true;
+        }
+    }
+
+    pub fn get_white_space(&self) -> LayoutWhiteSpace {
+        self.white_space
+    }
+
+    /// How whitespace in this node's text is processed before shaping, and
+    /// whether its lines wrap. Text that was already measured is measured
+    /// again right away.
+    pub fn set_white_space(&mut self, resources: &R, white_space: LayoutWhiteSpace) {
+        if self.white_space != white_space {
+            self.white_space = white_space;
+            self.remeasure_text(resources);
+            self.tainted = true;
+        }
+    }
+
+    /// Spans measured by `measure_self_as_spans`, if this node holds rich text.
+    pub fn get_text_spans(&self) -> &[LayoutTextSpan] {
+        &self.text_spans
+    }
+
+    /// Like `measure_self_as_text`, but for a paragraph made of differently
+    /// styled spans, each shaped with its own font. Fonts are looked up by
+    /// family and size only, since that's all the font cache goes by.
+    pub fn measure_self_as_spans(&mut self, resources: &R, spans: &[LayoutTextSpan]) {
+        let cache = resources.fonts();
+
+        let fonts = spans
+            .iter()
+            .map(|span| {
+                let size = span.font_size;
+                span.font_families
+                    .iter()
+                    .filter_map(|family| cache.get_font_with_size(family, size))
+                    .next()
+                    .or_else(|| cache.get_default_font_with_size(size))
+            })
+            .collect::<Vec<_>>();
+
+        let mut new_text_layout = {
+            let texts = spans.iter().map(|v| v.text.as_str()).collect::<Vec<_>>();
+            let direction = self.text_direction;
+            let white_space = self.white_space;
+            let shape = |span: usize, text: &str| fonts[span].as_ref().and_then(|f| cache.shape_text_h(f, text));
+            TextLayout::with_spans(&texts, direction, white_space, shape)
+        };
+
+        // The ellipsis follows the last span, so it's shaped with its font.
+        let ellipsis = match (self.text_overflow, fonts.iter().rev().filter_map(|v| v.as_ref()).next()) {
+            (LayoutTextOverflow::Ellipsis, Some(f)) => ShapedText(cache.shape_text_h(f, LAYOUT_TEXT_ELLIPSIS)),
+            _ => ShapedText::default()
+        };
+        new_text_layout.set_overflow(self.line_clamp, ellipsis);
+
+        self.text_spans = spans.to_vec();
+        self.text_key = None;
+        self.set_text_layout(new_text_layout);
+    }
+
+    /// Measures this node's text again with the fonts it was last measured
+    /// with, after a change to how it's laid out.
+    fn remeasure_text(&mut self, resources: &R) {
+        if !self.text_spans.is_empty() {
+            let spans = self.text_spans.clone();
+            self.measure_self_as_spans(resources, &spans);
+        } else if let Some(key) = self.text_key.take() {
+            let key = LayoutTextKey {
+                direction: self.text_direction,
+                text_overflow: self.text_overflow,
+                line_clamp: self.line_clamp,
+                white_space: self.white_space,
+                ..key
+            };
+            self.measure_text_with_key(resources, key);
+        }
+    }
+
+    fn measure_text_with_key(&mut self, resources: &R, key: LayoutTextKey) {
+        let cache = resources.fonts();
+        let font = match key.font_family {
+            Some(Some(ref name)) => cache.get_font_with_size(name, key.font_size),
+            Some(None) => cache.get_default_font_with_size(key.font_size),
+            None => None
+        };
+
+        let mut new_text_layout = match font {
+            Some(ref f) => {
+                let shape = |text: &str| cache.shape_text_h(f, text);
+                TextLayout::new(&key.text, key.direction, key.white_space, shape)
+            }
+            None => TextLayout::default()
+        };
+
+        let ellipsis = match (key.text_overflow, font.as_ref()) {
+            (LayoutTextOverflow::Ellipsis, Some(f)) => ShapedText(cache.shape_text_h(f, LAYOUT_TEXT_ELLIPSIS)),
+            _ => ShapedText::default()
+        };
+        new_text_layout.set_overflow(key.line_clamp, ellipsis);
+
+        self.text_key = Some(key);
+        self.text_spans.clear();
+        self.set_text_layout(new_text_layout);
+    }
+
+    /// Replaces this node's paragraph, only marking it dirty when the shaped
+    /// segments or line break opportunities changed, so that measuring
+    /// identical text doesn't force relayouts.
+    fn set_text_layout(&mut self, text_layout: TextLayout<<R::Fonts as TFontCache>::Glyphs>) {
+        let should_relayout = self.get_text_layout().has_different_layout(&text_layout);
+        self.shaped_text.set_layout(text_layout);
+        self.text_lines_box = None;
+
+        if !should_relayout {
+            // Lines break the same way, so only the runs need placing again.
+            self.update_text_lines();
+            return;
+        }
+
+        let context = LayoutMeasureContext::Text {
+            metrics: Rc::clone(self.get_text_layout().metrics())
+        };
+        self.layout.set_measure_context(Some(context));
+        self.layout.mark_dirty();
+
+        self.tainted = true;
+    }
+
+    /// Lines of text, broken for the width of this node's content box when
+    /// the last reflow was committed, or as they were laid out inline.
+    pub fn get_text_lines(&self) -> &[TextLine] {
+        self.shaped_text.lines()
+    }
+
+    /// Line boxes of this node's text, in global coordinates, built from
+    /// the shaped segments and the global content box.
+    pub fn get_line_boxes(&self) -> Vec<LayoutLineBox> {
+        let fragments = self.get_text_fragments();
+        if !fragments.is_empty() {
+            let border_box = self.get_global_box_rect_f(LayoutBoxKind::Border);
+            return self.get_text_layout().fragment_line_boxes(border_box.position, &fragments);
+        }
+        let content_box = self.get_global_box_rect_f(LayoutBoxKind::Content);
+        self.get_text_layout().line_boxes(&content_box, self.get_text_lines())
+    }
+
+    pub fn get_layout_margin(&self) -> LayoutBoxEdgesF {
+        self.layout.get_layout_margin()
+    }
+
+    pub fn get_layout_border(&self) -> LayoutBoxEdgesF {
+        self.layout.get_layout_border()
+    }
+
+    pub fn get_layout_padding(&self) -> LayoutBoxEdgesF {
+        self.layout.get_layout_padding()
+    }
+
+    pub fn get_local_box_rect_f(&self, kind: LayoutBoxKind) -> LayoutBoundingClientRectF {
+        // Yoga's layout rect is the border box.
+        let border_box = self.get_local_bounding_client_rect_f();
+        match kind {
+            LayoutBoxKind::Margin => border_box.inflate(&self.get_layout_margin()),
+            LayoutBoxKind::Border => border_box,
+            LayoutBoxKind::Padding => border_box.deflate(&self.get_layout_border()),
+            LayoutBoxKind::Content => border_box.deflate(&(self.get_layout_border() + self.get_layout_padding()))
+        }
+    }
+
+    pub fn get_global_box_rect_f(&self, kind: LayoutBoxKind) -> LayoutBoundingClientRectF {
+        self.get_local_box_rect_f(kind) + self.computed_client_position_f
+    }
+
+    pub fn get_local_box_rect(&self, kind: LayoutBoxKind) -> LayoutBoundingClientRect {
+        match self.pixel_snapping {
+            LayoutPixelSnapping::Cumulative => self.get_global_box_rect(kind) - self.computed_client_position,
+            _ => self.get_local_box_rect_f(kind).snap(self.pixel_snapping)
+        }
+    }
+
+    pub fn get_global_box_rect(&self, kind: LayoutBoxKind) -> LayoutBoundingClientRect {
+        match self.pixel_snapping {
+            LayoutPixelSnapping::Cumulative => self.get_global_box_rect_f(kind).snap(self.pixel_snapping),
+            _ => self.get_local_box_rect(kind) + self.computed_client_position
+        }
+    }
+
+    /// Scroll offset of this node's own contents, if it's a scroll container.
+    pub fn get_scroll_offset(&self) -> LayoutClientPosition {
+        self.scroll_offset
+    }
+
+    pub fn set_scroll_offset(&mut self, scroll_offset: LayoutClientPosition) {
+        self.scroll_offset = scroll_offset;
+    }
+
+    /// Sum of the scroll offsets of all of this node's ancestors. Layout rects
+    /// don't account for scrolling, so this is what separates the global
+    /// position of a node from where it's painted on the page.
+    pub fn get_computed_scroll_offset(&self) -> LayoutClientPosition {
+        self.computed_scroll_offset
+    }
+
+    pub fn set_computed_scroll_offset(&mut self, computed: LayoutClientPosition) {
+        self.computed_scroll_offset = computed;
+    }
+
+    pub fn global_from_page(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x + self.computed_scroll_offset.left, y + self.computed_scroll_offset.top)
+    }
+
+    pub fn page_from_global(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x - self.computed_scroll_offset.left, y - self.computed_scroll_offset.top)
+    }
+
+    /// Converts a global point to be relative to this node's border edge.
+    pub fn local_from_global(&self, point: (i32, i32)) -> (i32, i32) {
+        self.get_global_box_rect(LayoutBoxKind::Border).local_from_global(point)
+    }
+
+    pub fn global_from_local(&self, point: (i32, i32)) -> (i32, i32) {
+        self.get_global_box_rect(LayoutBoxKind::Border).global_from_local(point)
+    }
+
+    pub fn local_from_page(&self, point: (i32, i32)) -> (i32, i32) {
+        self.local_from_global(self.global_from_page(point))
+    }
+
+    pub fn page_from_local(&self, point: (i32, i32)) -> (i32, i32) {
+        self.page_from_global(self.global_from_local(point))
+    }
+
+    pub fn local_from_client(&self, viewport: &LayoutViewport, point: (i32, i32)) -> (i32, i32) {
+        self.local_from_page(viewport.page_from_client(point))
+    }
+
+    pub fn client_from_local(&self, viewport: &LayoutViewport, point: (i32, i32)) -> (i32, i32) {
+        viewport.client_from_page(self.page_from_local(point))
+    }
+
+    /// Converts a local point to be relative to the top left corner of this
+    /// node's scrolled contents, which is where its children are laid out.
+    pub fn contents_from_local(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x + self.scroll_offset.left, y + self.scroll_offset.top)
+    }
+
+    pub fn local_from_contents(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x - self.scroll_offset.left, y - self.scroll_offset.top)
+    }
+
+    /// Offset of a page point from the padding edge of this node.
+    /// See https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/offsetX
+    /// See https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/offsetY
+    pub fn offset_from_page(&self, point: (i32, i32)) -> (i32, i32) {
+        let padding_box = self.get_global_box_rect(LayoutBoxKind::Padding);
+        padding_box.local_from_global(self.global_from_page(point))
+    }
+
+    /// Compares the current layout results against the ones committed last
+    /// time, remembers any change until it's taken, and clears the tainted
+    /// flag, remembering that too.
+    fn commit_reflow(&mut self) {
+        let local = self.get_local_box_rect(LayoutBoxKind::Border);
+        let global = self.get_global_box_rect(LayoutBoxKind::Border);
+        if self.committed_local_rect != Some(local) || self.committed_global_rect != Some(global) {
+            let previous = match self.rect_change {
+                Some(change) => change.previous,
+                None => self.committed_global_rect
+            };
+            self.rect_change = Some(LayoutRectChange {
+                previous,
+                current: global
+            });
+        }
+        self.committed_local_rect = Some(local);
+        self.committed_global_rect = Some(global);
+        self.tainted_reflow = self.tainted_reflow || self.tainted;
+        self.tainted = false;
+        self.update_text_lines();
+    }
+
+    /// Keeps the lines text was laid out on inline, or breaks it for the
+    /// final size of the content box, and places them in line boxes relative
+    /// to the border box, so that renderers can draw them and lines aren't
+    /// broken again every time they're asked for.
+    fn update_text_lines(&mut self) {
+        let fragments = self.get_text_fragments();
+        if !fragments.is_empty() {
+            let lines = self.get_text_layout().fragment_lines(&fragments);
+            let line_boxes = self.get_text_layout().fragment_line_boxes(LayoutClientPositionF::default(), &fragments);
+            self.shaped_text.set_lines(lines, line_boxes);
+            self.text_lines_box = None;
+            return;
+        }
+
+        let border_box = self.get_local_box_rect_f(LayoutBoxKind::Border).zero_position();
+        let content_box = border_box.deflate(&(self.get_layout_border() + self.get_layout_padding()));
+        if self.text_lines_box != Some(content_box) {
+            let lines = self.get_text_layout().metrics().break_lines(Some(content_box.size.width));
+            let line_boxes = self.get_text_layout().line_boxes(&content_box, &lines);
+            self.shaped_text.set_lines(lines, line_boxes);
+            self.text_lines_box = Some(content_box);
+        }
+    }
+}
+
+impl<S, C, R, T, E> TLayoutTreeNode for LayoutNode<S, C, R, T, E>
+where
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
+    C: TComputedStyles<Styles = S>,
+    R: TResourceGroup,
+    T: TDOMText,
+    E: TLayoutEngine
+{
+    fn global_rect(&self) -> LayoutBoundingClientRect {
+        self.get_global_box_rect(LayoutBoxKind::Border)
+    }
+
+    fn is_hidden(&self) -> bool {
+        match self.styles.get(&LayoutStyle::Display(LayoutDisplay::Flex)) {
+            Some(&LayoutStyle::Display(LayoutDisplay::None)) => true,
+            _ => false
+        }
+    }
+
+    fn clips_overflow(&self) -> bool {
+        match self.styles.get(&LayoutStyle::Overflow(LayoutOverflow::Visible)) {
+            Some(&LayoutStyle::Overflow(LayoutOverflow::Visible)) | None => false,
+            _ => true
+        }
+    }
+
+    fn scroll_offset(&self) -> LayoutClientPosition {
+        self.get_scroll_offset()
+    }
+
+    fn global_rect_f(&self) -> LayoutBoundingClientRectF {
+        self.get_global_box_rect_f(LayoutBoxKind::Border)
+    }
+
+    fn set_computed_client_positions(&mut self, computed: LayoutClientPosition, computed_f: LayoutClientPositionF) {
+        // Positions are set on every node right after a reflow, so this is
+        // where its results get committed.
+        self.computed_client_position = computed;
+        self.computed_client_position_f = computed_f;
+        self.commit_reflow();
+    }
+
+    fn take_rect_change(&mut self) -> Option<LayoutRectChange> {
+        self.rect_change.take()
+    }
+
+    fn take_tainted_reflow(&mut self) -> bool {
+        mem::replace(&mut self.tainted_reflow, false)
+    }
+
+    fn take_removed_rects(&mut self) -> Vec<LayoutBoundingClientRect> {
+        mem::replace(&mut self.removed_rects, vec![])
+    }
+
+    fn refresh_user_agent_styles(&mut self) -> bool {
+        let styles = self.get_merged_snapshot();
+        let changed = self.update_styles(styles);
+        if changed {
+            self.tainted = true;
+        }
+        changed
+    }
+}
+
+impl<S, C, R, T, E> TLayoutNode for LayoutNode<S, C, R, T, E>
+where
+    S: TStyleDeclarations,
+    S::LayoutStyle: Copy + Into<LayoutStyle>,
     C: TComputedStyles<Styles = S> + 'static,
     R: TResourceGroup + 'static,
-    T: TDOMText + 'static
+    T: TDOMText + 'static,
+    E: TLayoutEngine
 {
     type Styles = S;
     type Resources = R;
     type TextMeasureMetadata = C;
     type ImageMeasureMetadata = ();
-    type NormalMeasureMetadata = !;
+    type NormalMeasureMetadata = Option<LayoutMeasureFunc>;
     type ReflowDirection = LayoutReflowDirection;
     type ClientPosition = LayoutClientPosition;
     type BoundingClientRect = LayoutBoundingClientRect;
     type MeasuredImage = MeasuredImage<<R::Images as TImageCache>::Dimensions>;
-    type ShapedText = ShapedText<<R::Fonts as TFontCache>::Glyphs>;
+    type ShapedText = LayoutShapedText<<R::Fonts as TFontCache>::Glyphs>;
 
-    fn make_initial_layout_node<U>(_: U) -> Self
+    fn make_initial_layout_node<U>(element: U) -> Self
     where
         U: TryInto<KnownElementName>
     {
-        LayoutNode {
-            tainted: false,
-            layout: yoga::Node::new(),
-            computed_client_position: LayoutClientPosition::default(),
-            shaped_text: ShapedText::default(),
-            measured_image: MeasuredImage::default(),
-            phantom: PhantomData
-        }
+        Self::make_layout_node_with_user_agent_styles(element, LayoutUserAgentStyles::new())
     }
 
-    fn reset_custom_styles<U>(&mut self, _: U)
+    fn reset_custom_styles<U>(&mut self, element: U)
     where
         U: TryInto<KnownElementName>
     {
-        // TODO: actually do something here. Copying styles from a new node
-        // seems to have an enormous allocation pressure, which is slow.
-        // self.layout.copy_style(&yoga::Node::new());
-        // self.tainted = true;
+        // Copying styles from a new Yoga node has an enormous allocation
+        // pressure, so only restore the properties that were actually set.
+        self.element = element.try_into().ok();
+        self.custom_styles = LayoutStyleSnapshot::new();
+        let styles = self.get_merged_snapshot();
+        if self.update_styles(styles) {
+            self.tainted = true;
+        }
     }
 
     fn is_tainted(&self) -> bool {
@@ -426,7 +1558,7 @@ where
     }
 
     fn insert_child(&mut self, child: &mut Self, index: usize) {
-        self.layout.insert_child(&mut child.layout, index as u32);
+        self.layout.insert_child(&mut child.layout, index);
         self.tainted = true;
     }
 
@@ -438,11 +1570,16 @@ where
 
     fn remove_child(&mut self, child: &mut Self) {
         self.layout.remove_child(&mut child.layout);
+        self.removed_rects.extend(child.committed_global_rect.take());
+        child.committed_local_rect = None;
+        child.rect_change = None;
         self.tainted = true;
     }
 
     fn apply_styles(&mut self, styles: &Self::Styles) {
-        styles.for_each_layout_style(|style| self.layout.apply_style(style));
+        if self.apply_layout_styles(styles) {
+            self.tainted = true;
+        }
     }
 
     fn mark_dirty(&mut self) {
@@ -453,7 +1590,6 @@ where
     where
         U: TDOMText
     {
-        let layout = &mut self.layout;
         let cache = resources.images();
         let new_dimensions = cache.measure_image(&image_src);
 
@@ -461,17 +1597,18 @@ where
             return;
         }
 
-        layout.set_context(Some(yoga::Context::new(ImageNodeContext {
+        let context = LayoutMeasureContext::Image {
             width: self.measured_image.width().unwrap_or(0),
             height: self.measured_image.height().unwrap_or(0)
-        })));
-
-        layout.set_measure_func(Some(measure_image));
-        layout.apply_style(&self.measured_image.aspect_ratio_or(1.0));
-
-        // TODO: Need to mark dirty, but this affects performance quite a lot.
-        // Should be smart about it and only mark dirty when actually necesssry.
-        // layout.mark_dirty();
+        };
+        self.layout.set_measure_context(Some(context));
+        self.image_aspect_ratio = Some(self.measured_image.aspect_ratio_or(1.0));
+        let styles = self.get_merged_snapshot();
+        self.update_styles(styles);
+
+        // Only reached when the measured dimensions changed, so this doesn't
+        // force relayouts when rerendering identical images.
+        self.layout.mark_dirty();
 
         self.tainted = true;
     }
@@ -480,7 +1617,6 @@ where
     where
         U: TDOMText
     {
-        let layout = &mut self.layout;
         let cache = resources.fonts();
         let computed_styles = metadata;
 
@@ -490,33 +1626,52 @@ where
             .map(|v| v.point())
             .unwrap_or(DEFAULT_FONT_SIZE);
 
-        let new_glyphs = computed_styles
-            .find_font(|name| cache.get_font_with_size(name, size))
-            .or_else(|| cache.get_default_font_with_size(size))
-            .and_then(|f| cache.shape_text_h(&f, source_text.as_ref()));
-
-        if !self.shaped_text.should_relayout(new_glyphs) {
+        let font_family = computed_styles
+            .find_font(|name| cache.get_font_with_size(name, size).map(|_| Some(name.to_string())))
+            .or_else(|| cache.get_default_font_with_size(size).map(|_| None));
+
+        let key = LayoutTextKey {
+            text: source_text.as_ref().to_string(),
+            font_family,
+            font_size: size,
+            direction: self.text_direction,
+            text_overflow: self.text_overflow,
+            line_clamp: self.line_clamp,
+            white_space: self.white_space
+        };
+        if self.text_key.as_ref() == Some(&key) {
             return;
         }
+        self.measure_text_with_key(resources, key);
+    }
+
+    fn measure_self_as_normal(&mut self, _: &Self::Resources, measure: &Self::NormalMeasureMetadata) {
+        let new_measure = measure.clone();
+
+        let should_relayout = match (&self.custom_measure, &new_measure) {
+            (&Some(ref a), &Some(ref b)) => !Rc::ptr_eq(a, b),
+            (&None, &None) => false,
+            _ => true
+        };
 
-        layout.set_context(Some(yoga::Context::new(TextNodeContext {
-            width_64: self.shaped_text.width_64().unwrap_or(0),
-            height_64: self.shaped_text.height_64().unwrap_or(0)
-        })));
+        self.custom_measure = new_measure;
 
-        layout.set_measure_func(Some(measure_text));
+        if !should_relayout {
+            return;
+        }
 
-        // TODO: Need to mark dirty, but this affects performance quite a lot.
-        // Should be smart about it and only mark dirty when actually necesssry.
-        // layout.mark_dirty();
+        let context = self.custom_measure.as_ref().map(|measure| LayoutMeasureContext::Custom {
+            measure: Rc::clone(measure)
+        });
+        let is_measured = context.is_some();
+        self.layout.set_measure_context(context);
+        if is_measured {
+            self.layout.mark_dirty();
+        }
 
         self.tainted = true;
     }
 
-    fn measure_self_as_normal(&mut self, _: &Self::Resources, _: &Self::NormalMeasureMetadata) {
-        unreachable!()
-    }
-
     fn reflow_subtree(&mut self, width: u32, height: u32, direction: Self::ReflowDirection) {
         let width = width as f32;
         let height = height as f32;
@@ -524,20 +1679,15 @@ where
     }
 
     fn set_computed_client_position(&mut self, computed: Self::ClientPosition) {
-        self.computed_client_position = computed;
+        self.set_computed_client_positions(computed, computed.into());
     }
 
     fn get_local_bounding_client_rect(&self) -> Self::BoundingClientRect {
-        Self::BoundingClientRect::new(
-            self.layout.get_layout_left() as u32,
-            self.layout.get_layout_top() as u32,
-            self.layout.get_layout_width() as u32,
-            self.layout.get_layout_height() as u32
-        )
+        self.get_local_box_rect(LayoutBoxKind::Border)
     }
 
     fn get_global_bounding_client_rect(&self) -> Self::BoundingClientRect {
-        self.get_local_bounding_client_rect() + self.computed_client_position
+        self.get_global_box_rect(LayoutBoxKind::Border)
     }
 
     fn get_measured_image(&self) -> &Self::MeasuredImage {
@@ -548,57 +1698,3 @@ where
         &self.shaped_text
     }
 }
-
-extern "C" fn measure_image(
-    node_ref: yoga::YGInternalNodeRef,
-    suggested_width: f32,
-    node_width_measure_mode: yoga::YGInternalMeasureMode,
-    suggested_height: f32,
-    node_height_measure_mode: yoga::YGInternalMeasureMode
-) -> yoga::YGInternalSize {
-    use self::yoga::YGInternalMeasureMode::*;
-
-    let context = yoga::Node::get_context(&node_ref)
-        .and_then(|v| v.downcast_ref::<ImageNodeContext>())
-        .expect("Invalid context when measuring images.");
-
-    yoga::YGInternalSize {
-        width: match (node_width_measure_mode, context.width as f32) {
-            (YGMeasureModeExactly, _) => suggested_width,
-            (YGMeasureModeAtMost, measured_width) => f32::min(measured_width, suggested_width),
-            (YGMeasureModeUndefined, measured_width) => measured_width
-        },
-        height: match (node_height_measure_mode, context.height as f32) {
-            (YGMeasureModeExactly, _) => suggested_height,
-            (YGMeasureModeAtMost, measured_height) => f32::min(measured_height, suggested_height),
-            (YGMeasureModeUndefined, measured_height) => measured_height
-        }
-    }
-}
-
-extern "C" fn measure_text(
-    node_ref: yoga::YGInternalNodeRef,
-    suggested_width: f32,
-    node_width_measure_mode: yoga::YGInternalMeasureMode,
-    suggested_height: f32,
-    node_height_measure_mode: yoga::YGInternalMeasureMode
-) -> yoga::YGInternalSize {
-    use self::yoga::YGInternalMeasureMode::*;
-
-    let context = yoga::Node::get_context(&node_ref)
-        .and_then(|v| v.downcast_ref::<TextNodeContext>())
-        .expect("Invalid context when measuring text.");
-
-    yoga::YGInternalSize {
-        width: match (node_width_measure_mode, context.width_64 as f32 / 64.0) {
-            (YGMeasureModeExactly, _) => suggested_width,
-            (YGMeasureModeAtMost, measured_width) => f32::min(measured_width, suggested_width),
-            (YGMeasureModeUndefined, measured_width) => measured_width
-        },
-        height: match (node_height_measure_mode, context.height_64 as f32 / 64.0) {
-            (YGMeasureModeExactly, _) => suggested_height,
-            (YGMeasureModeAtMost, measured_height) => f32::min(measured_height, suggested_height),
-            (YGMeasureModeUndefined, measured_height) => measured_height
-        }
-    }
-}
@@ -0,0 +1,89 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rsx_shared::types::KnownElementName;
+
+use styles::{LayoutFlexDirection, LayoutStyle};
+use traits::{TLayoutTreeMut, TLayoutTreeNode};
+
+/// Built-in layout styles for known elements, applied before any custom
+/// styles when a layout node is created or has its custom styles reset.
+/// Elements laid out with the engine defaults don't have any. Apps can
+/// override these with `LayoutUserAgentStyles`.
+pub fn default_user_agent_styles(element: &KnownElementName) -> &'static [LayoutStyle] {
+    match *element {
+        KnownElementName::Text => &[LayoutStyle::FlexDirection(LayoutFlexDirection::Row)],
+        _ => &[]
+    }
+}
+
+/// The user agent styles of an app: the built-in ones for each known
+/// element, unless the app overrides them. Clones share the same table, so
+/// every layout node created with it sees overrides made through any of
+/// them. Nodes look their styles up when they're created and whenever
+/// their custom styles are reset; use `refresh_user_agent_styles` to apply
+/// overrides to existing nodes right away.
+#[derive(Debug, Default, Clone)]
+pub struct LayoutUserAgentStyles(Rc<RefCell<HashMap<KnownElementName, Vec<LayoutStyle>>>>);
+
+impl LayoutUserAgentStyles {
+    pub fn new() -> Self {
+        LayoutUserAgentStyles(Rc::new(RefCell::new(HashMap::new())))
+    }
+
+    /// Layout styles currently used for a known element.
+    pub fn get(&self, element: &KnownElementName) -> Vec<LayoutStyle> {
+        match self.0.borrow().get(element) {
+            Some(styles) => styles.clone(),
+            None => default_user_agent_styles(element).to_vec()
+        }
+    }
+
+    /// Replaces the built-in layout styles for a known element.
+    pub fn set(&self, element: KnownElementName, styles: Vec<LayoutStyle>) {
+        self.0.borrow_mut().insert(element, styles);
+    }
+
+    /// Goes back to using the built-in layout styles for a known element.
+    pub fn reset(&self, element: &KnownElementName) {
+        self.0.borrow_mut().remove(element);
+    }
+}
+
+/// Applies the current user agent styles to every node of a tree, keeping
+/// their custom styles on top, after overriding or resetting some of them.
+/// Nodes whose styles changed are tainted. Returns whether any did.
+pub fn refresh_user_agent_styles<T>(tree: &mut T) -> bool
+where
+    T: TLayoutTreeMut
+{
+    let root = tree.root();
+    refresh_subtree(tree, root)
+}
+
+fn refresh_subtree<T>(tree: &mut T, id: T::NodeId) -> bool
+where
+    T: TLayoutTreeMut
+{
+    let mut changed = match tree.get_layout_node_mut(id) {
+        Some(node) => node.refresh_user_agent_styles(),
+        None => false
+    };
+
+    for child in tree.get_children(id) {
+        changed |= refresh_subtree(tree, child);
+    }
+    changed
+}
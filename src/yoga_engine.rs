@@ -0,0 +1,853 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::f32;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::{Rc, Weak};
+
+use yoga::{Align, Direction, Display, FlexDirection, FlexStyle, Justify, Overflow, PositionType, StyleUnit, Wrap};
+use yoga;
+
+use container::{LayoutContainer, LayoutContainerItem, TLayoutContainerChild};
+use engine::{constrain, LayoutMeasureContext, TLayoutEngine};
+use measure::LayoutMeasureMode;
+use styles::{
+    LayoutAlign,
+    LayoutDisplay,
+    LayoutFlexDirection,
+    LayoutFlexWrap,
+    LayoutJustify,
+    LayoutOverflow,
+    LayoutPositionType,
+    LayoutReflowDirection,
+    LayoutStyle,
+    LayoutStyleUnit
+};
+use text_layout::{LayoutTextFragment, TextMetrics};
+use types::{LayoutBoundingClientRectF, LayoutBoxEdgesF, LayoutClientSizeF};
+
+thread_local! {
+    // Unwinding into Yoga would abort, so panics in measure and baseline
+    // callbacks are kept here until Yoga returns, then resumed.
+    static CALLBACK_PANIC: RefCell<Option<Box<Any + Send>>> = RefCell::new(None);
+}
+
+/// Layout engine backed by Yoga's flexbox implementation. Grid and flow
+/// containers are measured like leaves by Yoga, and lay out each of their
+/// children as the root of a separate Yoga tree, inside the areas they give
+/// them once Yoga is done with their own tree.
+pub struct YogaLayoutEngine(Rc<YogaNode>);
+
+struct YogaNode {
+    node: RefCell<yoga::Node>,
+    state: RefCell<YogaNodeState>
+}
+
+struct YogaNodeState {
+    parent: Option<Weak<YogaNode>>,
+    children: Vec<Rc<YogaNode>>,
+    /// Whether the children were taken out of this node's Yoga tree, which
+    /// happens for grid and flow containers.
+    detached: bool,
+    hidden: bool,
+    measure: Option<LayoutMeasureContext>,
+    container: Option<LayoutContainer>,
+    item: LayoutContainerItem,
+    direction: LayoutReflowDirection,
+    /// Border box given by the grid or flow container this node is in,
+    /// instead of the one Yoga computed for it as a root.
+    placed_rect: Option<LayoutBoundingClientRectF>,
+    text_fragments: Vec<LayoutTextFragment>
+}
+
+/// What Yoga's measure and baseline functions get to look at. Baselines are
+/// relative to the border box, so they need the node's border and padding.
+struct YogaContext {
+    node: Weak<YogaNode>,
+    measure: LayoutMeasureContext
+}
+
+impl fmt::Debug for YogaLayoutEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "YogaLayoutEngine {{ layout: {:?} }}", self.get_layout_rect())
+    }
+}
+
+impl TLayoutEngine for YogaLayoutEngine {
+    fn new() -> Self {
+        YogaLayoutEngine(Rc::new(YogaNode {
+            node: RefCell::new(yoga::Node::new()),
+            state: RefCell::new(YogaNodeState {
+                parent: None,
+                children: vec![],
+                detached: false,
+                hidden: false,
+                measure: None,
+                container: None,
+                item: LayoutContainerItem::default(),
+                direction: LayoutReflowDirection::LTR,
+                placed_rect: None,
+                text_fragments: vec![]
+            })
+        }))
+    }
+
+    fn child_count(&self) -> usize {
+        self.0.state.borrow().children.len()
+    }
+
+    fn insert_child(&mut self, child: &mut Self, index: usize) {
+        child.0.state.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+        let detached = {
+            let mut state = self.0.state.borrow_mut();
+            state.children.insert(index, Rc::clone(&child.0));
+            state.detached
+        };
+        if !detached {
+            self.0.node.borrow_mut().insert_child(&mut child.0.node.borrow_mut(), index as u32);
+        }
+        mark_containers_dirty(&self.0);
+    }
+
+    fn remove_child(&mut self, child: &mut Self) {
+        let index = self.0.state.borrow().children.iter().position(|v| Rc::ptr_eq(v, &child.0));
+        if let Some(index) = index {
+            let detached = {
+                let mut state = self.0.state.borrow_mut();
+                state.children.remove(index);
+                state.detached
+            };
+            if !detached {
+                self.0.node.borrow_mut().remove_child(&mut child.0.node.borrow_mut());
+            }
+            {
+                let mut state = child.0.state.borrow_mut();
+                state.parent = None;
+                state.placed_rect = None;
+                state.text_fragments.clear();
+            }
+            mark_containers_dirty(&self.0);
+        }
+    }
+
+    fn apply_style(&mut self, style: &LayoutStyle) {
+        if let LayoutStyle::Display(display) = *style {
+            self.0.state.borrow_mut().hidden = match display {
+                LayoutDisplay::None => true,
+                LayoutDisplay::Flex => false
+            };
+        }
+        self.0.node.borrow_mut().apply_style(&FlexStyle::from(*style));
+        mark_containers_dirty(&self.0);
+    }
+
+    fn set_measure_context(&mut self, context: Option<LayoutMeasureContext>) {
+        self.0.state.borrow_mut().measure = context;
+        sync_measure(&self.0);
+        mark_containers_dirty(&self.0);
+    }
+
+    fn mark_dirty(&mut self) {
+        self.0.node.borrow_mut().mark_dirty();
+        mark_containers_dirty(&self.0);
+    }
+
+    fn set_container(&mut self, container: Option<LayoutContainer>) {
+        self.0.state.borrow_mut().container = container;
+        sync_measure(&self.0);
+        mark_containers_dirty(&self.0);
+    }
+
+    fn set_container_item(&mut self, item: LayoutContainerItem) {
+        self.0.state.borrow_mut().item = item;
+        mark_containers_dirty(&self.0);
+    }
+
+    fn calculate_layout(&mut self, width: f32, height: f32, direction: LayoutReflowDirection) {
+        let direction = match direction {
+            LayoutReflowDirection::Inherit => LayoutReflowDirection::LTR,
+            direction => direction
+        };
+        set_direction(&self.0, direction);
+        layout_tree(&self.0, width, height);
+    }
+
+    fn get_layout_rect(&self) -> LayoutBoundingClientRectF {
+        if let Some(rect) = self.0.state.borrow().placed_rect {
+            return rect;
+        }
+        let node = self.0.node.borrow();
+        LayoutBoundingClientRectF::new(
+            node.get_layout_left(),
+            node.get_layout_top(),
+            node.get_layout_width(),
+            node.get_layout_height()
+        )
+    }
+
+    fn get_layout_margin(&self) -> LayoutBoxEdgesF {
+        layout_margin(&self.0.node.borrow())
+    }
+
+    fn get_layout_border(&self) -> LayoutBoxEdgesF {
+        layout_border(&self.0.node.borrow())
+    }
+
+    fn get_layout_padding(&self) -> LayoutBoxEdgesF {
+        layout_padding(&self.0.node.borrow())
+    }
+
+    fn get_text_fragments(&self) -> Vec<LayoutTextFragment> {
+        self.0.state.borrow().text_fragments.clone()
+    }
+}
+
+fn layout_margin(node: &yoga::Node) -> LayoutBoxEdgesF {
+    LayoutBoxEdgesF::new(
+        node.get_layout_margin_top(),
+        node.get_layout_margin_right(),
+        node.get_layout_margin_bottom(),
+        node.get_layout_margin_left()
+    )
+}
+
+fn layout_border(node: &yoga::Node) -> LayoutBoxEdgesF {
+    LayoutBoxEdgesF::new(
+        node.get_layout_border_top(),
+        node.get_layout_border_right(),
+        node.get_layout_border_bottom(),
+        node.get_layout_border_left()
+    )
+}
+
+fn layout_padding(node: &yoga::Node) -> LayoutBoxEdgesF {
+    LayoutBoxEdgesF::new(
+        node.get_layout_padding_top(),
+        node.get_layout_padding_right(),
+        node.get_layout_padding_bottom(),
+        node.get_layout_padding_left()
+    )
+}
+
+fn is_container(state: &YogaNodeState) -> bool {
+    state.container.is_some() && state.measure.is_none()
+}
+
+/// Points Yoga at whatever measures a node. Grid and flow containers are
+/// measured by laying out their children, which are taken out of the Yoga
+/// tree, since nodes with a measure function can't have children in Yoga.
+fn sync_measure(node: &Rc<YogaNode>) {
+    let mut state = node.state.borrow_mut();
+    let mut yoga_node = node.node.borrow_mut();
+    let container = is_container(&state);
+
+    yoga_node.set_measure_func(None);
+    yoga_node.set_baseline_func(None);
+    yoga_node.set_context(None);
+
+    if container != state.detached {
+        for (index, child) in state.children.iter().enumerate() {
+            let mut child_node = child.node.borrow_mut();
+            if container {
+                yoga_node.remove_child(&mut child_node);
+            } else {
+                yoga_node.insert_child(&mut child_node, index as u32);
+                let mut child_state = child.state.borrow_mut();
+                child_state.placed_rect = None;
+                child_state.text_fragments.clear();
+            }
+        }
+        state.detached = container;
+    }
+
+    let context = if container { Some(container_context(node)) } else { state.measure.clone() };
+    if let Some(context) = context {
+        yoga_node.set_context(Some(yoga::Context::new(YogaContext {
+            node: Rc::downgrade(node),
+            measure: context
+        })));
+        yoga_node.set_measure_func(Some(measure));
+        // Containers have no baseline of their own, like custom elements.
+        if !container {
+            yoga_node.set_baseline_func(Some(baseline));
+        }
+    }
+    if container {
+        yoga_node.mark_dirty();
+    }
+}
+
+/// Makes the grid and flow containers at or above a node measure their
+/// children again, since Yoga doesn't know that they depend on them.
+fn mark_containers_dirty(node: &Rc<YogaNode>) {
+    let mut current = Some(Rc::clone(node));
+    while let Some(node) = current {
+        if is_container(&node.state.borrow()) {
+            node.node.borrow_mut().mark_dirty();
+        }
+        current = node.state.borrow().parent.as_ref().and_then(Weak::upgrade);
+    }
+}
+
+/// Remembers the direction containers lay out their children in, since
+/// they're laid out as separate Yoga trees.
+fn set_direction(node: &Rc<YogaNode>, direction: LayoutReflowDirection) {
+    let mut state = node.state.borrow_mut();
+    if state.direction != direction && is_container(&state) {
+        node.node.borrow_mut().mark_dirty();
+    }
+    state.direction = direction;
+    for child in &state.children {
+        set_direction(child, direction);
+    }
+}
+
+/// Lays out a Yoga tree, then the children of grid and flow containers in
+/// it, now that the size of each container is known.
+fn layout_tree(node: &Rc<YogaNode>, width: f32, height: f32) {
+    let direction = node.state.borrow().direction;
+    node.node.borrow_mut().calculate_layout(width, height, direction.into());
+    resume_callback_panic();
+    place_container_children(node);
+}
+
+fn place_container_children(node: &Rc<YogaNode>) {
+    let state = node.state.borrow();
+    let container = match (is_container(&state), state.container.as_ref()) {
+        (true, Some(container)) => container,
+        _ => {
+            for child in &state.children {
+                place_container_children(child);
+            }
+            return;
+        }
+    };
+
+    let (frame, content_width, content_height) = {
+        let node = node.node.borrow();
+        let frame = layout_border(&node) + layout_padding(&node);
+        let width = node.get_layout_width() - frame.left - frame.right;
+        let height = node.get_layout_height() - frame.top - frame.bottom;
+        (frame, width, height)
+    };
+
+    for child in state.children.iter().filter(|v| v.state.borrow().hidden) {
+        let mut child_state = child.state.borrow_mut();
+        child_state.placed_rect = Some(LayoutBoundingClientRectF::default());
+        child_state.text_fragments.clear();
+    }
+
+    let children = container_children(&state.children);
+    let layout = container.layout(&children, Some(content_width), Some(content_height));
+
+    for ((child, area), fragments) in children.iter().zip(layout.areas).zip(layout.text_fragments) {
+        layout_tree(&child.0, area.width, area.height.unwrap_or(f32::NAN));
+        let rect = {
+            let node = child.0.node.borrow();
+            // Yoga rounds the layout of each tree, but not where trees go.
+            LayoutBoundingClientRectF::new(
+                (frame.left + area.position.left + node.get_layout_left()).round(),
+                (frame.top + area.position.top + node.get_layout_top()).round(),
+                node.get_layout_width(),
+                node.get_layout_height()
+            )
+        };
+        let mut child_state = child.0.state.borrow_mut();
+        child_state.placed_rect = Some(rect);
+        child_state.text_fragments = fragments;
+    }
+}
+
+/// A child of a grid or flow container, laid out as the root of a Yoga tree.
+struct YogaContainerChild(Rc<YogaNode>);
+
+impl TLayoutContainerChild for YogaContainerChild {
+    fn container_item(&self) -> LayoutContainerItem {
+        self.0.state.borrow().item.clone()
+    }
+
+    fn text_metrics(&self) -> Option<Rc<TextMetrics>> {
+        match self.0.state.borrow().measure {
+            Some(LayoutMeasureContext::Text { ref metrics }) => Some(Rc::clone(metrics)),
+            _ => None
+        }
+    }
+
+    fn measure(&self, width: Option<f32>, height: Option<f32>) -> LayoutClientSizeF {
+        let direction = self.0.state.borrow().direction;
+        let mut node = self.0.node.borrow_mut();
+        node.calculate_layout(width.unwrap_or(f32::NAN), height.unwrap_or(f32::NAN), direction.into());
+        resume_callback_panic();
+        let margin = layout_margin(&node);
+        LayoutClientSizeF {
+            width: node.get_layout_width() + margin.left + margin.right,
+            height: node.get_layout_height() + margin.top + margin.bottom
+        }
+    }
+}
+
+fn container_children(children: &[Rc<YogaNode>]) -> Vec<YogaContainerChild> {
+    children
+        .iter()
+        .filter(|v| !v.state.borrow().hidden)
+        .map(|v| YogaContainerChild(Rc::clone(v)))
+        .collect()
+}
+
+fn container_context(node: &Rc<YogaNode>) -> LayoutMeasureContext {
+    let node = Rc::downgrade(node);
+    let measure = move |width: f32, width_mode: LayoutMeasureMode, height: f32, height_mode: LayoutMeasureMode| match node.upgrade() {
+        Some(node) => measure_container(&node, width, width_mode, height, height_mode),
+        None => LayoutClientSizeF::default()
+    };
+    LayoutMeasureContext::Custom { measure: Rc::new(measure) }
+}
+
+/// Measures the content box of a grid or flow container by laying out its
+/// children. Like blocks, containers fill the width available to them.
+fn measure_container(
+    node: &YogaNode,
+    width: f32,
+    width_mode: LayoutMeasureMode,
+    height: f32,
+    height_mode: LayoutMeasureMode
+) -> LayoutClientSizeF {
+    let state = node.state.borrow();
+    let container = match state.container {
+        Some(ref container) => container,
+        None => return LayoutClientSizeF::default()
+    };
+
+    let children = container_children(&state.children);
+    let available_width = match width_mode {
+        LayoutMeasureMode::Undefined => None,
+        _ => Some(width)
+    };
+    let definite_height = match height_mode {
+        LayoutMeasureMode::Exactly => Some(height),
+        _ => None
+    };
+    let layout = container.layout(&children, available_width, definite_height);
+
+    LayoutClientSizeF {
+        width: constrain(layout.size.width, width, width_mode),
+        height: constrain(layout.size.height, height, height_mode)
+    }
+}
+
+extern "C" fn measure(
+    node_ref: yoga::YGInternalNodeRef,
+    suggested_width: f32,
+    node_width_measure_mode: yoga::YGInternalMeasureMode,
+    suggested_height: f32,
+    node_height_measure_mode: yoga::YGInternalMeasureMode
+) -> yoga::YGInternalSize {
+    let size = catch_callback_panic(|| {
+        let context = yoga::Node::get_context(&node_ref)
+            .and_then(|v| v.downcast_ref::<YogaContext>())
+            .map(|v| &v.measure)
+            .expect("Invalid context when measuring nodes.");
+
+        let width_mode = measure_mode(node_width_measure_mode);
+        let height_mode = measure_mode(node_height_measure_mode);
+        context.measure(suggested_width, width_mode, suggested_height, height_mode)
+    });
+
+    // The size doesn't matter after a panic, which is resumed as soon as
+    // Yoga is done.
+    let size = size.unwrap_or_default();
+    yoga::YGInternalSize {
+        width: size.width,
+        height: size.height
+    }
+}
+
+extern "C" fn baseline(node_ref: yoga::YGInternalNodeRef, width: f32, height: f32) -> f32 {
+    let baseline = catch_callback_panic(|| {
+        let context = yoga::Node::get_context(&node_ref)
+            .and_then(|v| v.downcast_ref::<YogaContext>())
+            .expect("Invalid context when computing baselines.");
+
+        // Yoga has already laid out the node's border and padding by the time
+        // it asks for baselines, and only the root of the tree being laid out
+        // is borrowed, which never needs a baseline.
+        let frame = context
+            .node
+            .upgrade()
+            .and_then(|node| node.node.try_borrow().ok().map(|node| layout_border(&node) + layout_padding(&node)))
+            .unwrap_or_default();
+
+        context.measure.baseline(width, height, &frame)
+    });
+
+    // Yoga uses the height of leaves without a baseline of their own.
+    baseline.and_then(|v| v).unwrap_or(height)
+}
+
+/// Runs a callback Yoga called, keeping the first panic of a layout pass
+/// for `resume_callback_panic` instead of unwinding into Yoga.
+fn catch_callback_panic<F, U>(f: F) -> Option<U>
+where
+    F: FnOnce() -> U
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            CALLBACK_PANIC.with(|v| {
+                let mut stored = v.borrow_mut();
+                if stored.is_none() {
+                    *stored = Some(payload);
+                }
+            });
+            None
+        }
+    }
+}
+
+/// Resumes a panic caught in a callback, once Yoga returned from the call
+/// that made it.
+fn resume_callback_panic() {
+    if let Some(payload) = CALLBACK_PANIC.with(|v| v.borrow_mut().take()) {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn measure_mode(value: yoga::YGInternalMeasureMode) -> LayoutMeasureMode {
+    use self::yoga::YGInternalMeasureMode::*;
+
+    match value {
+        YGMeasureModeUndefined => LayoutMeasureMode::Undefined,
+        YGMeasureModeExactly => LayoutMeasureMode::Exactly,
+        YGMeasureModeAtMost => LayoutMeasureMode::AtMost
+    }
+}
+
+impl From<FlexStyle> for LayoutStyle {
+    fn from(value: FlexStyle) -> Self {
+        match value {
+            FlexStyle::AlignContent(v) => LayoutStyle::AlignContent(v.into()),
+            FlexStyle::AlignItems(v) => LayoutStyle::AlignItems(v.into()),
+            FlexStyle::AlignSelf(v) => LayoutStyle::AlignSelf(v.into()),
+            FlexStyle::AspectRatio(v) => LayoutStyle::AspectRatio(float(v.into_inner())),
+            FlexStyle::Border(v) => LayoutStyle::Border(float(v.into_inner())),
+            FlexStyle::BorderBottom(v) => LayoutStyle::BorderBottom(float(v.into_inner())),
+            FlexStyle::BorderEnd(v) => LayoutStyle::BorderEnd(float(v.into_inner())),
+            FlexStyle::BorderLeft(v) => LayoutStyle::BorderLeft(float(v.into_inner())),
+            FlexStyle::BorderRight(v) => LayoutStyle::BorderRight(float(v.into_inner())),
+            FlexStyle::BorderStart(v) => LayoutStyle::BorderStart(float(v.into_inner())),
+            FlexStyle::BorderTop(v) => LayoutStyle::BorderTop(float(v.into_inner())),
+            FlexStyle::Bottom(v) => LayoutStyle::Bottom(v.into()),
+            FlexStyle::Display(v) => LayoutStyle::Display(v.into()),
+            FlexStyle::End(v) => LayoutStyle::End(v.into()),
+            FlexStyle::Flex(v) => LayoutStyle::Flex(float(v.into_inner())),
+            FlexStyle::FlexBasis(v) => LayoutStyle::FlexBasis(v.into()),
+            FlexStyle::FlexDirection(v) => LayoutStyle::FlexDirection(v.into()),
+            FlexStyle::FlexGrow(v) => LayoutStyle::FlexGrow(float(v.into_inner())),
+            FlexStyle::FlexShrink(v) => LayoutStyle::FlexShrink(float(v.into_inner())),
+            FlexStyle::FlexWrap(v) => LayoutStyle::FlexWrap(v.into()),
+            FlexStyle::Height(v) => LayoutStyle::Height(v.into()),
+            FlexStyle::JustifyContent(v) => LayoutStyle::JustifyContent(v.into()),
+            FlexStyle::Left(v) => LayoutStyle::Left(v.into()),
+            FlexStyle::Margin(v) => LayoutStyle::Margin(v.into()),
+            FlexStyle::MarginBottom(v) => LayoutStyle::MarginBottom(v.into()),
+            FlexStyle::MarginEnd(v) => LayoutStyle::MarginEnd(v.into()),
+            FlexStyle::MarginHorizontal(v) => LayoutStyle::MarginHorizontal(v.into()),
+            FlexStyle::MarginLeft(v) => LayoutStyle::MarginLeft(v.into()),
+            FlexStyle::MarginRight(v) => LayoutStyle::MarginRight(v.into()),
+            FlexStyle::MarginStart(v) => LayoutStyle::MarginStart(v.into()),
+            FlexStyle::MarginTop(v) => LayoutStyle::MarginTop(v.into()),
+            FlexStyle::MarginVertical(v) => LayoutStyle::MarginVertical(v.into()),
+            FlexStyle::MaxHeight(v) => LayoutStyle::MaxHeight(v.into()),
+            FlexStyle::MaxWidth(v) => LayoutStyle::MaxWidth(v.into()),
+            FlexStyle::MinHeight(v) => LayoutStyle::MinHeight(v.into()),
+            FlexStyle::MinWidth(v) => LayoutStyle::MinWidth(v.into()),
+            FlexStyle::Overflow(v) => LayoutStyle::Overflow(v.into()),
+            FlexStyle::Padding(v) => LayoutStyle::Padding(v.into()),
+            FlexStyle::PaddingBottom(v) => LayoutStyle::PaddingBottom(v.into()),
+            FlexStyle::PaddingEnd(v) => LayoutStyle::PaddingEnd(v.into()),
+            FlexStyle::PaddingHorizontal(v) => LayoutStyle::PaddingHorizontal(v.into()),
+            FlexStyle::PaddingLeft(v) => LayoutStyle::PaddingLeft(v.into()),
+            FlexStyle::PaddingRight(v) => LayoutStyle::PaddingRight(v.into()),
+            FlexStyle::PaddingStart(v) => LayoutStyle::PaddingStart(v.into()),
+            FlexStyle::PaddingTop(v) => LayoutStyle::PaddingTop(v.into()),
+            FlexStyle::PaddingVertical(v) => LayoutStyle::PaddingVertical(v.into()),
+            FlexStyle::Position(v) => LayoutStyle::Position(v.into()),
+            FlexStyle::Right(v) => LayoutStyle::Right(v.into()),
+            FlexStyle::Start(v) => LayoutStyle::Start(v.into()),
+            FlexStyle::Top(v) => LayoutStyle::Top(v.into()),
+            FlexStyle::Width(v) => LayoutStyle::Width(v.into())
+        }
+    }
+}
+
+impl From<LayoutStyle> for FlexStyle {
+    fn from(value: LayoutStyle) -> Self {
+        match value {
+            LayoutStyle::AlignContent(v) => FlexStyle::AlignContent(v.into()),
+            LayoutStyle::AlignItems(v) => FlexStyle::AlignItems(v.into()),
+            LayoutStyle::AlignSelf(v) => FlexStyle::AlignSelf(v.into()),
+            LayoutStyle::AspectRatio(v) => FlexStyle::AspectRatio(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::Border(v) => FlexStyle::Border(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderBottom(v) => FlexStyle::BorderBottom(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderEnd(v) => FlexStyle::BorderEnd(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderLeft(v) => FlexStyle::BorderLeft(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderRight(v) => FlexStyle::BorderRight(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderStart(v) => FlexStyle::BorderStart(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::BorderTop(v) => FlexStyle::BorderTop(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::Bottom(v) => FlexStyle::Bottom(v.into()),
+            LayoutStyle::Display(v) => FlexStyle::Display(v.into()),
+            LayoutStyle::End(v) => FlexStyle::End(v.into()),
+            LayoutStyle::Flex(v) => FlexStyle::Flex(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::FlexBasis(v) => FlexStyle::FlexBasis(v.into()),
+            LayoutStyle::FlexDirection(v) => FlexStyle::FlexDirection(v.into()),
+            LayoutStyle::FlexGrow(v) => FlexStyle::FlexGrow(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::FlexShrink(v) => FlexStyle::FlexShrink(v.unwrap_or(f32::NAN).into()),
+            LayoutStyle::FlexWrap(v) => FlexStyle::FlexWrap(v.into()),
+            LayoutStyle::Height(v) => FlexStyle::Height(v.into()),
+            LayoutStyle::JustifyContent(v) => FlexStyle::JustifyContent(v.into()),
+            LayoutStyle::Left(v) => FlexStyle::Left(v.into()),
+            LayoutStyle::Margin(v) => FlexStyle::Margin(v.into()),
+            LayoutStyle::MarginBottom(v) => FlexStyle::MarginBottom(v.into()),
+            LayoutStyle::MarginEnd(v) => FlexStyle::MarginEnd(v.into()),
+            LayoutStyle::MarginHorizontal(v) => FlexStyle::MarginHorizontal(v.into()),
+            LayoutStyle::MarginLeft(v) => FlexStyle::MarginLeft(v.into()),
+            LayoutStyle::MarginRight(v) => FlexStyle::MarginRight(v.into()),
+            LayoutStyle::MarginStart(v) => FlexStyle::MarginStart(v.into()),
+            LayoutStyle::MarginTop(v) => FlexStyle::MarginTop(v.into()),
+            LayoutStyle::MarginVertical(v) => FlexStyle::MarginVertical(v.into()),
+            LayoutStyle::MaxHeight(v) => FlexStyle::MaxHeight(v.into()),
+            LayoutStyle::MaxWidth(v) => FlexStyle::MaxWidth(v.into()),
+            LayoutStyle::MinHeight(v) => FlexStyle::MinHeight(v.into()),
+            LayoutStyle::MinWidth(v) => FlexStyle::MinWidth(v.into()),
+            LayoutStyle::Overflow(v) => FlexStyle::Overflow(v.into()),
+            LayoutStyle::Padding(v) => FlexStyle::Padding(v.into()),
+            LayoutStyle::PaddingBottom(v) => FlexStyle::PaddingBottom(v.into()),
+            LayoutStyle::PaddingEnd(v) => FlexStyle::PaddingEnd(v.into()),
+            LayoutStyle::PaddingHorizontal(v) => FlexStyle::PaddingHorizontal(v.into()),
+            LayoutStyle::PaddingLeft(v) => FlexStyle::PaddingLeft(v.into()),
+            LayoutStyle::PaddingRight(v) => FlexStyle::PaddingRight(v.into()),
+            LayoutStyle::PaddingStart(v) => FlexStyle::PaddingStart(v.into()),
+            LayoutStyle::PaddingTop(v) => FlexStyle::PaddingTop(v.into()),
+            LayoutStyle::PaddingVertical(v) => FlexStyle::PaddingVertical(v.into()),
+            LayoutStyle::Position(v) => FlexStyle::Position(v.into()),
+            LayoutStyle::Right(v) => FlexStyle::Right(v.into()),
+            LayoutStyle::Start(v) => FlexStyle::Start(v.into()),
+            LayoutStyle::Top(v) => FlexStyle::Top(v.into()),
+            LayoutStyle::Width(v) => FlexStyle::Width(v.into())
+        }
+    }
+}
+
+impl From<StyleUnit> for LayoutStyleUnit {
+    fn from(value: StyleUnit) -> Self {
+        match value {
+            StyleUnit::UndefinedValue => LayoutStyleUnit::Undefined,
+            StyleUnit::Point(v) => LayoutStyleUnit::Point(v.into_inner()),
+            StyleUnit::Percent(v) => LayoutStyleUnit::Percent(v.into_inner()),
+            StyleUnit::Auto => LayoutStyleUnit::Auto
+        }
+    }
+}
+
+impl From<LayoutStyleUnit> for StyleUnit {
+    fn from(value: LayoutStyleUnit) -> Self {
+        match value {
+            LayoutStyleUnit::Undefined => StyleUnit::UndefinedValue,
+            LayoutStyleUnit::Point(v) => StyleUnit::Point(v.into()),
+            LayoutStyleUnit::Percent(v) => StyleUnit::Percent(v.into()),
+            LayoutStyleUnit::Auto => StyleUnit::Auto
+        }
+    }
+}
+
+impl From<Align> for LayoutAlign {
+    fn from(value: Align) -> Self {
+        match value {
+            Align::Auto => LayoutAlign::Auto,
+            Align::FlexStart => LayoutAlign::FlexStart,
+            Align::Center => LayoutAlign::Center,
+            Align::FlexEnd => LayoutAlign::FlexEnd,
+            Align::Stretch => LayoutAlign::Stretch,
+            Align::Baseline => LayoutAlign::Baseline,
+            Align::SpaceBetween => LayoutAlign::SpaceBetween,
+            Align::SpaceAround => LayoutAlign::SpaceAround
+        }
+    }
+}
+
+impl From<LayoutAlign> for Align {
+    fn from(value: LayoutAlign) -> Self {
+        match value {
+            LayoutAlign::Auto => Align::Auto,
+            LayoutAlign::FlexStart => Align::FlexStart,
+            LayoutAlign::Center => Align::Center,
+            LayoutAlign::FlexEnd => Align::FlexEnd,
+            LayoutAlign::Stretch => Align::Stretch,
+            LayoutAlign::Baseline => Align::Baseline,
+            LayoutAlign::SpaceBetween => Align::SpaceBetween,
+            LayoutAlign::SpaceAround => Align::SpaceAround
+        }
+    }
+}
+
+impl From<Justify> for LayoutJustify {
+    fn from(value: Justify) -> Self {
+        match value {
+            Justify::FlexStart => LayoutJustify::FlexStart,
+            Justify::Center => LayoutJustify::Center,
+            Justify::FlexEnd => LayoutJustify::FlexEnd,
+            Justify::SpaceBetween => LayoutJustify::SpaceBetween,
+            Justify::SpaceAround => LayoutJustify::SpaceAround
+        }
+    }
+}
+
+impl From<LayoutJustify> for Justify {
+    fn from(value: LayoutJustify) -> Self {
+        match value {
+            LayoutJustify::FlexStart => Justify::FlexStart,
+            LayoutJustify::Center => Justify::Center,
+            LayoutJustify::FlexEnd => Justify::FlexEnd,
+            LayoutJustify::SpaceBetween => Justify::SpaceBetween,
+            LayoutJustify::SpaceAround => Justify::SpaceAround
+        }
+    }
+}
+
+impl From<FlexDirection> for LayoutFlexDirection {
+    fn from(value: FlexDirection) -> Self {
+        match value {
+            FlexDirection::Column => LayoutFlexDirection::Column,
+            FlexDirection::ColumnReverse => LayoutFlexDirection::ColumnReverse,
+            FlexDirection::Row => LayoutFlexDirection::Row,
+            FlexDirection::RowReverse => LayoutFlexDirection::RowReverse
+        }
+    }
+}
+
+impl From<LayoutFlexDirection> for FlexDirection {
+    fn from(value: LayoutFlexDirection) -> Self {
+        match value {
+            LayoutFlexDirection::Column => FlexDirection::Column,
+            LayoutFlexDirection::ColumnReverse => FlexDirection::ColumnReverse,
+            LayoutFlexDirection::Row => FlexDirection::Row,
+            LayoutFlexDirection::RowReverse => FlexDirection::RowReverse
+        }
+    }
+}
+
+impl From<Wrap> for LayoutFlexWrap {
+    fn from(value: Wrap) -> Self {
+        match value {
+            Wrap::NoWrap => LayoutFlexWrap::NoWrap,
+            Wrap::Wrap => LayoutFlexWrap::Wrap,
+            Wrap::WrapReverse => LayoutFlexWrap::WrapReverse
+        }
+    }
+}
+
+impl From<LayoutFlexWrap> for Wrap {
+    fn from(value: LayoutFlexWrap) -> Self {
+        match value {
+            LayoutFlexWrap::NoWrap => Wrap::NoWrap,
+            LayoutFlexWrap::Wrap => Wrap::Wrap,
+            LayoutFlexWrap::WrapReverse => Wrap::WrapReverse
+        }
+    }
+}
+
+impl From<Display> for LayoutDisplay {
+    fn from(value: Display) -> Self {
+        match value {
+            Display::Flex => LayoutDisplay::Flex,
+            Display::None => LayoutDisplay::None
+        }
+    }
+}
+
+impl From<LayoutDisplay> for Display {
+    fn from(value: LayoutDisplay) -> Self {
+        match value {
+            LayoutDisplay::Flex => Display::Flex,
+            LayoutDisplay::None => Display::None
+        }
+    }
+}
+
+impl From<PositionType> for LayoutPositionType {
+    fn from(value: PositionType) -> Self {
+        match value {
+            PositionType::Relative => LayoutPositionType::Relative,
+            PositionType::Absolute => LayoutPositionType::Absolute
+        }
+    }
+}
+
+impl From<LayoutPositionType> for PositionType {
+    fn from(value: LayoutPositionType) -> Self {
+        match value {
+            LayoutPositionType::Relative => PositionType::Relative,
+            LayoutPositionType::Absolute => PositionType::Absolute
+        }
+    }
+}
+
+impl From<Overflow> for LayoutOverflow {
+    fn from(value: Overflow) -> Self {
+        match value {
+            Overflow::Visible => LayoutOverflow::Visible,
+            Overflow::Hidden => LayoutOverflow::Hidden,
+            Overflow::Scroll => LayoutOverflow::Scroll
+        }
+    }
+}
+
+impl From<LayoutOverflow> for Overflow {
+    fn from(value: LayoutOverflow) -> Self {
+        match value {
+            LayoutOverflow::Visible => Overflow::Visible,
+            LayoutOverflow::Hidden => Overflow::Hidden,
+            LayoutOverflow::Scroll => Overflow::Scroll
+        }
+    }
+}
+
+impl From<Direction> for LayoutReflowDirection {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::Inherit => LayoutReflowDirection::Inherit,
+            Direction::LTR => LayoutReflowDirection::LTR,
+            Direction::RTL => LayoutReflowDirection::RTL
+        }
+    }
+}
+
+impl From<LayoutReflowDirection> for Direction {
+    fn from(value: LayoutReflowDirection) -> Self {
+        match value {
+            LayoutReflowDirection::Inherit => Direction::Inherit,
+            LayoutReflowDirection::LTR => Direction::LTR,
+            LayoutReflowDirection::RTL => Direction::RTL
+        }
+    }
+}
+
+// Yoga treats NaN as undefined.
+fn float(value: f32) -> Option<f32> {
+    if value.is_nan() {
+        None
+    } else {
+        Some(value)
+    }
+}
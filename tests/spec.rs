@@ -19,10 +19,31 @@ extern crate rsx_resources;
 extern crate rsx_shared;
 extern crate rsx_stylesheet;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use rsx::{css, rsx};
 use rsx_dom::types::*;
 use rsx_dom::types::DOMNode as TTDOMNode;
+use rsx_layout::container::*;
+use rsx_layout::damage::*;
+use rsx_layout::engine::*;
+#[cfg(feature = "flexbox")]
+use rsx_layout::flexbox_engine::*;
+use rsx_layout::flow::*;
+use rsx_layout::grid::*;
+use rsx_layout::hit_testing::*;
+use rsx_layout::measure::*;
+use rsx_layout::object_fit::*;
+use rsx_layout::reflow::*;
+use rsx_layout::spatial_index::*;
+use rsx_layout::styles::*;
+use rsx_layout::text_layout::*;
+use rsx_layout::traits::*;
 use rsx_layout::types::*;
+use rsx_layout::user_agent::*;
+#[cfg(feature = "yoga")]
+use rsx_layout::yoga_engine::*;
 use rsx_resources::files::types::*;
 use rsx_resources::fonts::types::*;
 use rsx_resources::images::types::*;
@@ -39,6 +60,114 @@ type DOMNode = TTDOMNode<
     ComputedStyles,
     LayoutNode<StyleDeclarations, ComputedStyles, ResourceGroup<ImageKeysAPI, FontKeysAPI>, DOMText>
 >;
+type Layout = LayoutNode<StyleDeclarations, ComputedStyles, ResourceGroup<ImageKeysAPI, FontKeysAPI>, DOMText>;
+type EngineLayout<E> = LayoutNode<StyleDeclarations, ComputedStyles, ResourceGroup<ImageKeysAPI, FontKeysAPI>, DOMText, E>;
+// Laid out by Yoga, or by the flexbox engine when built without Yoga.
+type LayoutTree = EngineTree<DefaultLayoutEngine>;
+
+fn layout_styles(styles: Vec<FlexStyle>) -> StyleDeclarations {
+    StyleDeclarations(InlineDeclarations::from_vec(
+        styles.into_iter().map(StyleDeclaration::Layout).collect()
+    ))
+}
+
+struct EngineTree<E: TLayoutEngine> {
+    nodes: Vec<EngineLayout<E>>,
+    children: Vec<Vec<usize>>
+}
+
+impl<E: TLayoutEngine> EngineTree<E> {
+    fn new(styles: Vec<FlexStyle>) -> Self {
+        let mut root = EngineLayout::<E>::make_initial_layout_node(KnownElementName::Div);
+        root.apply_styles(&layout_styles(styles));
+        EngineTree {
+            nodes: vec![root],
+            children: vec![vec![]]
+        }
+    }
+
+    fn append(&mut self, parent: usize, styles: Vec<FlexStyle>) -> usize {
+        let mut node = EngineLayout::<E>::make_initial_layout_node(KnownElementName::Div);
+        node.apply_styles(&layout_styles(styles));
+        self.nodes[parent].append_child(&mut node);
+
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.children.push(vec![]);
+        self.children[parent].push(id);
+        id
+    }
+
+    fn reflow(&mut self, width: u32, height: u32) -> LayoutChangeSet<usize> {
+        reflow_tree(self, width, height, LayoutReflowDirection::LTR)
+    }
+}
+
+impl<E: TLayoutEngine> TLayoutTree for EngineTree<E> {
+    type NodeId = usize;
+    type LayoutNode = EngineLayout<E>;
+
+    fn root(&self) -> usize {
+        0
+    }
+
+    fn get_layout_node(&self, id: usize) -> Option<&EngineLayout<E>> {
+        self.nodes.get(id)
+    }
+
+    fn get_children(&self, id: usize) -> Vec<usize> {
+        self.children[id].clone()
+    }
+}
+
+impl<E: TLayoutEngine> TLayoutTreeMut for EngineTree<E> {
+    fn get_layout_node_mut(&mut self, id: usize) -> Option<&mut EngineLayout<E>> {
+        self.nodes.get_mut(id)
+    }
+}
+
+fn empty_resources() -> ResourceGroup<ImageKeysAPI, FontKeysAPI> {
+    let files = FileCache::new().unwrap();
+    let images = ImageCache::new(ImageKeysAPI::new(())).unwrap();
+    let fonts = FontCache::new(FontKeysAPI::new(())).unwrap();
+    ResourceGroup::new(files, images, fonts)
+}
+
+fn font_resources() -> ResourceGroup<ImageKeysAPI, FontKeysAPI> {
+    let mut files = FileCache::new().unwrap();
+
+    let image_path = "tests/fixtures/Quantum.png";
+    assert!(files.add_file(image_path).is_ok());
+
+    let font_path = "tests/fixtures/FreeSans.ttf";
+    assert!(files.add_file(font_path).is_ok());
+
+    let image_keys = ImageKeysAPI::new(());
+    let mut images = ImageCache::new(image_keys).unwrap();
+
+    let font_keys = FontKeysAPI::new(());
+    let mut fonts = FontCache::new(font_keys).unwrap();
+
+    let image_id = ImageId::new("logo");
+    let image_bytes = files.get_file(image_path).unwrap();
+    images.add_raw(image_id, image_bytes).unwrap();
+
+    let font_id = FontId::new("FreeSans");
+    let font_bytes = files.get_file(font_path).unwrap();
+    fonts.add_raw(font_id, font_bytes, 0).unwrap();
+
+    ResourceGroup::new(files, images, fonts)
+}
+
+fn absolute(left: f32, top: f32, width: f32, height: f32) -> Vec<FlexStyle> {
+    vec![
+        FlexStyle::Position(PositionType::Absolute),
+        FlexStyle::Left(StyleUnit::Point(left.into())),
+        FlexStyle::Top(StyleUnit::Point(top.into())),
+        FlexStyle::Width(StyleUnit::Point(width.into())),
+        FlexStyle::Height(StyleUnit::Point(height.into())),
+    ]
+}
 
 #[test]
 fn test_reflow_simple() {
@@ -374,3 +503,1820 @@ fn test_reflow_example_2() {
         LayoutBoundingClientRect::new(120, 48, 360, 25)
     );
 }
+
+#[test]
+fn test_pixel_snapping() {
+    let rect = LayoutBoundingClientRectF::new(10.4, 20.6, 50.4, 60.4);
+
+    assert_eq!(
+        rect.snap(LayoutPixelSnapping::Floor),
+        LayoutBoundingClientRect::new(10, 20, 50, 60)
+    );
+
+    assert_eq!(
+        rect.snap(LayoutPixelSnapping::Round),
+        LayoutBoundingClientRect::new(10, 21, 50, 60)
+    );
+
+    assert_eq!(
+        rect.snap(LayoutPixelSnapping::Cumulative),
+        LayoutBoundingClientRect::new(10, 21, 51, 60)
+    );
+}
+
+/// Places nodes exactly at their offsets and sizes, without rounding like
+/// Yoga does, to check how fractional layouts get snapped.
+#[derive(Debug, Default)]
+struct FractionalLayoutEngine(LayoutBoundingClientRectF);
+
+impl TLayoutEngine for FractionalLayoutEngine {
+    fn new() -> Self {
+        FractionalLayoutEngine::default()
+    }
+
+    fn child_count(&self) -> usize {
+        0
+    }
+
+    fn insert_child(&mut self, _: &mut Self, _: usize) {}
+
+    fn remove_child(&mut self, _: &mut Self) {}
+
+    fn apply_style(&mut self, style: &LayoutStyle) {
+        let rect = &mut self.0;
+        match *style {
+            LayoutStyle::Left(LayoutStyleUnit::Point(value)) => rect.position.left = value,
+            LayoutStyle::Top(LayoutStyleUnit::Point(value)) => rect.position.top = value,
+            LayoutStyle::Width(LayoutStyleUnit::Point(value)) => rect.size.width = value,
+            LayoutStyle::Height(LayoutStyleUnit::Point(value)) => rect.size.height = value,
+            _ => {}
+        }
+    }
+
+    fn set_measure_context(&mut self, _: Option<LayoutMeasureContext>) {}
+
+    fn mark_dirty(&mut self) {}
+
+    fn set_container(&mut self, _: Option<LayoutContainer>) {}
+
+    fn set_container_item(&mut self, _: LayoutContainerItem) {}
+
+    fn calculate_layout(&mut self, _: f32, _: f32, _: LayoutReflowDirection) {}
+
+    fn get_layout_rect(&self) -> LayoutBoundingClientRectF {
+        self.0
+    }
+
+    fn get_layout_margin(&self) -> LayoutBoxEdgesF {
+        LayoutBoxEdgesF::default()
+    }
+
+    fn get_layout_border(&self) -> LayoutBoxEdgesF {
+        LayoutBoxEdgesF::default()
+    }
+
+    fn get_layout_padding(&self) -> LayoutBoxEdgesF {
+        LayoutBoxEdgesF::default()
+    }
+
+    fn get_text_fragments(&self) -> Vec<LayoutTextFragment> {
+        vec![]
+    }
+}
+
+#[test]
+fn test_cumulative_pixel_snapping() {
+    let mut tree = EngineTree::<FractionalLayoutEngine>::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let a = tree.append(0, absolute(0.4, 0.4, 50.0, 50.0));
+    let b = tree.append(a, absolute(0.4, 0.4, 20.3, 20.3));
+    let c = tree.append(b, absolute(0.4, 0.4, 10.0, 10.0));
+    tree.append(c, absolute(0.4, 0.4, 5.0, 5.0));
+    for node in &mut tree.nodes {
+        node.set_pixel_snapping(LayoutPixelSnapping::Cumulative);
+    }
+    tree.reflow(100, 100);
+
+    // Global edges are rounded, so offsets that round down on their own
+    // still add up once nested deep enough.
+    let global = tree.nodes[a..].iter().map(|v| v.get_global_bounding_client_rect()).collect::<Vec<_>>();
+    assert_eq!(
+        global,
+        vec![
+            LayoutBoundingClientRect::new(0, 0, 50, 50),
+            LayoutBoundingClientRect::new(1, 1, 20, 20),
+            LayoutBoundingClientRect::new(1, 1, 10, 10),
+            LayoutBoundingClientRect::new(2, 2, 5, 5),
+        ]
+    );
+
+    let local = tree.nodes[a..].iter().map(|v| v.get_local_bounding_client_rect()).collect::<Vec<_>>();
+    assert_eq!(
+        local,
+        vec![
+            LayoutBoundingClientRect::new(0, 0, 50, 50),
+            LayoutBoundingClientRect::new(1, 1, 20, 20),
+            LayoutBoundingClientRect::new(0, 0, 10, 10),
+            LayoutBoundingClientRect::new(1, 1, 5, 5),
+        ]
+    );
+}
+
+#[test]
+fn test_negative_client_positions() {
+    let rect = LayoutBoundingClientRectF::new(-10.5, -20.5, 50.0, 60.0);
+
+    assert_eq!(
+        rect.snap(LayoutPixelSnapping::Floor),
+        LayoutBoundingClientRect::new(-11, -21, 50, 60)
+    );
+
+    let parent = LayoutClientPosition { left: 5, top: 5 };
+    let local = LayoutBoundingClientRect::new(-10, -20, 50, 60);
+
+    assert_eq!(local + parent, LayoutBoundingClientRect::new(-5, -15, 50, 60));
+    assert!(local.contains_point((1, 1)));
+}
+
+#[test]
+fn test_box_rects() {
+    let mut stylesheet = css!("tests/fixtures/test_2.css");
+
+    let mut root = Layout::make_initial_layout_node(KnownElementName::Div);
+    root.apply_styles(&stylesheet.take(".root"));
+    root.reflow_subtree(1000, 1000, LayoutReflowDirection::LTR);
+
+    assert_eq!(
+        root.get_local_box_rect(LayoutBoxKind::Margin),
+        LayoutBoundingClientRect::new(0, 0, 500, 120)
+    );
+
+    assert_eq!(
+        root.get_local_box_rect(LayoutBoxKind::Border),
+        LayoutBoundingClientRect::new(0, 0, 500, 120)
+    );
+
+    assert_eq!(
+        root.get_local_box_rect(LayoutBoxKind::Padding),
+        LayoutBoundingClientRect::new(0, 0, 500, 120)
+    );
+
+    assert_eq!(
+        root.get_local_box_rect(LayoutBoxKind::Content),
+        LayoutBoundingClientRect::new(20, 20, 460, 80)
+    );
+
+    root.set_computed_client_position(LayoutClientPosition { left: 100, top: 100 });
+    assert_eq!(root.offset_from_page((150, 150)), (50, 50));
+}
+
+#[test]
+fn test_coordinate_conversions() {
+    let rect = LayoutBoundingClientRect::new(10, 20, 50, 60);
+
+    assert_eq!(rect.client_from_page((15, 30)), (5, 10));
+    assert_eq!(rect.offset_from_page((15, 30)), (5, 10));
+    assert_eq!(rect.client_from_page((5, 5)), (0, 0));
+    assert_eq!(rect.offset_from_page((5, 5)), (-5i32 as u32, -15i32 as u32));
+    assert_eq!(
+        rect.client_from_page((u32::max_value(), u32::max_value())),
+        (u32::max_value() - 10, u32::max_value() - 20)
+    );
+    assert_eq!(rect.offset_from_page((u32::max_value(), 20)), (i32::max_value() as u32, 0));
+    assert_eq!(rect.local_from_global((5, 5)), (-5, -15));
+    assert_eq!(rect.global_from_local(rect.local_from_global((5, 5))), (5, 5));
+
+    let viewport = LayoutViewport::new(
+        LayoutClientPosition { left: 0, top: 100 },
+        LayoutClientSize { width: 800, height: 600 }
+    );
+
+    assert_eq!(viewport.page_from_client((15, 30)), (15, 130));
+    assert_eq!(viewport.client_from_page(viewport.page_from_client((15, 30))), (15, 30));
+}
+
+#[test]
+fn test_hit_testing() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let a = tree.append(0, absolute(0.0, 0.0, 50.0, 50.0));
+    let b = tree.append(0, absolute(25.0, 25.0, 50.0, 50.0));
+    let c = tree.append(b, absolute(0.0, 0.0, 10.0, 10.0));
+    tree.reflow(100, 100);
+
+    assert_eq!(hit_test(&tree, (30, 30)), Some(c));
+    assert_eq!(hit_test_all(&tree, (30, 30)), vec![c, b, a, 0]);
+    assert_eq!(hit_test(&tree, (0, 0)), Some(a));
+    assert_eq!(hit_test(&tree, (60, 60)), Some(b));
+    assert_eq!(hit_test(&tree, (100, 100)), None);
+}
+
+#[test]
+fn test_hit_testing_clipping() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let mut styles = absolute(25.0, 25.0, 50.0, 50.0);
+    styles.push(FlexStyle::Overflow(Overflow::Hidden));
+    let a = tree.append(0, styles);
+    let b = tree.append(a, absolute(40.0, 40.0, 20.0, 20.0));
+    let mut styles = absolute(0.0, 0.0, 10.0, 10.0);
+    styles.push(FlexStyle::Display(Display::None));
+    tree.append(0, styles);
+    tree.reflow(100, 100);
+
+    assert_eq!(hit_test(&tree, (70, 70)), Some(b));
+    assert_eq!(hit_test(&tree, (80, 80)), Some(0));
+    assert_eq!(hit_test(&tree, (5, 5)), Some(0));
+}
+
+#[test]
+fn test_hit_testing_scrolling() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let mut styles = absolute(0.0, 0.0, 50.0, 50.0);
+    styles.push(FlexStyle::Overflow(Overflow::Scroll));
+    let a = tree.append(0, styles);
+    let b = tree.append(a, absolute(0.0, 60.0, 20.0, 20.0));
+    let c = tree.append(a, absolute(0.0, 0.0, 20.0, 20.0));
+    tree.reflow(100, 100);
+
+    assert_eq!(hit_test(&tree, (10, 10)), Some(c));
+    assert_eq!(hit_test(&tree, (10, 70)), Some(0));
+
+    // Scrolled down, b is painted inside a, and c above it, where it's
+    // clipped away.
+    tree.nodes[a].set_scroll_offset(LayoutClientPosition { left: 0, top: 50 });
+    assert_eq!(hit_test(&tree, (10, 15)), Some(b));
+    assert_eq!(hit_test(&tree, (10, 5)), Some(a));
+    assert_eq!(hit_test(&tree, (10, 70)), Some(0));
+}
+
+#[test]
+fn test_spatial_index() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let a = tree.append(0, absolute(0.0, 0.0, 100.0, 100.0));
+    let b = tree.append(0, absolute(500.0, 500.0, 100.0, 100.0));
+    let c = tree.append(b, absolute(400.0, 400.0, 10.0, 10.0));
+    tree.reflow(1000, 1000);
+
+    let mut index = LayoutSpatialIndex::from_tree(&tree, 64);
+    assert_eq!(index.len(), 4);
+
+    let mut found = index.query_rect(&LayoutBoundingClientRect::new(50, 50, 10, 10));
+    found.sort();
+    assert_eq!(found, vec![0, a]);
+
+    let mut found = index.query_rect(&LayoutBoundingClientRect::new(550, 550, 500, 500));
+    found.sort();
+    assert_eq!(found, vec![0, b, c]);
+
+    let mut found = index.query_point((905, 914), 5);
+    found.sort();
+    assert_eq!(found, vec![0, c]);
+
+    // Only the part of a node that isn't clipped by its ancestors is indexed,
+    // including for nodes that didn't move when the clip changed.
+    let mut styles = absolute(500.0, 500.0, 100.0, 100.0);
+    styles.push(FlexStyle::Overflow(Overflow::Hidden));
+    tree.nodes[b].apply_styles(&layout_styles(styles));
+    let d = tree.append(b, absolute(50.0, 50.0, 100.0, 100.0));
+    let changes = tree.reflow(1000, 1000);
+    assert_eq!(changes.changed, vec![d]);
+    assert_eq!(changes.tainted, vec![b, d]);
+
+    index.update_changed(&tree, &changes);
+    assert_eq!(index.get_rect(c), None);
+    assert_eq!(index.get_rect(d), Some(LayoutBoundingClientRect::new(550, 550, 50, 50)));
+    assert_eq!(index.query_point((905, 914), 5), vec![0]);
+}
+
+#[test]
+fn test_spatial_index_empty_cells() {
+    let mut index = LayoutSpatialIndex::new(0);
+    index.insert(1, LayoutBoundingClientRect::new(0, 0, 10, 10));
+    index.insert(2, LayoutBoundingClientRect::new(300, 300, 10, 10));
+    assert_eq!(index.query_rect(&LayoutBoundingClientRect::new(5, 5, 1, 1)), vec![1]);
+    assert_eq!(index.query_point((305, 305), 0), vec![2]);
+}
+
+#[test]
+fn test_line_breaking() {
+    let segment = |width_f, hard_break| TextSegmentMetrics {
+        width_f,
+        trailing_width_f: 5.0,
+        hard_break,
+        joins_next: false
+    };
+
+    let metrics = TextMetrics {
+        segments: vec![
+            segment(30.0, false),
+            segment(30.0, false),
+            segment(30.0, true),
+            segment(50.0, false),
+        ],
+        spans: vec![],
+        line_height_f: 10.0,
+        ascent_f: 8.0,
+        max_lines: None,
+        ellipsis_width_f: None,
+        first_char: None,
+        last_char: None
+    };
+
+    assert_eq!(metrics.measure(None), (100.0, 20.0));
+    assert_eq!(metrics.measure(Some(65.0)), (65.0, 30.0));
+    assert_eq!(metrics.measure(Some(20.0)), (50.0, 40.0));
+
+    let lines = metrics.break_lines(Some(65.0));
+    assert_eq!(lines[0].segments, 0..2);
+    assert_eq!(lines[1].segments, 2..3);
+    assert_eq!(lines[2].segments, 3..4);
+
+    // Clamped text is only as tall as its visible lines, and the last one
+    // makes room for the ellipsis.
+    let clamped = TextMetrics {
+        max_lines: Some(2),
+        ellipsis_width_f: Some(10.0),
+        ..metrics.clone()
+    };
+    assert_eq!(clamped.measure(Some(65.0)), (65.0, 20.0));
+    let lines = clamped.break_lines(Some(65.0));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].ellipsis, false);
+    assert_eq!(
+        lines[1],
+        TextLine {
+            segments: 2..3,
+            width_f: 40.0,
+            ellipsis: true
+        }
+    );
+
+    // Segments that don't fit next to the ellipsis are dropped.
+    let lines = clamped.break_lines(Some(35.0));
+    assert_eq!(lines[0].segments, 0..1);
+    assert_eq!(lines[1].segments, 1..1);
+    assert_eq!(lines[1].width_f, 10.0);
+}
+
+#[test]
+fn test_object_fit() {
+    let content_box = LayoutBoundingClientRectF::new(10.0, 10.0, 100.0, 50.0);
+    let image_size = LayoutClientSizeF {
+        width: 200.0,
+        height: 200.0
+    };
+    let position = LayoutObjectPosition::default();
+
+    let fill = LayoutImagePlacement::new(LayoutObjectFit::Fill, position, &content_box, image_size);
+    assert_eq!(fill.destination, content_box);
+    assert_eq!(fill.source, LayoutBoundingClientRectF::new(0.0, 0.0, 200.0, 200.0));
+
+    let contain = LayoutImagePlacement::new(LayoutObjectFit::Contain, position, &content_box, image_size);
+    assert_eq!(contain.destination, LayoutBoundingClientRectF::new(35.0, 10.0, 50.0, 50.0));
+    assert_eq!(contain.source, LayoutBoundingClientRectF::new(0.0, 0.0, 200.0, 200.0));
+
+    let cover = LayoutImagePlacement::new(LayoutObjectFit::Cover, position, &content_box, image_size);
+    assert_eq!(cover.destination, content_box);
+    assert_eq!(cover.source, LayoutBoundingClientRectF::new(0.0, 50.0, 200.0, 100.0));
+
+    let none = LayoutImagePlacement::new(LayoutObjectFit::None, position, &content_box, image_size);
+    assert_eq!(none.destination, content_box);
+    assert_eq!(none.source, LayoutBoundingClientRectF::new(50.0, 75.0, 100.0, 50.0));
+}
+
+#[test]
+fn test_custom_measure() {
+    let mut registry = LayoutMeasureRegistry::new();
+    registry.register(
+        "chart",
+        Rc::new(|width, width_mode, _, _| {
+            let width = match width_mode {
+                LayoutMeasureMode::Undefined => 120.0,
+                _ => f32::min(width, 120.0)
+            };
+            LayoutClientSizeF { width, height: 80.0 }
+        })
+    );
+
+    let resources = empty_resources();
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let chart = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart)]);
+    tree.nodes[chart].measure_self_as_normal(&resources, &registry.get("chart"));
+    tree.reflow(100, 100);
+
+    assert_eq!(
+        tree.nodes[chart].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 100, 80)
+    );
+
+    assert!(registry.unregister("chart").is_some());
+    assert!(registry.get("chart").is_none());
+    assert!(LayoutMeasureRegistry::new().get("chart").is_none());
+}
+
+#[test]
+fn test_custom_measure_baseline() {
+    let mut registry = LayoutMeasureRegistry::new();
+    registry.register("chart", Rc::new(|_, _, _, _| LayoutClientSizeF { width: 40.0, height: 80.0 }));
+
+    let resources = empty_resources();
+    let mut tree = LayoutTree::new(vec![
+        FlexStyle::Width(StyleUnit::Point(100.0.into())),
+        FlexStyle::Height(StyleUnit::Point(100.0.into())),
+        FlexStyle::FlexDirection(FlexDirection::Row),
+        FlexStyle::AlignItems(Align::Baseline),
+    ]);
+    let chart = tree.append(0, vec![]);
+    let square = tree.append(
+        0,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(20.0.into())),
+            FlexStyle::Height(StyleUnit::Point(20.0.into())),
+        ]
+    );
+    tree.nodes[chart].measure_self_as_normal(&resources, &registry.get("chart"));
+    tree.reflow(100, 100);
+
+    // Custom measured nodes don't have a baseline of their own, so they're
+    // aligned by their bottom edge like any other box.
+    assert_eq!(
+        tree.nodes[chart].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 40, 80)
+    );
+    assert_eq!(
+        tree.nodes[square].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(40, 60, 20, 20)
+    );
+}
+
+#[test]
+#[should_panic(expected = "Chart failed to measure.")]
+fn test_custom_measure_panics_after_layout() {
+    let mut registry = LayoutMeasureRegistry::new();
+    registry.register("chart", Rc::new(|_, _, _, _| -> LayoutClientSizeF { panic!("Chart failed to measure.") }));
+
+    let resources = empty_resources();
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let chart = tree.append(0, vec![]);
+    tree.nodes[chart].measure_self_as_normal(&resources, &registry.get("chart"));
+    tree.reflow(100, 100);
+}
+
+#[test]
+fn test_text_baseline() {
+    let resources = font_resources();
+
+    let mut tree = LayoutTree::new(vec![
+        FlexStyle::Width(StyleUnit::Point(300.0.into())),
+        FlexStyle::Height(StyleUnit::Point(100.0.into())),
+        FlexStyle::FlexDirection(FlexDirection::Row),
+        FlexStyle::AlignItems(Align::Baseline),
+    ]);
+    let text = tree.append(0, vec![FlexStyle::PaddingTop(StyleUnit::Point(6.0.into())), FlexStyle::BorderTop(4.0.into())]);
+    let square = tree.append(
+        0,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(20.0.into())),
+            FlexStyle::Height(StyleUnit::Point(20.0.into())),
+        ]
+    );
+    tree.nodes[text].measure_self_as_spans(&resources, &[LayoutTextSpan::new("Hello", 12.0)]);
+    tree.reflow(300, 100);
+
+    // The baseline is one ascent below the top of the content box,
+    // and the square sits on it with its bottom edge.
+    let ascent = tree.nodes[text].get_text_layout().metrics().ascent_f;
+    assert!(ascent > 0.0);
+    let baseline = tree.nodes[text].get_local_bounding_client_rect_f().position.top + 10.0 + ascent;
+    let square_rect = tree.nodes[square].get_local_bounding_client_rect_f();
+    assert!((square_rect.position.top + square_rect.size.height - baseline).abs() <= 1.0);
+    assert!((tree.nodes[text].get_line_boxes()[0].baseline_f - baseline).abs() < 0.01);
+}
+
+#[test]
+fn test_reset_custom_styles() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let child = tree.append(
+        0,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(50.0.into())),
+            FlexStyle::Height(StyleUnit::Point(20.0.into())),
+        ]
+    );
+    tree.reflow(100, 100);
+
+    assert_eq!(
+        tree.nodes[child].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 50, 20)
+    );
+
+    tree.nodes[child].reset_custom_styles(KnownElementName::Div);
+    assert!(tree.nodes[child].is_tainted());
+    tree.reflow(100, 100);
+
+    assert_eq!(
+        tree.nodes[child].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 100, 0)
+    );
+}
+
+#[test]
+fn test_user_agent_styles() {
+    let row = LayoutStyle::FlexDirection(LayoutFlexDirection::Row);
+    let column = LayoutStyle::FlexDirection(LayoutFlexDirection::Column);
+
+    let div = Layout::make_initial_layout_node(KnownElementName::Div);
+    assert!(div.get_styles().is_empty());
+
+    let mut text = Layout::make_initial_layout_node(KnownElementName::Text);
+    assert_eq!(text.get_styles().get(&row), Some(&row));
+
+    text.apply_styles(&layout_styles(vec![FlexStyle::FlexDirection(FlexDirection::Column)]));
+    assert_eq!(text.get_styles().get(&row), Some(&column));
+
+    text.reset_custom_styles(KnownElementName::Text);
+    assert_eq!(text.get_styles().get(&row), Some(&row));
+
+    let mut div = div;
+    div.reset_custom_styles(KnownElementName::Text);
+    assert_eq!(div.get_styles().get(&row), Some(&row));
+
+    let user_agent = LayoutUserAgentStyles::new();
+    user_agent.set(KnownElementName::Text, vec![column]);
+    assert!(div.set_user_agent_styles(user_agent.clone()));
+    assert_eq!(div.get_styles().get(&row), Some(&column));
+    assert_eq!(div.get_user_agent_styles(), vec![column]);
+    assert_eq!(Layout::make_initial_layout_node(KnownElementName::Text).get_user_agent_styles(), vec![row]);
+
+    let text = Layout::make_layout_node_with_user_agent_styles(KnownElementName::Text, user_agent.clone());
+    assert_eq!(text.get_styles().get(&row), Some(&column));
+
+    user_agent.reset(&KnownElementName::Text);
+    assert_eq!(div.get_user_agent_styles(), vec![row]);
+    assert!(div.refresh_user_agent_styles());
+    assert_eq!(div.get_styles().get(&row), Some(&row));
+    assert!(!div.refresh_user_agent_styles());
+}
+
+#[test]
+fn test_user_agent_styles_keep_custom_styles() {
+    let row = LayoutStyle::FlexDirection(LayoutFlexDirection::Row);
+    let wrap = LayoutStyle::FlexWrap(LayoutFlexWrap::Wrap);
+    let user_agent = LayoutUserAgentStyles::new();
+
+    let mut text = Layout::make_layout_node_with_user_agent_styles(KnownElementName::Text, user_agent.clone());
+    text.apply_styles(&layout_styles(vec![FlexStyle::FlexDirection(FlexDirection::Column)]));
+
+    user_agent.set(KnownElementName::Text, vec![row, wrap]);
+    assert!(text.refresh_user_agent_styles());
+    assert!(text.is_tainted());
+    assert_eq!(text.get_styles().get(&row), Some(&LayoutStyle::FlexDirection(LayoutFlexDirection::Column)));
+    assert_eq!(text.get_styles().get(&wrap), Some(&wrap));
+
+    text.reset_custom_styles(KnownElementName::Text);
+    assert_eq!(text.get_styles().get(&row), Some(&row));
+    assert_eq!(text.get_styles().get(&wrap), Some(&wrap));
+}
+
+#[test]
+fn test_refresh_user_agent_styles() {
+    let user_agent = LayoutUserAgentStyles::new();
+    let root = Layout::make_layout_node_with_user_agent_styles(KnownElementName::Div, user_agent.clone());
+    let mut tree = LayoutTree {
+        nodes: vec![root],
+        children: vec![vec![]]
+    };
+    let mut text = Layout::make_layout_node_with_user_agent_styles(KnownElementName::Text, user_agent.clone());
+    tree.nodes[0].append_child(&mut text);
+    tree.nodes.push(text);
+    tree.children.push(vec![]);
+    tree.children[0].push(1);
+    tree.reflow(100, 100);
+
+    assert!(!refresh_user_agent_styles(&mut tree));
+
+    let column = LayoutStyle::FlexDirection(LayoutFlexDirection::Column);
+    user_agent.set(KnownElementName::Text, vec![column]);
+    assert!(refresh_user_agent_styles(&mut tree));
+    assert!(!tree.nodes[0].is_tainted());
+    assert!(tree.nodes[1].is_tainted());
+    assert_eq!(tree.nodes[1].get_styles().get(&column), Some(&column));
+}
+
+#[test]
+fn test_style_snapshot_diff() {
+    let mut before = LayoutStyleSnapshot::new();
+    before.set(LayoutStyle::Width(LayoutStyleUnit::Point(50.0)));
+    before.set(LayoutStyle::Height(LayoutStyleUnit::Point(20.0)));
+
+    let mut after = LayoutStyleSnapshot::new();
+    after.set(LayoutStyle::Width(LayoutStyleUnit::Point(50.0)));
+    after.set(LayoutStyle::FlexGrow(Some(1.0)));
+
+    assert_eq!(
+        before.diff(&after),
+        vec![
+            LayoutStyle::Height(LayoutStyleUnit::Auto),
+            LayoutStyle::FlexGrow(Some(1.0)),
+        ]
+    );
+
+    assert!(after.diff(&after.clone()).is_empty());
+}
+
+#[test]
+fn test_apply_styles_diffing() {
+    let styles = layout_styles(vec![
+        FlexStyle::Width(StyleUnit::Point(50.0.into())),
+        FlexStyle::Height(StyleUnit::Point(20.0.into())),
+    ]);
+
+    let mut node = Layout::make_initial_layout_node(KnownElementName::Div);
+    assert!(node.apply_layout_styles(&styles));
+    assert!(!node.apply_layout_styles(&styles));
+
+    let styles = layout_styles(vec![FlexStyle::Width(StyleUnit::Point(50.0.into()))]);
+    assert!(!node.apply_layout_styles(&styles));
+    assert_eq!(
+        node.get_styles().get(&LayoutStyle::Height(LayoutStyleUnit::Auto)),
+        Some(&LayoutStyle::Height(LayoutStyleUnit::Point(20.0)))
+    );
+
+    let styles = layout_styles(vec![FlexStyle::Height(StyleUnit::Point(30.0.into()))]);
+    assert!(node.apply_layout_styles(&styles));
+    assert_eq!(
+        node.get_styles().get(&LayoutStyle::Width(LayoutStyleUnit::Auto)),
+        Some(&LayoutStyle::Width(LayoutStyleUnit::Point(50.0)))
+    );
+}
+
+#[test]
+fn test_remeasure_text_relayouts() {
+    let resources = font_resources();
+    let computed_styles = ComputedStyles::default();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 100.0));
+    let text = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart)]);
+
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello"), &computed_styles);
+    tree.reflow(1000, 100);
+    let short = tree.nodes[text].get_local_bounding_client_rect();
+    assert!(short.size.width > 0);
+
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello world, hello world"), &computed_styles);
+    tree.reflow(1000, 100);
+    let long = tree.nodes[text].get_local_bounding_client_rect();
+    assert!(long.size.width > short.size.width);
+    assert_eq!(long.size.height, short.size.height);
+
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello"), &computed_styles);
+    tree.reflow(1000, 100);
+    assert_eq!(tree.nodes[text].get_local_bounding_client_rect(), short);
+}
+
+#[test]
+fn test_remeasure_text_wraps() {
+    let resources = font_resources();
+    let computed_styles = ComputedStyles::default();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let text = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart)]);
+
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello world"), &computed_styles);
+    tree.reflow(1000, 1000);
+    let single_line = tree.nodes[text].get_local_bounding_client_rect();
+
+    tree.nodes[0].apply_styles(&layout_styles(absolute(0.0, 0.0, 1.0, 1000.0)));
+    tree.reflow(1000, 1000);
+    let wrapped = tree.nodes[text].get_local_bounding_client_rect();
+    assert_eq!(wrapped.size.height, single_line.size.height * 2);
+    assert_eq!(tree.nodes[text].get_text_lines().len(), 2);
+
+    // Measuring the same text the same way again doesn't shape it again.
+    let metrics = Rc::clone(tree.nodes[text].get_text_layout().metrics());
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello world"), &computed_styles);
+    assert!(Rc::ptr_eq(&metrics, tree.nodes[text].get_text_layout().metrics()));
+    assert!(!tree.nodes[text].is_tainted());
+
+    // Changing how text is laid out measures it again right away.
+    tree.nodes[text].set_white_space(&resources, LayoutWhiteSpace::NoWrap);
+    assert!(!Rc::ptr_eq(&metrics, tree.nodes[text].get_text_layout().metrics()));
+    assert!(tree.nodes[text].is_tainted());
+    tree.reflow(1000, 1000);
+    assert_eq!(tree.nodes[text].get_text_lines().len(), 1);
+
+    let metrics = Rc::clone(tree.nodes[text].get_text_layout().metrics());
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("Hello world"), &computed_styles);
+    assert!(Rc::ptr_eq(&metrics, tree.nodes[text].get_text_layout().metrics()));
+}
+
+#[test]
+fn test_line_boxes() {
+    let resources = font_resources();
+    let computed_styles = ComputedStyles::default();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let mut styles = absolute(20.0, 30.0, 100.0, 200.0);
+    styles.push(FlexStyle::Padding(StyleUnit::Point(5.0.into())));
+    let text = tree.append(0, styles);
+
+    tree.nodes[text].measure_self_as_text(&resources, &DOMText::from("one two three four five six seven"), &computed_styles);
+    tree.reflow(1000, 1000);
+
+    let content_box = tree.nodes[text].get_global_box_rect_f(LayoutBoxKind::Content);
+    let metrics = Rc::clone(tree.nodes[text].get_text_layout().metrics());
+    let line_boxes = tree.nodes[text].get_line_boxes();
+    assert!(line_boxes.len() > 1);
+    assert_eq!(line_boxes.len(), tree.nodes[text].get_text_lines().len());
+
+    // Lines are stacked from the top left corner of the global content box,
+    // each with its baseline one ascent below its top.
+    let mut top = content_box.position.top;
+    for line_box in &line_boxes {
+        assert_eq!(line_box.rect.position.left, content_box.position.left);
+        assert_eq!(line_box.rect.position.top, top);
+        assert!((line_box.rect.size.height - metrics.line_height_f).abs() < 0.01);
+        assert!(line_box.rect.size.width <= content_box.size.width + 0.01);
+        assert_eq!(line_box.baseline_f, top + metrics.ascent_f);
+        top += line_box.rect.size.height;
+    }
+
+    // Glyph ranges of consecutive lines follow each other, and together
+    // cover the glyphs of every segment.
+    let shaped_text = tree.nodes[text].get_shaped_text();
+    let segments = shaped_text.layout().segments();
+    let glyph_count = segments.iter().map(|v| v.shaped_text.glyphs().len()).sum::<usize>();
+    assert_eq!(line_boxes[0].glyph_range.start, 0);
+    assert_eq!(line_boxes[line_boxes.len() - 1].glyph_range.end, glyph_count);
+    for pair in line_boxes.windows(2) {
+        assert_eq!(pair[0].glyph_range.end, pair[1].glyph_range.start);
+        assert_eq!(pair[0].segments.end, pair[1].segments.start);
+    }
+
+    // Each run holds the glyphs of one segment of its line, left to right,
+    // with glyph positions relative to the run's origin.
+    for line_box in &line_boxes {
+        let mut glyph_start = line_box.glyph_range.start;
+        let mut left = line_box.rect.position.left;
+        assert_eq!(line_box.runs.len(), line_box.segments.len());
+        for run in &line_box.runs {
+            assert!(line_box.segments.start <= run.segment && run.segment < line_box.segments.end);
+            assert!(!run.is_rtl);
+            assert_eq!(run.glyph_range.start, glyph_start);
+            assert_eq!(run.glyph_range.len(), shaped_text.run_text(run).glyphs().len());
+            assert!(run.origin.0 >= left);
+            assert_eq!(run.origin.1, line_box.baseline_f - metrics.ascent_f);
+            assert_eq!(run.glyph_origin((2.0, 3.0)), (run.origin.0 + 2.0, run.origin.1 + 3.0));
+            glyph_start = run.glyph_range.end;
+            left = run.origin.0 + metrics.segments[run.segment].width_f;
+        }
+        assert_eq!(glyph_start, line_box.glyph_range.end);
+    }
+}
+
+#[test]
+fn test_remeasure_image_relayouts() {
+    let resources = font_resources();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let image = tree.append(
+        0,
+        vec![
+            FlexStyle::AlignSelf(Align::FlexStart),
+            FlexStyle::Width(StyleUnit::Point(80.0.into())),
+        ]
+    );
+
+    tree.nodes[image].measure_self_as_image(&resources, &DOMText::from("logo"), &());
+    tree.reflow(1000, 1000);
+    assert_eq!(
+        tree.nodes[image].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 80, 83)
+    );
+
+    tree.nodes[image].measure_self_as_image(&resources, &DOMText::from("missing"), &());
+    tree.reflow(1000, 1000);
+    assert_eq!(
+        tree.nodes[image].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 80, 80)
+    );
+}
+
+#[test]
+fn test_image_aspect_ratio_yields_to_custom_styles() {
+    let resources = font_resources();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let image = tree.append(
+        0,
+        vec![
+            FlexStyle::AlignSelf(Align::FlexStart),
+            FlexStyle::Width(StyleUnit::Point(80.0.into())),
+            FlexStyle::AspectRatio(2.0.into()),
+        ]
+    );
+
+    tree.nodes[image].measure_self_as_image(&resources, &DOMText::from("logo"), &());
+    tree.reflow(1000, 1000);
+    assert_eq!(
+        tree.nodes[image].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 80, 40)
+    );
+
+    tree.nodes[image].reset_custom_styles(KnownElementName::Image);
+    tree.nodes[image].apply_styles(&layout_styles(vec![
+        FlexStyle::AlignSelf(Align::FlexStart),
+        FlexStyle::Width(StyleUnit::Point(80.0.into())),
+    ]));
+    tree.reflow(1000, 1000);
+    assert_eq!(
+        tree.nodes[image].get_local_bounding_client_rect(),
+        LayoutBoundingClientRect::new(0, 0, 80, 83)
+    );
+}
+
+#[test]
+fn test_reflow_change_set() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let a = tree.append(0, absolute(0.0, 0.0, 10.0, 10.0));
+    let b = tree.append(0, absolute(20.0, 20.0, 10.0, 10.0));
+    let c = tree.append(b, absolute(0.0, 0.0, 5.0, 5.0));
+
+    assert!(tree.nodes[0].is_tainted());
+    assert_eq!(tree.reflow(100, 100).changed, vec![0, a, b, c]);
+    assert!(!tree.nodes[0].is_tainted());
+    assert!(tree.reflow(100, 100).is_empty());
+
+    tree.nodes[b].apply_styles(&layout_styles(absolute(30.0, 20.0, 10.0, 10.0)));
+    assert!(tree.nodes[b].is_tainted());
+    assert_eq!(tree.reflow(100, 100).changed, vec![b, c]);
+    assert!(!tree.nodes[b].is_tainted());
+
+    // Owners that set positions themselves after reflowing collect changes
+    // afterwards.
+    tree.nodes[c].apply_styles(&layout_styles(absolute(1.0, 0.0, 5.0, 5.0)));
+    tree.nodes[0].reflow_subtree(100, 100, LayoutReflowDirection::LTR);
+    let position = tree.nodes[b].get_global_bounding_client_rect().position;
+    tree.nodes[c].set_computed_client_position(position);
+    assert!(!tree.nodes[c].is_tainted());
+    let changes = collect_changes(&mut tree);
+    assert_eq!(changes.changed, vec![c]);
+    assert_eq!(
+        changes.damage.rects(),
+        vec![LayoutBoundingClientRect::new(30, 20, 6, 5)]
+    );
+    assert!(collect_changes(&mut tree).is_empty());
+
+    let mut index = LayoutSpatialIndex::from_tree(&tree, 16);
+    tree.nodes[a].apply_styles(&layout_styles(absolute(50.0, 50.0, 10.0, 10.0)));
+    let changes = tree.reflow(100, 100);
+    index.update_changed(&tree, &changes);
+    assert_eq!(index.get_rect(a), Some(LayoutBoundingClientRect::new(50, 50, 10, 10)));
+}
+
+#[test]
+fn test_reflow_damage() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 100.0, 100.0));
+    let a = tree.append(0, absolute(0.0, 0.0, 10.0, 10.0));
+    let b = tree.append(0, absolute(50.0, 50.0, 10.0, 10.0));
+    tree.append(0, absolute(80.0, 80.0, 10.0, 10.0));
+
+    assert_eq!(
+        tree.reflow(100, 100).damage.rects(),
+        vec![LayoutBoundingClientRect::new(0, 0, 100, 100)]
+    );
+
+    tree.nodes[a].apply_styles(&layout_styles(absolute(5.0, 5.0, 10.0, 10.0)));
+    tree.nodes[b].apply_styles(&layout_styles(absolute(50.0, 50.0, 20.0, 10.0)));
+    let mut damage = tree.reflow(100, 100).damage.rects();
+    damage.sort_by_key(|v| v.position.left);
+
+    assert_eq!(
+        damage,
+        vec![
+            LayoutBoundingClientRect::new(0, 0, 15, 15),
+            LayoutBoundingClientRect::new(50, 50, 20, 10),
+        ]
+    );
+
+    let mut damage = LayoutDamage::new();
+    damage.add(LayoutBoundingClientRect::new(0, 0, 10, 10));
+    damage.add(LayoutBoundingClientRect::new(20, 0, 10, 10));
+    damage.add(LayoutBoundingClientRect::new(5, 0, 20, 5));
+    assert_eq!(damage.rects(), vec![LayoutBoundingClientRect::new(0, 0, 30, 10)]);
+}
+
+#[test]
+fn test_measure_context() {
+    let image = LayoutMeasureContext::Image { width: 100, height: 50 };
+    assert_eq!(
+        image.measure(0.0, LayoutMeasureMode::Undefined, 0.0, LayoutMeasureMode::Undefined),
+        LayoutClientSizeF { width: 100.0, height: 50.0 }
+    );
+    assert_eq!(
+        image.measure(80.0, LayoutMeasureMode::AtMost, 30.0, LayoutMeasureMode::Exactly),
+        LayoutClientSizeF { width: 80.0, height: 30.0 }
+    );
+    assert_eq!(image.baseline(100.0, 50.0, &LayoutBoxEdgesF::default()), Some(50.0));
+
+    // Text starts below the border and padding at the top of its box.
+    let text = LayoutMeasureContext::Text {
+        metrics: Rc::new(TextMetrics {
+            line_height_f: 10.0,
+            ascent_f: 8.0,
+            ..TextMetrics::default()
+        })
+    };
+    assert_eq!(text.baseline(100.0, 20.0, &LayoutBoxEdgesF::new(7.0, 3.0, 3.0, 3.0)), Some(15.0));
+
+    let custom = LayoutMeasureContext::Custom {
+        measure: Rc::new(|width, _, _, _| LayoutClientSizeF { width: width / 2.0, height: 10.0 })
+    };
+    assert_eq!(
+        custom.measure(80.0, LayoutMeasureMode::AtMost, 0.0, LayoutMeasureMode::Undefined),
+        LayoutClientSizeF { width: 40.0, height: 10.0 }
+    );
+    assert_eq!(custom.baseline(40.0, 10.0, &LayoutBoxEdgesF::default()), None);
+
+    let mut node = Layout::make_initial_layout_node(KnownElementName::Div);
+    node.apply_styles(&layout_styles(vec![
+        FlexStyle::Width(StyleUnit::Point(30.0.into())),
+        FlexStyle::Height(StyleUnit::Point(40.0.into())),
+    ]));
+    node.reflow_subtree(100, 100, LayoutReflowDirection::LTR);
+    assert_eq!(node.get_local_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 30, 40));
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+fn assert_same_layout(width: u32, height: u32, fixture: Vec<(usize, Vec<FlexStyle>)>) {
+    fn build<E: TLayoutEngine>(fixture: &[(usize, Vec<FlexStyle>)]) -> EngineTree<E> {
+        let mut tree = EngineTree::new(fixture[0].1.clone());
+        for &(parent, ref styles) in &fixture[1..] {
+            tree.append(parent, styles.clone());
+        }
+        tree
+    }
+
+    let mut yoga = build::<YogaLayoutEngine>(&fixture);
+    let mut flexbox = build::<FlexboxLayoutEngine>(&fixture);
+    yoga.reflow(width, height);
+    flexbox.reflow(width, height);
+
+    for id in 0..fixture.len() {
+        assert_eq!(
+            flexbox.nodes[id].get_global_bounding_client_rect(),
+            yoga.nodes[id].get_global_bounding_client_rect(),
+            "Node {} in {:?}",
+            id,
+            fixture
+        );
+        assert_eq!(flexbox.nodes[id].get_layout_padding(), yoga.nodes[id].get_layout_padding());
+        assert_eq!(flexbox.nodes[id].get_layout_border(), yoga.nodes[id].get_layout_border());
+    }
+}
+
+#[cfg(feature = "flexbox")]
+#[test]
+fn test_flexbox_relayout() {
+    let px = |v: f32| StyleUnit::Point(v.into());
+
+    let mut tree = EngineTree::<FlexboxLayoutEngine>::new(vec![
+        FlexStyle::FlexDirection(FlexDirection::Row),
+        FlexStyle::AlignItems(Align::FlexStart),
+    ]);
+    let outer = tree.append(0, vec![]);
+    let inner = tree.append(outer, vec![FlexStyle::Width(px(30.0)), FlexStyle::Height(px(20.0))]);
+    let sibling = tree.append(0, vec![FlexStyle::Width(px(50.0)), FlexStyle::Height(px(10.0))]);
+
+    tree.reflow(200, 100);
+    tree.reflow(200, 100);
+    assert_eq!(tree.nodes[outer].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 30, 20));
+    assert_eq!(tree.nodes[sibling].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(30, 0, 50, 10));
+
+    // Changing a nested node invalidates whatever was cached for the nodes
+    // containing it.
+    tree.nodes[inner].apply_styles(&layout_styles(vec![FlexStyle::Width(px(80.0))]));
+    tree.reflow(200, 100);
+    assert_eq!(tree.nodes[outer].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 80, 20));
+    assert_eq!(tree.nodes[inner].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 80, 20));
+    assert_eq!(tree.nodes[sibling].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(80, 0, 50, 10));
+}
+
+#[cfg(feature = "flexbox")]
+#[test]
+fn test_flexbox_non_finite_sizes() {
+    let px = |v: f32| StyleUnit::Point(v.into());
+
+    let mut registry = LayoutMeasureRegistry::new();
+    registry.register(
+        "broken",
+        Rc::new(|_, _, _, _| LayoutClientSizeF {
+            width: std::f32::NAN,
+            height: std::f32::INFINITY
+        })
+    );
+
+    let resources = empty_resources();
+    let mut tree = EngineTree::<FlexboxLayoutEngine>::new(vec![
+        FlexStyle::Width(px(100.0)),
+        FlexStyle::Height(px(100.0)),
+        FlexStyle::FlexDirection(FlexDirection::Row),
+        FlexStyle::AlignItems(Align::FlexStart),
+    ]);
+    let broken = tree.append(0, vec![FlexStyle::FlexShrink(1.0.into())]);
+    tree.append(0, vec![FlexStyle::Width(px(std::f32::INFINITY)), FlexStyle::FlexShrink(1.0.into())]);
+    tree.nodes[broken].measure_self_as_normal(&resources, &registry.get("broken"));
+
+    // Shrinking an infinitely wide item used to never settle.
+    tree.reflow(100, 100);
+    assert_eq!(tree.nodes[broken].get_local_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 0, 0));
+}
+
+/// Builds an rsx! fixture laid out by the given engine, and returns the
+/// local rects of all its nodes in document order.
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+macro_rules! fixture_rects {
+    ($engine:ty, $width:expr, $height:expr, $stylesheet:ident = $css:tt, $tree:tt) => {{
+        type DOMNode = TTDOMNode<(), StyleDeclarations, ComputedStyles, EngineLayout<$engine>>;
+
+        let mut $stylesheet = css!($css);
+        let mut tree = rsx! $tree;
+
+        let resources = font_resources();
+        tree.generate_layout_tree(&resources);
+        tree.reflow_subtree($width, $height, LayoutReflowDirection::LTR);
+
+        tree.root()
+            .descendants_iter()
+            .map(|v| v.get_local_bounding_client_rect())
+            .collect::<Vec<_>>()
+    }};
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+macro_rules! assert_same_fixture_layout {
+    ($width:expr, $height:expr, $stylesheet:ident = $css:tt, $tree:tt) => {{
+        let yoga = fixture_rects!(YogaLayoutEngine, $width, $height, $stylesheet = $css, $tree);
+        let flexbox = fixture_rects!(FlexboxLayoutEngine, $width, $height, $stylesheet = $css, $tree);
+        assert!(yoga.len() > 1);
+        assert_eq!(flexbox, yoga, "Fixture {}", $css);
+    }};
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+#[test]
+fn test_flexbox_fixture_conformance() {
+    assert_same_fixture_layout!(100, 100, stylesheet = "tests/fixtures/test_1.css", {
+        <div style={stylesheet.take(".foo")}>
+            Hello world!
+        </div>
+    });
+
+    assert_same_fixture_layout!(1000, 1000, stylesheet = "tests/fixtures/test_2.css", {
+        <root style={stylesheet.take(".root")}>
+            <image src={"logo"} style={stylesheet.take(".image")} />
+            <text style={stylesheet.take(".text")} />
+        </root>
+    });
+
+    assert_same_fixture_layout!(1000, 1000, stylesheet = "tests/fixtures/test_3.css", {
+        <root style={stylesheet.take(".root")}>
+            <image src={"logo"} style={stylesheet.take(".image")} />
+            <text style={stylesheet.take(".text")} />
+        </root>
+    });
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+#[test]
+fn test_flexbox_conformance() {
+    let px = |v: f32| StyleUnit::Point(v.into());
+    let pct = |v: f32| StyleUnit::Percent(v.into());
+
+    // Justification along both axes.
+    for &justify in &[
+        Justify::FlexStart,
+        Justify::Center,
+        Justify::FlexEnd,
+        Justify::SpaceBetween,
+        Justify::SpaceAround,
+    ] {
+        for &direction in &[
+            FlexDirection::Row,
+            FlexDirection::RowReverse,
+            FlexDirection::Column,
+            FlexDirection::ColumnReverse,
+        ] {
+            assert_same_layout(
+                300,
+                200,
+                vec![
+                    (0, vec![FlexStyle::FlexDirection(direction), FlexStyle::JustifyContent(justify)]),
+                    (0, vec![FlexStyle::Width(px(30.0)), FlexStyle::Height(px(20.0))]),
+                    (0, vec![FlexStyle::Width(px(40.0)), FlexStyle::Height(px(10.0)), FlexStyle::Margin(px(5.0))]),
+                    (0, vec![FlexStyle::Width(px(50.0)), FlexStyle::Height(px(30.0))]),
+                ]
+            );
+        }
+    }
+
+    // Alignment along the cross axis, including stretching.
+    for &align in &[Align::FlexStart, Align::Center, Align::FlexEnd, Align::Stretch, Align::Baseline] {
+        assert_same_layout(
+            300,
+            200,
+            vec![
+                (0, vec![FlexStyle::FlexDirection(FlexDirection::Row), FlexStyle::AlignItems(align)]),
+                (0, vec![FlexStyle::Width(px(30.0)), FlexStyle::Height(px(20.0))]),
+                (0, vec![FlexStyle::Width(px(40.0)), FlexStyle::MarginTop(px(15.0))]),
+                (0, vec![FlexStyle::Width(px(50.0)), FlexStyle::Height(px(50.0)), FlexStyle::AlignSelf(Align::Center)]),
+            ]
+        );
+    }
+
+    // Growing and shrinking, with fractional sizes rounded to the pixel grid.
+    assert_same_layout(
+        100,
+        100,
+        vec![
+            (0, vec![FlexStyle::FlexDirection(FlexDirection::Row)]),
+            (0, vec![FlexStyle::FlexGrow(1.0.into())]),
+            (0, vec![FlexStyle::FlexGrow(1.0.into())]),
+            (0, vec![FlexStyle::FlexGrow(1.0.into())]),
+        ]
+    );
+    assert_same_layout(
+        400,
+        100,
+        vec![
+            (0, vec![FlexStyle::FlexDirection(FlexDirection::Row), FlexStyle::Padding(px(10.0))]),
+            (0, vec![FlexStyle::Flex(1.0.into()), FlexStyle::MarginHorizontal(px(5.0))]),
+            (0, vec![FlexStyle::Flex(2.0.into()), FlexStyle::MaxWidth(px(120.0))]),
+            (0, vec![FlexStyle::FlexGrow(1.0.into()), FlexStyle::FlexBasis(px(50.0))]),
+        ]
+    );
+    assert_same_layout(
+        100,
+        100,
+        vec![
+            (0, vec![FlexStyle::FlexDirection(FlexDirection::Row)]),
+            (0, vec![FlexStyle::FlexBasis(px(80.0)), FlexStyle::FlexShrink(1.0.into())]),
+            (0, vec![FlexStyle::FlexBasis(px(40.0)), FlexStyle::FlexShrink(1.0.into())]),
+            (0, vec![FlexStyle::FlexBasis(px(40.0)), FlexStyle::FlexShrink(2.0.into()), FlexStyle::MinWidth(px(30.0))]),
+        ]
+    );
+
+    // Wrapping into multiple lines, distributed along the cross axis.
+    for &wrap in &[Wrap::Wrap, Wrap::WrapReverse] {
+        for &align in &[Align::FlexStart, Align::Center, Align::FlexEnd, Align::Stretch, Align::SpaceBetween, Align::SpaceAround] {
+            let mut fixture = vec![
+                (
+                    0,
+                    vec![
+                        FlexStyle::FlexDirection(FlexDirection::Row),
+                        FlexStyle::FlexWrap(wrap),
+                        FlexStyle::AlignContent(align),
+                        FlexStyle::Width(px(100.0)),
+                        FlexStyle::Height(px(120.0)),
+                    ]
+                ),
+            ];
+            for _ in 0..5 {
+                fixture.push((0, vec![FlexStyle::Width(px(30.0)), FlexStyle::Height(px(30.0))]));
+            }
+            assert_same_layout(200, 200, fixture);
+        }
+    }
+
+    // Percentages, minimum and maximum sizes, borders and padding.
+    assert_same_layout(
+        200,
+        100,
+        vec![
+            (0, vec![FlexStyle::Padding(pct(5.0)), FlexStyle::Border(4.0.into())]),
+            (0, vec![FlexStyle::Width(pct(50.0)), FlexStyle::Height(pct(25.0))]),
+            (0, vec![FlexStyle::MinHeight(px(30.0)), FlexStyle::MaxWidth(px(80.0)), FlexStyle::BorderLeft(2.0.into())]),
+            (2, vec![FlexStyle::Height(px(10.0)), FlexStyle::MarginVertical(pct(10.0))]),
+        ]
+    );
+
+    // Containers sized by their content.
+    assert_same_layout(
+        300,
+        300,
+        vec![
+            (0, vec![FlexStyle::AlignItems(Align::FlexStart)]),
+            (0, vec![FlexStyle::FlexDirection(FlexDirection::Row), FlexStyle::Padding(px(5.0))]),
+            (1, vec![FlexStyle::Width(px(20.0)), FlexStyle::Height(px(40.0))]),
+            (1, vec![FlexStyle::Width(px(30.0)), FlexStyle::Height(px(10.0)), FlexStyle::Margin(px(3.0))]),
+            (0, vec![FlexStyle::AlignSelf(Align::Center)]),
+            (4, vec![FlexStyle::Width(px(25.0)), FlexStyle::Height(px(25.0))]),
+            (4, vec![FlexStyle::Width(px(35.0)), FlexStyle::Height(px(15.0))]),
+        ]
+    );
+
+    // Absolute and relative positioning, aspect ratios and hidden nodes.
+    assert_same_layout(
+        200,
+        200,
+        vec![
+            (0, vec![FlexStyle::Padding(px(10.0)), FlexStyle::Border(5.0.into())]),
+            (
+                0,
+                vec![
+                    FlexStyle::Position(PositionType::Absolute),
+                    FlexStyle::Right(px(10.0)),
+                    FlexStyle::Bottom(px(20.0)),
+                    FlexStyle::Width(px(30.0)),
+                    FlexStyle::Height(px(40.0)),
+                ]
+            ),
+            (
+                0,
+                vec![
+                    FlexStyle::Position(PositionType::Absolute),
+                    FlexStyle::Left(px(10.0)),
+                    FlexStyle::Right(px(30.0)),
+                    FlexStyle::Top(px(0.0)),
+                    FlexStyle::Height(px(10.0)),
+                ]
+            ),
+            (0, vec![FlexStyle::Left(px(7.0)), FlexStyle::Top(px(-3.0)), FlexStyle::Height(px(10.0))]),
+            (0, vec![FlexStyle::Width(px(40.0)), FlexStyle::AspectRatio(2.0.into())]),
+            (0, vec![FlexStyle::Display(Display::None), FlexStyle::Height(px(50.0))]),
+            (0, vec![FlexStyle::Height(px(10.0))]),
+        ]
+    );
+}
+
+#[test]
+fn test_grid_placement() {
+    let mut columns = LayoutGridTemplate::new(vec![LayoutGridTrack::auto(); 3]);
+    columns.name_line(0, "none");
+    assert!(columns.line_names.is_empty());
+    columns.name_line(2, "main");
+    let grid = LayoutGrid::new(columns, LayoutGridTemplate::default());
+
+    let areas = grid.place_items(&[
+        LayoutGridPlacement::new(
+            LayoutGridLine::Named("main".to_string()),
+            LayoutGridLine::Auto,
+            LayoutGridLine::Line(2),
+            LayoutGridLine::Auto
+        ),
+        LayoutGridPlacement::new(
+            LayoutGridLine::Line(-2),
+            LayoutGridLine::Line(-1),
+            LayoutGridLine::Auto,
+            LayoutGridLine::Auto
+        ),
+        LayoutGridPlacement::default(),
+        LayoutGridPlacement::new(
+            LayoutGridLine::Auto,
+            LayoutGridLine::Auto,
+            LayoutGridLine::Span(2),
+            LayoutGridLine::Auto
+        ),
+    ]);
+
+    let area = |column_start, column_end, row_start, row_end| LayoutGridArea {
+        column_start,
+        column_end,
+        row_start,
+        row_end
+    };
+    assert_eq!(areas, vec![area(1, 2, 1, 2), area(2, 3, 0, 1), area(0, 1, 1, 2), area(2, 3, 1, 3)]);
+}
+
+#[test]
+fn test_grid_track_sizing() {
+    let columns = LayoutGridTemplate::new(vec![
+        LayoutGridTrack::auto(),
+        LayoutGridTrack::fraction(1.0),
+        LayoutGridTrack::minmax(LayoutGridBreadth::Point(20.0), LayoutGridBreadth::Point(40.0)),
+    ]);
+    let mut grid = LayoutGrid::new(columns, LayoutGridTemplate::default());
+    grid.column_gap = 10.0;
+    grid.row_gap = 5.0;
+
+    let items = vec![LayoutGridPlacement::default(); 4];
+    let sizes = [(50.0, 20.0), (10.0, 10.0), (10.0, 30.0), (30.0, 10.0)];
+    let layout = grid.layout(&items, Some(200.0), None, |index, _| {
+        let (width, height) = sizes[index];
+        LayoutClientSizeF { width, height }
+    });
+
+    assert_eq!(layout.columns, vec![50.0, 90.0, 40.0]);
+    assert_eq!(layout.rows, vec![30.0, 10.0]);
+    assert_eq!(layout.size, LayoutClientSizeF { width: 200.0, height: 45.0 });
+    assert_eq!(
+        layout.areas,
+        vec![
+            LayoutBoundingClientRectF::new(0.0, 0.0, 50.0, 30.0),
+            LayoutBoundingClientRectF::new(60.0, 0.0, 90.0, 30.0),
+            LayoutBoundingClientRectF::new(160.0, 0.0, 40.0, 30.0),
+            LayoutBoundingClientRectF::new(0.0, 35.0, 50.0, 10.0),
+        ]
+    );
+}
+
+#[test]
+fn test_grid_reflow() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 300.0, 200.0));
+    let a = tree.append(0, vec![]);
+    let b = tree.append(0, vec![]);
+    let c = tree.append(0, vec![]);
+    let d = tree.append(0, vec![]);
+
+    let columns = LayoutGridTemplate::new(vec![
+        LayoutGridTrack::point(100.0),
+        LayoutGridTrack::fraction(1.0),
+        LayoutGridTrack::fraction(2.0),
+    ]);
+    let rows = LayoutGridTemplate::new(vec![LayoutGridTrack::point(50.0), LayoutGridTrack::auto()]);
+    let mut grid = LayoutGrid::new(columns, rows);
+    grid.column_gap = 10.0;
+    grid.row_gap = 10.0;
+    tree.nodes[0].set_grid(Some(grid));
+    tree.nodes[d].set_grid_placement(LayoutGridPlacement::new(
+        LayoutGridLine::Span(3),
+        LayoutGridLine::Auto,
+        LayoutGridLine::Auto,
+        LayoutGridLine::Auto
+    ));
+    tree.reflow(300, 200);
+
+    assert_eq!(tree.nodes[a].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 100, 50));
+    assert_eq!(tree.nodes[b].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(110, 0, 60, 50));
+    assert_eq!(tree.nodes[c].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(180, 0, 120, 50));
+    assert_eq!(tree.nodes[d].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 60, 300, 140));
+
+    // Containers without a height grow to fit their rows.
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 300.0, 200.0));
+    let container = tree.append(
+        0,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(200.0.into())),
+            FlexStyle::Padding(StyleUnit::Point(10.0.into())),
+        ]
+    );
+    let a = tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(30.0.into()))]);
+    let b = tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(20.0.into()))]);
+
+    let columns = LayoutGridTemplate::new(vec![LayoutGridTrack::point(50.0), LayoutGridTrack::fraction(1.0)]);
+    let mut grid = LayoutGrid::new(columns, LayoutGridTemplate::default());
+    grid.column_gap = 10.0;
+    tree.nodes[container].set_grid(Some(grid));
+    tree.reflow(300, 200);
+
+    assert_eq!(tree.nodes[container].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 200, 50));
+    assert_eq!(tree.nodes[a].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(10, 10, 50, 30));
+    assert_eq!(tree.nodes[b].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(70, 10, 120, 20));
+
+    tree.nodes[container].set_grid(None);
+    tree.reflow(300, 200);
+
+    assert_eq!(tree.nodes[container].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 200, 70));
+    assert_eq!(tree.nodes[b].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(10, 40, 180, 20));
+
+    assert_eq!(
+        grid_subtree_rects::<DefaultLayoutEngine>(),
+        vec![
+            LayoutBoundingClientRect::new(0, 0, 200, 30),
+            LayoutBoundingClientRect::new(0, 0, 100, 30),
+            LayoutBoundingClientRect::new(100, 0, 100, 20),
+        ]
+    );
+}
+
+/// Grid items are placed by the layout engine, so laying out a subtree is
+/// enough to place them. Returns the local rects of the container and its
+/// items.
+fn grid_subtree_rects<E: TLayoutEngine>() -> Vec<LayoutBoundingClientRect> {
+    let mut tree = EngineTree::<E>::new(absolute(0.0, 0.0, 300.0, 200.0));
+    let container = tree.append(0, vec![FlexStyle::Width(StyleUnit::Point(200.0.into()))]);
+    tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(30.0.into()))]);
+    tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(20.0.into()))]);
+
+    let columns = LayoutGridTemplate::new(vec![LayoutGridTrack::fraction(1.0), LayoutGridTrack::fraction(1.0)]);
+    tree.nodes[container].set_grid(Some(LayoutGrid::new(columns, LayoutGridTemplate::default())));
+    tree.nodes[0].reflow_subtree(300, 200, LayoutReflowDirection::LTR);
+
+    tree.nodes[1..].iter().map(|v| v.get_local_bounding_client_rect()).collect()
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+#[test]
+fn test_flexbox_grid_reflow() {
+    assert_eq!(grid_subtree_rects::<FlexboxLayoutEngine>(), grid_subtree_rects::<YogaLayoutEngine>());
+}
+
+#[test]
+fn test_flow_layout() {
+    let text = |segments: Vec<(f32, f32)>| {
+        LayoutFlowItem::Text(Rc::new(TextMetrics {
+            segments: segments
+                .into_iter()
+                .map(|(width_f, trailing_width_f)| TextSegmentMetrics {
+                    width_f,
+                    trailing_width_f,
+                    hard_break: false,
+                    joins_next: false
+                })
+                .collect(),
+            spans: vec![],
+            line_height_f: 10.0,
+            ascent_f: 8.0,
+            max_lines: None,
+            ellipsis_width_f: None,
+            first_char: None,
+            last_char: None
+        }))
+    };
+
+    let items = vec![
+        text(vec![(30.0, 5.0), (20.0, 5.0)]),
+        LayoutFlowItem::Atomic(LayoutClientSizeF { width: 20.0, height: 16.0 }),
+        text(vec![(40.0, 5.0), (30.0, 0.0)]),
+        LayoutFlowItem::Block(LayoutClientSizeF { width: 50.0, height: 25.0 }),
+    ];
+    let layout = layout_flow(&items, Some(70.0));
+
+    let rects = layout.fragments.iter().map(|v| v.rect).collect::<Vec<_>>();
+    assert_eq!(
+        rects,
+        vec![
+            LayoutBoundingClientRectF::new(0.0, 0.0, 55.0, 10.0),
+            LayoutBoundingClientRectF::new(0.0, 10.0, 20.0, 16.0),
+            LayoutBoundingClientRectF::new(0.0, 18.0, 60.0, 20.0),
+            LayoutBoundingClientRectF::new(0.0, 38.0, 70.0, 25.0),
+        ]
+    );
+    assert_eq!(
+        layout.fragments[2].lines,
+        vec![
+            LayoutTextFragment {
+                segments: 0..1,
+                rect: LayoutBoundingClientRectF::new(20.0, 0.0, 40.0, 10.0),
+                baseline_f: 8.0
+            },
+            LayoutTextFragment {
+                segments: 1..2,
+                rect: LayoutBoundingClientRectF::new(0.0, 10.0, 30.0, 10.0),
+                baseline_f: 18.0
+            },
+        ]
+    );
+    assert_eq!(layout.size, LayoutClientSizeF { width: 70.0, height: 63.0 });
+
+    // Without a width, lines only break at hard breaks.
+    let layout = layout_flow(&items, None);
+    assert_eq!(layout.fragments[2].rect, LayoutBoundingClientRectF::new(80.0, 8.0, 75.0, 10.0));
+    assert_eq!(layout.fragments[3].rect, LayoutBoundingClientRectF::new(0.0, 18.0, 50.0, 25.0));
+    assert_eq!(layout.size, LayoutClientSizeF { width: 155.0, height: 43.0 });
+}
+
+#[test]
+fn test_flow_text_across_items() {
+    let text = |width_f, first_char, last_char| {
+        LayoutFlowItem::Text(Rc::new(TextMetrics {
+            segments: vec![
+                TextSegmentMetrics {
+                    width_f,
+                    ..TextSegmentMetrics::default()
+                },
+            ],
+            line_height_f: 10.0,
+            ascent_f: 8.0,
+            first_char: Some(first_char),
+            last_char: Some(last_char),
+            ..TextMetrics::default()
+        }))
+    };
+
+    // "ab" and "cd" make up one word, so it overflows instead of breaking
+    // between them, while "ef" can go on the next line after a space.
+    let items = vec![text(20.0, 'a', 'b'), text(20.0, 'c', ' '), text(20.0, 'e', 'f')];
+    let layout = layout_flow(&items, Some(30.0));
+
+    let rects = layout.fragments.iter().map(|v| v.rect).collect::<Vec<_>>();
+    assert_eq!(
+        rects,
+        vec![
+            LayoutBoundingClientRectF::new(0.0, 0.0, 20.0, 10.0),
+            LayoutBoundingClientRectF::new(20.0, 0.0, 20.0, 10.0),
+            LayoutBoundingClientRectF::new(0.0, 10.0, 20.0, 10.0),
+        ]
+    );
+}
+
+#[test]
+fn test_flow_reflow() {
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 300.0, 200.0));
+    let container = tree.append(0, vec![FlexStyle::Width(StyleUnit::Point(100.0.into()))]);
+    let block = tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(30.0.into()))]);
+    let a = tree.append(
+        container,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(40.0.into())),
+            FlexStyle::Height(StyleUnit::Point(20.0.into())),
+        ]
+    );
+    let b = tree.append(
+        container,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(70.0.into())),
+            FlexStyle::Height(StyleUnit::Point(10.0.into())),
+        ]
+    );
+
+    tree.nodes[container].set_flow_layout(true);
+    tree.nodes[a].set_flow_display(LayoutFlowDisplay::Inline);
+    tree.nodes[b].set_flow_display(LayoutFlowDisplay::Inline);
+    tree.reflow(300, 200);
+
+    assert_eq!(tree.nodes[container].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 100, 60));
+    assert_eq!(tree.nodes[block].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 0, 100, 30));
+    assert_eq!(tree.nodes[a].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 30, 40, 20));
+    assert_eq!(tree.nodes[b].get_global_bounding_client_rect(), LayoutBoundingClientRect::new(0, 50, 70, 10));
+
+    assert_eq!(
+        flow_subtree_rects::<DefaultLayoutEngine>(),
+        vec![
+            LayoutBoundingClientRect::new(0, 0, 100, 50),
+            LayoutBoundingClientRect::new(0, 0, 100, 30),
+            LayoutBoundingClientRect::new(0, 30, 40, 20),
+        ]
+    );
+}
+
+/// Like grid items, flow items are placed by the layout engine. Returns the
+/// local rects of the container and its items.
+fn flow_subtree_rects<E: TLayoutEngine>() -> Vec<LayoutBoundingClientRect> {
+    let mut tree = EngineTree::<E>::new(absolute(0.0, 0.0, 300.0, 200.0));
+    let container = tree.append(0, vec![FlexStyle::Width(StyleUnit::Point(100.0.into()))]);
+    tree.append(container, vec![FlexStyle::Height(StyleUnit::Point(30.0.into()))]);
+    let inline = tree.append(
+        container,
+        vec![
+            FlexStyle::Width(StyleUnit::Point(40.0.into())),
+            FlexStyle::Height(StyleUnit::Point(20.0.into())),
+        ]
+    );
+
+    tree.nodes[container].set_flow_layout(true);
+    tree.nodes[inline].set_flow_display(LayoutFlowDisplay::Inline);
+    tree.nodes[0].reflow_subtree(300, 200, LayoutReflowDirection::LTR);
+
+    tree.nodes[1..].iter().map(|v| v.get_local_bounding_client_rect()).collect()
+}
+
+#[cfg(all(feature = "yoga", feature = "flexbox"))]
+#[test]
+fn test_flexbox_flow_reflow() {
+    assert_eq!(flow_subtree_rects::<FlexboxLayoutEngine>(), flow_subtree_rects::<YogaLayoutEngine>());
+}
+
+#[test]
+fn test_rich_text_spans() {
+    let resources = font_resources();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let large = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart)]);
+    let rich = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart)]);
+
+    let mut bold = LayoutTextSpan::new("world", 24.0);
+    bold.font_families = vec!["FreeSans".to_string()];
+    bold.font_weight = 700;
+    bold.color_key = Some("accent".to_string());
+
+    tree.nodes[large].measure_self_as_spans(&resources, &[bold.clone()]);
+    tree.nodes[rich].measure_self_as_spans(&resources, &[LayoutTextSpan::new("Hello ", 12.0), bold]);
+    tree.reflow(1000, 1000);
+
+    let large_rect = tree.nodes[large].get_local_bounding_client_rect();
+    let rich_rect = tree.nodes[rich].get_local_bounding_client_rect();
+    assert_eq!(rich_rect.size.height, large_rect.size.height);
+    assert!(rich_rect.size.width > large_rect.size.width);
+
+    // Renderers get each span's runs, shaped with that span's font.
+    {
+        let shaped_text = tree.nodes[rich].get_shaped_text();
+        assert_eq!(shaped_text.line_boxes().len(), 1);
+        let runs = &shaped_text.line_boxes()[0].runs;
+        assert_eq!(runs.iter().map(|v| shaped_text.layout().segments()[v.segment].span).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(runs.iter().all(|v| !shaped_text.run_text(v).glyphs().is_empty()));
+    }
+    assert_eq!(tree.nodes[rich].get_text_spans()[1].color_key, Some("accent".to_string()));
+    let spans = tree.nodes[rich].get_text_layout().segments().iter().map(|v| v.span).collect::<Vec<_>>();
+    assert_eq!(spans, vec![0, 1]);
+
+    // Both spans share a baseline, so the smaller text starts lower down.
+    let line_boxes = tree.nodes[rich].get_line_boxes();
+    assert_eq!(line_boxes.len(), 1);
+    assert!(line_boxes[0].runs[0].origin.1 > line_boxes[0].runs[1].origin.1);
+
+    // Span boundaries inside a word aren't break opportunities.
+    tree.nodes[rich].measure_self_as_spans(&resources, &[LayoutTextSpan::new("Hello bo", 12.0), LayoutTextSpan::new("ld", 12.0)]);
+    let metrics = Rc::clone(tree.nodes[rich].get_text_layout().metrics());
+    assert_eq!(metrics.segments.len(), 3);
+    assert!(metrics.segments[1].joins_next);
+    let lines = metrics.break_lines(Some(1.0));
+    assert_eq!(lines.iter().map(|v| v.segments.clone()).collect::<Vec<_>>(), vec![0..1, 1..3]);
+}
+
+#[test]
+fn test_bidi_text() {
+    let resources = font_resources();
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let text = tree.append(0, vec![]);
+
+    // A run of Hebrew words inside a Latin paragraph is drawn right to left.
+    tree.nodes[text].measure_self_as_spans(&resources, &[LayoutTextSpan::new("one \u{5D0}\u{5D1} \u{5D2}\u{5D3} two", 12.0)]);
+    tree.reflow(1000, 1000);
+
+    let levels = tree.nodes[text].get_text_layout().segments().iter().map(|v| v.level).collect::<Vec<_>>();
+    assert_eq!(levels, vec![0, 1, 1, 0]);
+
+    let line_boxes = tree.nodes[text].get_line_boxes();
+    assert_eq!(line_boxes.len(), 1);
+    assert_eq!(line_boxes[0].rect.position.left, 0.0);
+    let order = line_boxes[0].runs.iter().map(|v| v.segment).collect::<Vec<_>>();
+    assert_eq!(order, vec![0, 2, 1, 3]);
+    assert!(line_boxes[0].runs[1].is_rtl);
+    assert!(line_boxes[0].runs[0].origin.0 < line_boxes[0].runs[1].origin.0);
+    assert!(line_boxes[0].runs[1].origin.0 < line_boxes[0].runs[2].origin.0);
+
+    // Paragraphs starting with a right-to-left character are right aligned.
+    tree.nodes[text].measure_self_as_spans(&resources, &[LayoutTextSpan::new("\u{5D0}\u{5D1} \u{5D2}\u{5D3}", 12.0)]);
+    tree.reflow(1000, 1000);
+
+    let line_boxes = tree.nodes[text].get_line_boxes();
+    let rect = &line_boxes[0].rect;
+    assert!((rect.position.left + rect.size.width - 1000.0).abs() < 0.01);
+    let order = line_boxes[0].runs.iter().map(|v| v.segment).collect::<Vec<_>>();
+    assert_eq!(order, vec![1, 0]);
+
+    // An explicit direction overrides the one found in the text, and text
+    // that was already measured is measured again with it.
+    tree.nodes[text].set_text_direction(&resources, LayoutReflowDirection::LTR);
+    tree.reflow(1000, 1000);
+
+    let line_boxes = tree.nodes[text].get_line_boxes();
+    assert_eq!(line_boxes[0].rect.position.left, 0.0);
+    let order = line_boxes[0].runs.iter().map(|v| v.segment).collect::<Vec<_>>();
+    assert_eq!(order, vec![1, 0]);
+
+    // Only the text direction decides how text is ordered, not the direction
+    // boxes are laid out in.
+    reflow_tree(&mut tree, 1000, 1000, LayoutReflowDirection::RTL);
+    let levels = tree.nodes[text].get_text_layout().segments().iter().map(|v| v.level).collect::<Vec<_>>();
+    assert_eq!(levels, vec![1, 1]);
+    let order = tree.nodes[text].get_line_boxes()[0].runs.iter().map(|v| v.segment).collect::<Vec<_>>();
+    assert_eq!(order, vec![1, 0]);
+}
+
+#[test]
+fn test_rtl_visual_text() {
+    let resources = font_resources();
+    let cache = resources.fonts();
+    let font = cache.get_default_font_with_size(12.0).unwrap();
+    let shaped = RefCell::new(vec![]);
+    let shape = |text: &str| {
+        shaped.borrow_mut().push(text.to_string());
+        cache.shape_text_h(&font, text)
+    };
+
+    // Right-to-left segments are shaped in visual order: marks stay after
+    // their base, paired punctuation is mirrored, and Arabic letters take
+    // the forms they have when joined, lam and alef making a ligature.
+    let text = "\u{5E9}\u{5C1}\u{5DC}\u{5D5}\u{5DD} (\u{644}\u{627}) \u{628}\u{628}\u{628}";
+    let layout = TextLayout::new(text, LayoutReflowDirection::Inherit, LayoutWhiteSpace::Normal, shape);
+    assert!(layout.segments().iter().all(|v| v.is_rtl()));
+    assert_eq!(
+        *shaped.borrow(),
+        vec![
+            " ".to_string(),
+            "\u{5DD}\u{5D5}\u{5DC}\u{5E9}\u{5C1}".to_string(),
+            "(\u{FEFB})".to_string(),
+            "\u{FE90}\u{FE92}\u{FE91}".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_text_overflow() {
+    let resources = font_resources();
+    let text = "one two three four five six seven eight nine ten";
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let full = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart), FlexStyle::Width(StyleUnit::Point(100.0.into()))]);
+    let clamped = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart), FlexStyle::Width(StyleUnit::Point(100.0.into()))]);
+
+    tree.nodes[clamped].set_line_clamp(&resources, Some(2));
+    tree.nodes[clamped].set_text_overflow(&resources, LayoutTextOverflow::Ellipsis);
+    tree.nodes[full].measure_self_as_spans(&resources, &[LayoutTextSpan::new(text, 12.0)]);
+    tree.nodes[clamped].measure_self_as_spans(&resources, &[LayoutTextSpan::new(text, 12.0)]);
+    tree.reflow(1000, 1000);
+
+    let full_lines = tree.nodes[full].get_line_boxes();
+    let clamped_lines = tree.nodes[clamped].get_line_boxes();
+    assert!(full_lines.len() > 2);
+    assert_eq!(clamped_lines.len(), 2);
+
+    let line_height = full_lines[0].rect.size.height;
+    let clamped_rect = tree.nodes[clamped].get_local_bounding_client_rect_f();
+    assert!((clamped_rect.size.height - line_height * 2.0).abs() < 0.01);
+
+    // Only the last visible line ends with an ellipsis, and it still fits.
+    assert!(tree.nodes[clamped].get_text_layout().ellipsis().is_some());
+    assert_eq!(clamped_lines[0].ellipsis_origin, None);
+    let (ellipsis_left, _) = clamped_lines[1].ellipsis_origin.unwrap();
+    let ellipsis_width = tree.nodes[clamped].get_text_layout().ellipsis().width_f().unwrap();
+    assert!(ellipsis_left + ellipsis_width <= clamped_rect.position.left + 100.0 + 0.01);
+
+    // Renderers only get the runs of the visible lines.
+    {
+        let shaped_text = tree.nodes[clamped].get_shaped_text();
+        let drawn = shaped_text.line_boxes().iter().map(|v| v.runs.len()).sum::<usize>();
+        assert!(drawn < shaped_text.layout().segments().len());
+        assert!(shaped_text.ellipsis().is_some());
+    }
+
+    // The clamp also applies to text laid out inline.
+    let container = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart), FlexStyle::Width(StyleUnit::Point(100.0.into()))]);
+    let inline = tree.append(container, vec![]);
+    tree.nodes[container].set_flow_layout(true);
+    tree.nodes[inline].set_flow_display(LayoutFlowDisplay::Inline);
+    tree.nodes[inline].set_line_clamp(&resources, Some(2));
+    tree.nodes[inline].set_text_overflow(&resources, LayoutTextOverflow::Ellipsis);
+    tree.nodes[inline].measure_self_as_spans(&resources, &[LayoutTextSpan::new(text, 12.0)]);
+    tree.reflow(1000, 1000);
+
+    assert!(tree.nodes[inline].get_text_fragments().len() > 2);
+    let inline_lines = tree.nodes[inline].get_line_boxes();
+    assert_eq!(inline_lines.len(), 2);
+    assert_eq!(inline_lines[0].ellipsis_origin, None);
+    assert!(inline_lines[1].ellipsis_origin.is_some());
+    assert_eq!(tree.nodes[inline].get_shaped_text().line_boxes().len(), 2);
+
+    // Clamping text that was already measured takes effect right away.
+    tree.nodes[full].set_text_overflow(&resources, LayoutTextOverflow::Ellipsis);
+    tree.nodes[full].set_line_clamp(&resources, Some(1));
+    tree.reflow(1000, 1000);
+    let full_lines = tree.nodes[full].get_line_boxes();
+    assert_eq!(full_lines.len(), 1);
+    assert!(full_lines[0].ellipsis_origin.is_some());
+}
+
+#[test]
+fn test_white_space() {
+    assert_eq!(
+        process_white_space(&["a  b\n\tc ", " d"], LayoutWhiteSpace::Normal),
+        vec!["a b c ".to_string(), "d".to_string()]
+    );
+    assert_eq!(
+        process_white_space(&["a\tb\n  c"], LayoutWhiteSpace::Pre),
+        vec!["a       b\n  c".to_string()]
+    );
+    assert_eq!(
+        process_white_space(&["a  \n  b   c"], LayoutWhiteSpace::PreLine),
+        vec!["a\nb c".to_string()]
+    );
+    assert_eq!(process_white_space(&["  foo"], LayoutWhiteSpace::Normal), vec!["foo".to_string()]);
+    assert_eq!(
+        process_white_space(&[" \t", "\n foo"], LayoutWhiteSpace::NoWrap),
+        vec!["".to_string(), "foo".to_string()]
+    );
+    assert_eq!(process_white_space(&["  foo"], LayoutWhiteSpace::PreLine), vec!["foo".to_string()]);
+    assert_eq!(process_white_space(&["  foo"], LayoutWhiteSpace::Pre), vec!["  foo".to_string()]);
+
+    let resources = font_resources();
+    let text = "one two three\nfour five six seven";
+
+    let mut tree = LayoutTree::new(absolute(0.0, 0.0, 1000.0, 1000.0));
+    let white_spaces = [LayoutWhiteSpace::Normal, LayoutWhiteSpace::NoWrap, LayoutWhiteSpace::Pre, LayoutWhiteSpace::PreLine];
+    let nodes = white_spaces
+        .iter()
+        .map(|white_space| {
+            let node = tree.append(0, vec![FlexStyle::AlignSelf(Align::FlexStart), FlexStyle::Width(StyleUnit::Point(60.0.into()))]);
+            tree.nodes[node].set_white_space(&resources, *white_space);
+            tree.nodes[node].measure_self_as_spans(&resources, &[LayoutTextSpan::new(text, 12.0)]);
+            node
+        })
+        .collect::<Vec<_>>();
+    tree.reflow(1000, 1000);
+
+    let line_counts = nodes.iter().map(|v| tree.nodes[*v].get_text_lines().len()).collect::<Vec<_>>();
+    let (normal, no_wrap, pre, pre_line) = (line_counts[0], line_counts[1], line_counts[2], line_counts[3]);
+
+    // Newlines only break lines when preserved, and only some modes wrap.
+    assert!(normal > 2);
+    assert_eq!(no_wrap, 1);
+    assert_eq!(pre, 2);
+    assert!(pre_line > 1);
+    let pre_line_lines = tree.nodes[nodes[3]].get_text_lines();
+    assert!(pre_line_lines.iter().any(|v| v.segments.end == 3));
+
+    let nodes_height = |node: usize| tree.nodes[node].get_local_bounding_client_rect_f().size.height;
+    assert!(nodes_height(nodes[1]) < nodes_height(nodes[2]));
+}